@@ -1,21 +1,46 @@
 // vim: tw=80
 use std::{
+    fmt,
     io::{self, IoSlice, IoSliceMut},
-    os::unix::io::{AsRawFd, BorrowedFd, RawFd},
+    mem::{self, MaybeUninit},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
     pin::Pin,
+    ptr,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
 };
 
 use mio::{event, Interest, Registry, Token};
-pub use nix::sys::aio::AioFsyncMode;
+pub use nix::sys::aio::{
+    AioCancelStat,
+    AioFsync,
+    AioFsyncMode,
+    AioRead,
+    AioReadv,
+    AioWrite,
+    AioWritev,
+};
 use nix::{
-    libc::off_t,
+    errno::Errno,
+    libc::{self, off_t},
     sys::{
-        aio::{self, Aio},
-        event::EventFlag,
+        aio::{self, Aio, LioMode},
+        event::{EventFilter, EventFlag, FilterFlag, KEvent, Kqueue},
         signal::SigevNotify,
+        time::TimeSpec,
     },
 };
 
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::{
+    capabilities::{set_aio_unavailable, FallbackPolicy},
+    error::{fsize_limit, misuse, AioError, FileTooLarge, SubmitError},
+    priority::AioPriority,
+    quota::Permit,
+    sendfile::{trigger, NEXT_IDENT},
+};
+
 /// Return type of [`Source::read_at`]
 pub type ReadAt<'a> = Source<aio::AioRead<'a>>;
 /// Return type of [`Source::readv_at`]
@@ -27,28 +52,454 @@ pub type WriteAt<'a> = Source<aio::AioWrite<'a>>;
 /// Return type of [`Source::writev_at`]
 pub type WritevAt<'a> = Source<aio::AioWritev<'a>>;
 
+mod private {
+    use nix::{libc, sys::aio::Aio};
+
+    use super::Opcode;
+
+    /// Operation-specific details that `nix`'s `Aio` trait doesn't expose.
+    pub trait AioOp: Aio + AsMut<libc::aiocb> + AsRef<libc::aiocb> {
+        /// Which kind of operation this is.
+        const OPCODE: Opcode;
+
+        /// How many bytes the operation will attempt to transfer, if any.
+        ///
+        /// # Safety
+        ///
+        /// For a vectored operation, the iovec array must still be valid.
+        /// It's only required to live until submission, so [`Source`]
+        /// calls this once, at construction, and caches the result.
+        ///
+        /// [`Source`]: super::Source
+        unsafe fn nbytes(&self) -> Option<usize>;
+
+        /// How many bytes were transferred, given `aio_return`'s result.
+        fn transferred(output: &Self::Output) -> Option<usize>;
+
+        /// Perform the operation synchronously, for when AIO is unavailable.
+        fn emulate(&self) -> nix::Result<Self::Output>;
+    }
+}
+pub(crate) use private::AioOp;
+
+/// Sum the lengths of the iovecs referenced by a vectored aiocb.
+///
+/// # Safety
+///
+/// The iovec array must still be valid.
+unsafe fn iovecs_len(aiocb: &libc::aiocb) -> usize {
+    let iovs = unsafe {
+        std::slice::from_raw_parts(
+            aiocb.aio_buf as *const libc::iovec,
+            aiocb.aio_nbytes,
+        )
+    };
+    iovs.iter().map(|iov| iov.iov_len).sum()
+}
+
+/// Borrow the contents of a `Vec` that an operation will own.
+///
+/// Operations that take ownership of their buffers store them as `Vec`s rather
+/// than `Box`es, because moving a `Box` would assert unique ownership of data
+/// that the kernel is still accessing.  A `Vec`'s heap allocation doesn't move
+/// even if the `Vec` itself does.
+///
+/// # Safety
+///
+/// The `Vec` must be neither dropped nor resized until the operation that uses
+/// the returned slice has completed.
+pub(crate) unsafe fn owned_slice<'a, T>(v: &[T]) -> &'a [T] {
+    unsafe { std::slice::from_raw_parts(v.as_ptr(), v.len()) }
+}
+
+/// Like [`owned_slice`], but mutable.
+///
+/// # Safety
+///
+/// Same as [`owned_slice`].  Additionally, the `Vec`'s contents must not be
+/// accessed until the operation has completed.
+pub(crate) unsafe fn owned_slice_mut<'a, T>(v: &mut [T]) -> &'a mut [T] {
+    unsafe { std::slice::from_raw_parts_mut(v.as_mut_ptr(), v.len()) }
+}
+
+/// Shorten a scatter/gather list so that it covers at most `max` bytes.
+///
+/// The iovec that reaches the cap is shortened.  Returns how many iovecs
+/// should be kept, which is always at least one, even if it's empty.
+fn cap_iovecs(iovs: &mut [libc::iovec], max: usize) -> usize {
+    let mut remaining = max;
+    for (i, iov) in iovs.iter_mut().enumerate() {
+        if iov.iov_len >= remaining {
+            iov.iov_len = remaining;
+            return i + 1;
+        }
+        remaining -= iov.iov_len;
+    }
+    iovs.len()
+}
+
+/// Convert a file offset to `off_t`, failing with `EOVERFLOW` if it won't fit.
+pub(crate) fn checked_offset(offs: u64) -> nix::Result<off_t> {
+    off_t::try_from(offs).map_err(|_| Errno::EOVERFLOW)
+}
+
+/// Call `aio_return`, retrying if the syscall itself is interrupted.
+pub(crate) fn aio_return_nointr<T>(
+    mut op: Pin<&mut T>,
+) -> nix::Result<T::Output>
+where
+    T: Aio + AsRef<libc::aiocb>,
+{
+    loop {
+        match op.as_mut().aio_return() {
+            // EINTR could be the operation's own result, or it could mean that
+            // the syscall was interrupted.  Only in the latter case will the
+            // operation still be unreaped with a successful status.
+            Err(Errno::EINTR) if raw_aio_error(&*op) == 0 => continue,
+            r => return r,
+        }
+    }
+}
+
+/// Call `aio_cancel`, retrying if the syscall itself is interrupted.
+fn cancel_nointr<T: Aio>(mut op: Pin<&mut T>) -> nix::Result<CancelStat> {
+    loop {
+        match op.as_mut().cancel() {
+            Err(Errno::EINTR) => continue,
+            r => return r.map(CancelStat::from),
+        }
+    }
+}
+
+/// Cancel an in-progress operation, block until it's finished, and reap it.
+pub(crate) fn cancel_and_reap<T>(mut op: Pin<&mut T>) -> nix::Result<T::Output>
+where
+    T: Aio + AsRef<libc::aiocb>,
+{
+    let _ = cancel_nointr(op.as_mut());
+    while error_nointr(&*op) == Err(Errno::EINPROGRESS) {
+        let _ = aio::aio_suspend(&[&*op], None);
+    }
+    aio_return_nointr(op)
+}
+
+/// Call `aio_error`, retrying if the syscall itself is interrupted.
+///
+/// Unlike `Aio::error`, this can distinguish an interrupted syscall from an
+/// operation that failed with `EINTR`.
+fn error_nointr<T: AsRef<libc::aiocb>>(op: &T) -> nix::Result<()> {
+    loop {
+        match raw_aio_error(op) {
+            0 => return Ok(()),
+            -1 if Errno::last() == Errno::EINTR => continue,
+            -1 => return Err(Errno::last()),
+            e => return Err(Errno::from_raw(e)),
+        }
+    }
+}
+
+/// The kqueue that an aiocb will notify upon completion, if any.
+fn sigev_kq(aiocb: &libc::aiocb) -> Option<RawFd> {
+    let sigev = &aiocb.aio_sigevent;
+    if sigev.sigev_notify == libc::SIGEV_KEVENT {
+        // sigev_notify_kqueue is an alias for sigev_signo
+        Some(sigev.sigev_signo)
+    } else {
+        None
+    }
+}
+
+/// Check that an operation may be registered with the given kqueue.
+///
+/// Once an operation is in progress, the kernel has already copied its
+/// sigevent, so registering it with a different kqueue would have no effect.
+/// The completion notification would silently go to the old kqueue instead.
+fn check_kq(
+    aiocb: &libc::aiocb,
+    in_progress: bool,
+    kq: RawFd,
+) -> io::Result<()> {
+    if in_progress && sigev_kq(aiocb) != Some(kq) {
+        Err(Errno::EBUSY.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that an operation may be reregistered with the given token.
+///
+/// Like the kqueue, the token is copied by the kernel at submission, and
+/// FreeBSD provides no way to change an AIO event's udata afterwards.
+fn check_token(
+    aiocb: &libc::aiocb,
+    in_progress: bool,
+    udata: usize,
+) -> io::Result<()> {
+    let current = aiocb.aio_sigevent.sigev_value.sival_ptr as usize;
+    if in_progress && current != udata {
+        Err(Errno::EBUSY.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Add or delete a nanosecond `EVFILT_TIMER` event.
+fn deadline_timer(
+    kq: RawFd,
+    ident: usize,
+    flags: u16,
+    nanos: libc::intptr_t,
+    udata: usize,
+) -> nix::Result<()> {
+    // Safe because every field of a kevent may be zero.
+    let mut kev: libc::kevent = unsafe { mem::zeroed() };
+    kev.ident = ident;
+    kev.filter = libc::EVFILT_TIMER;
+    kev.flags = flags;
+    kev.fflags = libc::NOTE_NSECONDS;
+    kev.data = nanos as _;
+    kev.udata = udata as *mut libc::c_void;
+    // Safe because we pass exactly one change and no events.
+    let r =
+        unsafe { libc::kevent(kq, &kev, 1, ptr::null_mut(), 0, ptr::null()) };
+    Errno::result(r).map(drop)
+}
+
+/// Check that an operation is being registered with AIO interest.
+pub(crate) fn check_interests(interests: Interest) -> io::Result<()> {
+    if interests.is_aio() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "AIO operations must be registered with Interest::AIO",
+        ))
+    }
+}
+
+/// Interpret the result of reaping an operation, for
+/// [`SourceApi::bytes_transferred`].
+pub(crate) fn transferred<O>(
+    r: Result<O, AioError>,
+    count: impl FnOnce(O) -> usize,
+) -> Option<usize> {
+    match r.map_err(AioError::errno) {
+        Ok(output) => Some(count(output)),
+        Err(Errno::ECANCELED) => Some(0),
+        Err(_) => None,
+    }
+}
+
+/// Like [`transferred`], but for two-stage operations whose first stage had
+/// already written `written` bytes, if it had finished.
+fn staged_transferred(
+    r: Result<usize, AioError>,
+    written: Option<usize>,
+) -> Option<usize> {
+    match r.map_err(AioError::errno) {
+        Err(Errno::EINPROGRESS) => None,
+        Err(Errno::ECANCELED) => Some(written.unwrap_or(0)),
+        Err(_) => written,
+        Ok(n) => Some(n),
+    }
+}
+
+pub(crate) fn raw_aio_error<T: AsRef<libc::aiocb>>(op: &T) -> libc::c_int {
+    let aiocb: &libc::aiocb = op.as_ref();
+    // Safe because aio_error doesn't modify the aiocb.
+    unsafe { libc::aio_error(aiocb) }
+}
+
+impl<'a> AioOp for aio::AioFsync<'a> {
+    const OPCODE: Opcode = Opcode::Fsync;
+
+    unsafe fn nbytes(&self) -> Option<usize> {
+        None
+    }
+
+    fn transferred(_output: &()) -> Option<usize> {
+        None
+    }
+
+    fn emulate(&self) -> nix::Result<()> {
+        let fd = self.as_ref().aio_fildes;
+        // Safe because neither function touches any memory.
+        let r = match self.mode() {
+            AioFsyncMode::O_DSYNC => unsafe { libc::fdatasync(fd) },
+            _ => unsafe { libc::fsync(fd) },
+        };
+        Errno::result(r).map(drop)
+    }
+}
+
+impl<'a> AioOp for aio::AioRead<'a> {
+    const OPCODE: Opcode = Opcode::Read;
+
+    unsafe fn nbytes(&self) -> Option<usize> {
+        Some(aio::AioRead::nbytes(self))
+    }
+
+    fn transferred(output: &usize) -> Option<usize> {
+        Some(*output)
+    }
+
+    fn emulate(&self) -> nix::Result<usize> {
+        let aiocb: &libc::aiocb = self.as_ref();
+        // Safe because the buffer is borrowed for the operation's lifetime.
+        let r = unsafe {
+            libc::pread(
+                aiocb.aio_fildes,
+                aiocb.aio_buf,
+                aiocb.aio_nbytes,
+                aiocb.aio_offset,
+            )
+        };
+        Errno::result(r).map(|n| n as usize)
+    }
+}
+
+impl<'a> AioOp for aio::AioReadv<'a> {
+    const OPCODE: Opcode = Opcode::Readv;
+
+    unsafe fn nbytes(&self) -> Option<usize> {
+        Some(unsafe { iovecs_len(self.as_ref()) })
+    }
+
+    fn transferred(output: &usize) -> Option<usize> {
+        Some(*output)
+    }
+
+    fn emulate(&self) -> nix::Result<usize> {
+        let aiocb: &libc::aiocb = self.as_ref();
+        // Safe because the iovecs and their buffers are borrowed for the
+        // operation's lifetime.
+        let r = unsafe {
+            libc::preadv(
+                aiocb.aio_fildes,
+                aiocb.aio_buf as *const libc::iovec,
+                aiocb.aio_nbytes as libc::c_int,
+                aiocb.aio_offset,
+            )
+        };
+        Errno::result(r).map(|n| n as usize)
+    }
+}
+
+impl<'a> AioOp for aio::AioWrite<'a> {
+    const OPCODE: Opcode = Opcode::Write;
+
+    unsafe fn nbytes(&self) -> Option<usize> {
+        Some(aio::AioWrite::nbytes(self))
+    }
+
+    fn transferred(output: &usize) -> Option<usize> {
+        Some(*output)
+    }
+
+    fn emulate(&self) -> nix::Result<usize> {
+        let aiocb: &libc::aiocb = self.as_ref();
+        // Safe because the buffer is borrowed for the operation's lifetime.
+        let r = unsafe {
+            libc::pwrite(
+                aiocb.aio_fildes,
+                aiocb.aio_buf,
+                aiocb.aio_nbytes,
+                aiocb.aio_offset,
+            )
+        };
+        Errno::result(r).map(|n| n as usize)
+    }
+}
+
+impl<'a> AioOp for aio::AioWritev<'a> {
+    const OPCODE: Opcode = Opcode::Writev;
+
+    unsafe fn nbytes(&self) -> Option<usize> {
+        Some(unsafe { iovecs_len(self.as_ref()) })
+    }
+
+    fn transferred(output: &usize) -> Option<usize> {
+        Some(*output)
+    }
+
+    fn emulate(&self) -> nix::Result<usize> {
+        let aiocb: &libc::aiocb = self.as_ref();
+        // Safe because the iovecs and their buffers are borrowed for the
+        // operation's lifetime.
+        let r = unsafe {
+            libc::pwritev(
+                aiocb.aio_fildes,
+                aiocb.aio_buf as *const libc::iovec,
+                aiocb.aio_nbytes as libc::c_int,
+                aiocb.aio_offset,
+            )
+        };
+        Errno::result(r).map(|n| n as usize)
+    }
+}
+
 /// Common methods supported by all POSIX AIO Mio sources
+///
+/// Each method's errors are tagged with its phase of the operation's life
+/// cycle: [`AioError::Submit`] from [`SourceApi::submit`],
+/// [`AioError::Cancel`] from [`SourceApi::cancel`], and [`AioError::Return`]
+/// from [`SourceApi::error`] and [`SourceApi::aio_return`].
 pub trait SourceApi {
     /// Return type of [`SourceApi::aio_return`].
     type Output;
 
     /// Read the final result of the operation
-    fn aio_return(self: Pin<&mut Self>) -> nix::Result<Self::Output>;
+    ///
+    /// If the `aio_return` syscall is interrupted by a signal, it will be
+    /// retried.  But an operation that itself failed with `EINTR` will still
+    /// report that error.
+    fn aio_return(self: Pin<&mut Self>) -> Result<Self::Output, AioError>;
+
+    /// Reap a finished operation, reporting only how many bytes it
+    /// transferred.
+    ///
+    /// This is an alternative to [`SourceApi::aio_return`] for callers that
+    /// need to know how much data moved even if the operation failed.  An
+    /// operation that was canceled transferred nothing, because FreeBSD only
+    /// cancels operations that haven't yet started, and operations that were
+    /// interrupted partway report a short count rather than an error.  But
+    /// for other errors the kernel discards the partial count, so this
+    /// returns `None`.  It also returns `None` if the operation is still in
+    /// progress.
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize>;
 
     /// Ask the operating system to cancel the operation
     ///
     /// Most file systems on most operating systems don't actually support
-    /// cancellation; they'll just return `AIO_NOTCANCELED`.
-    fn cancel(self: Pin<&mut Self>) -> nix::Result<aio::AioCancelStat>;
+    /// cancellation; they'll just return `AIO_NOTCANCELED`.  If the syscall is
+    /// interrupted by a signal, it will be retried.
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError>;
 
     /// Retrieve the status of an in-progress or complete operation.
     ///
     /// Not usually needed, since `mio_aio` always uses kqueue for notification.
-    fn error(self: Pin<&mut Self>) -> nix::Result<()>;
+    /// If the `aio_error` syscall is interrupted by a signal, it will be
+    /// retried.  Once the operation is complete, its status is cached, so
+    /// polling this method in a loop only enters the kernel until completion.
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError>;
 
     /// Does this operation currently have any in-kernel state?
     fn in_progress(&self) -> bool;
 
+    /// Check whether the operation is done, without a reactor.
+    ///
+    /// This is like [`SourceApi::error`], but it distinguishes an operation
+    /// that's still running from one that failed.  It's intended for
+    /// embedders with their own event loops.  An operation that was never
+    /// submitted is misused, and reports `Failed(EINVAL)`.
+    fn poll_state(self: Pin<&mut Self>) -> AioState {
+        match self.error().map_err(AioError::errno) {
+            Ok(()) => AioState::Ready,
+            Err(Errno::EINPROGRESS) => AioState::Pending,
+            Err(e) => AioState::Failed(e),
+        }
+    }
+
     /// Extra registration method needed by Tokio
     #[cfg(feature = "tokio")]
     #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
@@ -63,7 +514,230 @@ pub trait SourceApi {
     ///
     /// After calling this method and until [`SourceApi::aio_return`] returns
     /// `Ok`, the structure may not be moved in memory.
-    fn submit(self: Pin<&mut Self>) -> nix::Result<()>;
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError>;
+
+    /// Like [`SourceApi::submit`], but distinguishes a full queue from other
+    /// errors.
+    ///
+    /// If the system's AIO queue is full, this fails with
+    /// [`SubmitError::WouldBlock`], and the operation remains idle.  So it's
+    /// safe to submit the same pinned operation again later, for example
+    /// after another operation completes.
+    fn try_submit(self: Pin<&mut Self>) -> Result<(), SubmitError> {
+        self.submit().map_err(|e| SubmitError::from(e.errno()))
+    }
+}
+
+/// A [`SourceApi`] that can also be registered with a reactor, as a trait
+/// object.
+///
+/// `Source<AioRead>` and `Source<AioWrite>` are different types, so a
+/// collection of in-flight operations of mixed types must box them.  Every
+/// operation whose [`SourceApi::Output`] is a byte count implements this
+/// trait, so they can all be stored as `Pin<Box<dyn DynSource>>`, and still
+/// be registered, submitted, and reaped.  Get one with [`Source::into_dyn`],
+/// or by boxing any such operation yourself.
+pub trait DynSource: SourceApi<Output = usize> + event::Source {}
+
+impl<S: SourceApi<Output = usize> + event::Source + ?Sized> DynSource for S {}
+
+/// The result of [`SourceApi::poll_state`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AioState {
+    /// The operation is still in progress.
+    Pending,
+    /// The operation succeeded, and is ready to be reaped.
+    Ready,
+    /// The operation failed, and is ready to be reaped.
+    Failed(Errno),
+}
+
+/// The result of [`SourceApi::cancel`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CancelStat {
+    /// The operation was canceled.
+    AioCanceled,
+    /// The operation could not be canceled, and is still in progress.
+    AioNotCanceled,
+    /// The operation had already completed.
+    AioAllDone,
+}
+
+impl CancelStat {
+    /// Was the operation canceled?
+    pub fn is_canceled(&self) -> bool {
+        *self == CancelStat::AioCanceled
+    }
+
+    /// Had the operation already completed before it could be canceled?
+    pub fn is_all_done(&self) -> bool {
+        *self == CancelStat::AioAllDone
+    }
+
+    /// Must the caller still wait for the operation to complete?
+    ///
+    /// If so, the kernel may still be using the operation's buffers, so they
+    /// must not be freed until the operation's completion has been reaped.
+    pub fn must_wait(&self) -> bool {
+        *self == CancelStat::AioNotCanceled
+    }
+}
+
+impl fmt::Display for CancelStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CancelStat::AioCanceled => "operation canceled",
+            CancelStat::AioNotCanceled => "operation not canceled",
+            CancelStat::AioAllDone => "operation already complete",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::error::Error for CancelStat {}
+
+impl From<aio::AioCancelStat> for CancelStat {
+    fn from(stat: aio::AioCancelStat) -> Self {
+        match stat {
+            aio::AioCancelStat::AioCanceled => CancelStat::AioCanceled,
+            aio::AioCancelStat::AioNotCanceled => CancelStat::AioNotCanceled,
+            aio::AioCancelStat::AioAllDone => CancelStat::AioAllDone,
+        }
+    }
+}
+
+/// How an operation ended, as reported by [`Source::cancel_and_wait`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CancelOutcome {
+    /// The operation was canceled before it did anything.
+    Canceled,
+    /// The operation couldn't be canceled, or had already finished, and it
+    /// succeeded.
+    Completed,
+    /// The operation couldn't be canceled, or had already finished, and it
+    /// failed with this error.
+    Failed(Errno),
+}
+
+/// How the kqueue delivers an operation's completion event.
+///
+/// Set with [`Source::set_notify_mode`], or the same method of a composite
+/// operation such as [`WriteThenFsync`], before registration.  Whatever the
+/// mode, each submission arms a fresh event, so an operation that's reaped,
+/// reset, and resubmitted will notify again without being re-registered.
+/// The modes differ in what happens between completion and reaping.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum NotifyMode {
+    /// `EV_ONESHOT`: the event is delivered once, and then deleted.  This is
+    /// safe when the thread that polls isn't the one that reaps.
+    #[default]
+    Oneshot,
+    /// `EV_CLEAR`: the event is delivered once, but stays attached to the
+    /// operation until it's reaped.
+    Clear,
+    /// `EV_DISPATCH`: the event is delivered once, and then disabled, but
+    /// stays attached to the operation until it's reaped.
+    ///
+    /// For most kqueue filters, a disabled event can be cheaply re-enabled,
+    /// which is what makes `EV_DISPATCH` attractive.  But an AIO event is
+    /// never re-enabled: each submission arms a fresh one regardless.  So for
+    /// AIO, this behaves like [`NotifyMode::Clear`], except that the event
+    /// can't be delivered twice even if the kernel posts it again.
+    Dispatch,
+    /// Level-triggered: the event is delivered by every poll, until the
+    /// operation is reaped.
+    Level,
+}
+
+impl NotifyMode {
+    fn flags(self) -> EventFlag {
+        match self {
+            NotifyMode::Oneshot => EventFlag::EV_ONESHOT,
+            NotifyMode::Clear => EventFlag::EV_CLEAR,
+            NotifyMode::Dispatch => EventFlag::EV_DISPATCH,
+            NotifyMode::Level => EventFlag::empty(),
+        }
+    }
+}
+
+/// The lifecycle state of a [`Source`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum State {
+    /// Not in progress.  Either not yet submitted, or already reaped.
+    Idle,
+    /// Submitted and not yet reaped.
+    InProgress,
+    /// Finished, as observed by [`SourceApi::error`], but not yet reaped by
+    /// [`SourceApi::aio_return`].
+    Completed,
+    /// Aborted by [`Source::abort_pending`] before submission.
+    Aborted,
+}
+
+/// How an operation finished, according to its completion event.
+///
+/// Returned by [`Source::completion_status`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CompletionStatus {
+    /// The operation succeeded.
+    Succeeded,
+    /// The operation failed, or was canceled.
+    Failed(Errno),
+    /// The event doesn't belong to this operation.
+    Unrelated,
+}
+
+/// The kind of I/O that an operation performs.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Opcode {
+    /// [`Source::fsync`]
+    Fsync,
+    /// [`Source::read_at`]
+    Read,
+    /// [`Source::readv_at`]
+    Readv,
+    /// [`Source::write_at`]
+    Write,
+    /// [`Source::writev_at`]
+    Writev,
+}
+
+impl Opcode {
+    /// Does this operation read from a file?
+    pub fn is_read(&self) -> bool {
+        matches!(self, Opcode::Read | Opcode::Readv)
+    }
+
+    /// Does this operation write to a file?
+    pub fn is_write(&self) -> bool {
+        matches!(self, Opcode::Write | Opcode::Writev)
+    }
+
+    /// Does this operation sync a file to stable storage?
+    pub fn is_sync(&self) -> bool {
+        matches!(self, Opcode::Fsync)
+    }
+}
+
+/// A description of an operation, as passed to [`Source::submit_observed`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct OpDescriptor {
+    /// The operation's file descriptor
+    pub fd:        RawFd,
+    /// The file offset.  Always 0 for fsync.
+    pub offset:    u64,
+    /// How many bytes the operation will attempt to transfer.  Always 0 for
+    /// fsync.
+    pub len:       usize,
+    /// The kind of operation
+    pub opcode:    Opcode,
+    /// The operation's priority
+    pub priority:  i32,
+    /// When the operation was submitted
+    pub timestamp: Instant,
 }
 
 /// A Mio source based on a single POSIX AIO operation.
@@ -71,14 +745,222 @@ pub trait SourceApi {
 /// The generic parameter specifies exactly which operation it is.  This struct
 /// implements `mio::Source`.  After creation, use `mio::Source::register` to
 /// connect it to the event loop.
-#[derive(Debug)]
-pub struct Source<T> {
-    inner: T,
+///
+/// # Registration
+///
+/// Register with [`Interest::AIO`]; any other interest fails with
+/// `InvalidInput`.  An operation that's already in progress can't be
+/// registered with a different reactor, and fails with `EBUSY`.  The kernel
+/// copies the token when the operation is submitted, so `reregister` can only
+/// change it while the operation is idle.  Reregistering an in-progress
+/// operation with its current reactor and token is allowed, but does nothing.
+/// The composite operations, such as [`WriteThenFsync`], follow the same
+/// rules.
+///
+/// # Reuse
+///
+/// A completed operation may be submitted again.  `set_fd` rebinds it to a
+/// different file, and the `reset_*` methods, such as
+/// [`Source::reset_read_at`], re-arm it with new arguments.  Either way the
+/// existing allocation is reused and the kqueue registration is unaffected, so
+/// it's cheaper than constructing a new `Source`, and a single registered
+/// `Source` may be reused for many files.  These methods fail with `EBUSY` if
+/// the operation is still in progress, and the ones that take an offset fail
+/// with `EOVERFLOW` if it doesn't fit in an `off_t`.
+///
+/// # Dropping
+///
+/// If a `Source` is dropped while its operation is still in progress, the
+/// kernel could still be using its buffer.  So `Drop` will first try to cancel
+/// the operation, and then block until the kernel is done with it.  Since
+/// most file systems don't support cancellation, that might take as long as
+/// the operation itself would.
+pub struct Source<T: AioOp> {
+    inner:       T,
+    /// How many bytes the operation will attempt to transfer, if any.
+    /// Cached, because a vectored operation's iovec array needn't outlive
+    /// submission.
+    len:         Option<usize>,
+    /// Cached terminal result of `aio_error`
+    status:      Option<nix::Result<()>>,
+    /// Bytes transferred, as reported by the last successful `aio_return`
+    returned:    Option<usize>,
+    /// Set by `abort_pending`
+    aborted:     bool,
+    /// The kqueue holding a timer armed by `cancel_after`, if any
+    deadline_kq: Option<RawFd>,
+    /// Set by `submit_all`, since `nix` doesn't know about `lio_listio`
+    listed:      bool,
+    notify_mode: NotifyMode,
+    /// Held from `submit_with_permit` until the operation is reaped
+    permit:      Option<Permit>,
+    /// Result of an operation that `submit` performed synchronously, per the
+    /// `FallbackPolicy`, until it's reaped
+    emulated:    Option<nix::Result<T::Output>>,
+    #[cfg(feature = "metrics")]
+    stopwatch:   metrics::Stopwatch,
 }
-impl<T: Aio> Source<T> {
+impl<T: AioOp> Source<T> {
     pin_utils::unsafe_pinned!(inner: T);
 
+    pin_utils::unsafe_unpinned!(len: Option<usize>);
+
+    pin_utils::unsafe_unpinned!(status: Option<nix::Result<()>>);
+
+    pin_utils::unsafe_unpinned!(returned: Option<usize>);
+
+    pin_utils::unsafe_unpinned!(aborted: bool);
+
+    pin_utils::unsafe_unpinned!(listed: bool);
+
+    pin_utils::unsafe_unpinned!(permit: Option<Permit>);
+
+    pin_utils::unsafe_unpinned!(emulated: Option<nix::Result<T::Output>>);
+
+    #[cfg(feature = "metrics")]
+    pin_utils::unsafe_unpinned!(stopwatch: metrics::Stopwatch);
+
+    fn new(inner: T) -> Self {
+        // Safe because every constructor calls this while the iovec array, if
+        // any, is still valid, and from_inner requires the same of its caller.
+        let len = unsafe { inner.nbytes() };
+        Source {
+            inner,
+            len,
+            status: None,
+            returned: None,
+            aborted: false,
+            deadline_kq: None,
+            listed: false,
+            notify_mode: NotifyMode::default(),
+            permit: None,
+            emulated: None,
+            #[cfg(feature = "metrics")]
+            stopwatch: metrics::Stopwatch::default(),
+        }
+    }
+
+    /// Abort an operation that has not yet been submitted.
+    ///
+    /// Afterwards, [`SourceApi::submit`] will do nothing but fail with
+    /// `ECANCELED`.  This is cheaper than dropping the `Source` and is useful
+    /// for schedulers that may change their plans between building operations
+    /// and submitting them.  Fails with [`AioError::Cancel`]`(EBUSY)` if the
+    /// operation is already in progress.
+    pub fn abort_pending(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress() {
+            return Err(AioError::Cancel(misuse(
+                Errno::EBUSY,
+                "abort_pending while in progress",
+            )));
+        }
+        *self.aborted() = true;
+        Ok(())
+    }
+
+    /// Wrap an already-constructed `nix` AIO operation.
+    ///
+    /// This is an escape hatch for callers who need features of the
+    /// lower-level `nix` API that this crate's constructors don't expose.
+    ///
+    /// # Safety
+    ///
+    /// `inner` must not be in progress.  If it were, its `aio_sigevent` would
+    /// already be baked into the kernel's copy of the aiocb, and any later
+    /// registration with `mio` would silently have no effect.  Any sigevent
+    /// configured on `inner` will be overwritten by
+    /// [`event::Source::register`] and [`event::Source::deregister`].
+    ///
+    /// If `inner` is vectored, its `IoSlice` array must still be valid, so
+    /// that the operation's length can be recorded.
+    pub unsafe fn from_inner(inner: T) -> Self {
+        Source::new(inner)
+    }
+
+    /// Report the operation's lifecycle state.
+    pub fn state(&self) -> State {
+        if self.aborted {
+            State::Aborted
+        } else if self.in_progress() {
+            if self.status.is_some() {
+                State::Completed
+            } else {
+                State::InProgress
+            }
+        } else {
+            State::Idle
+        }
+    }
+
+    /// Unwrap the underlying `nix` AIO operation.
+    ///
+    /// Since this consumes an unpinned `Source`, the operation can never be in
+    /// progress.
+    pub fn into_inner(self) -> T {
+        let this = mem::ManuallyDrop::new(self);
+        // Safe because `this` will never be dropped, and its other fields
+        // don't need to be.
+        unsafe { ptr::read(&this.inner) }
+    }
+
+    /// Box and pin the operation, as required for submission.
+    pub fn into_pinned_box(self) -> Pin<Box<Self>> {
+        Box::pin(self)
+    }
+
+    /// Reap a completed operation, and unpin it.
+    ///
+    /// Pinning is only necessary while the kernel owns the operation.  Once
+    /// it's complete, this reaps it with [`SourceApi::aio_return`], and hands
+    /// back the result along with the unpinned `Source`.  That releases its
+    /// borrowed buffers, or it may be resubmitted after pinning it again.
+    ///
+    /// # Errors
+    ///
+    /// - `EINVAL`: the operation isn't in progress, because it was never
+    ///   submitted or was already reaped.
+    /// - `EINPROGRESS`: the operation is still in progress.  It will be
+    ///   dropped, which blocks until it completes.  Check
+    ///   [`SourceApi::error`] first to avoid that.
+    ///
+    /// Otherwise, returns the operation's own error, if it failed.
+    pub fn into_result(
+        mut self: Pin<Box<Self>>,
+    ) -> Result<(T::Output, Self), AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "into_result before submit",
+            )));
+        }
+        if self.as_mut().error() == Err(AioError::Return(Errno::EINPROGRESS)) {
+            return Err(AioError::Return(misuse(
+                Errno::EINPROGRESS,
+                "into_result while in progress",
+            )));
+        }
+        let output = self.as_mut().aio_return()?;
+        // Safe because the operation is no longer in progress, so the kernel
+        // has no pointers to it.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+        Ok((output, *this))
+    }
+
+    /// Register the operation with a Mio reactor, and then box and pin it.
+    ///
+    /// This is shorthand for [`Registry::register`] with [`Interest::AIO`],
+    /// followed by [`Source::into_pinned_box`].
+    pub fn register_pinned(
+        mut self,
+        registry: &Registry,
+        token: Token,
+    ) -> io::Result<Pin<Box<Self>>> {
+        registry.register(&mut self, token, Interest::AIO)?;
+        Ok(self.into_pinned_box())
+    }
+
     fn _deregister_raw(&mut self) {
+        self.disarm_deadline();
         let sigev = SigevNotify::SigevNone;
         self.inner.set_sigev_notify(sigev);
     }
@@ -87,66 +969,755 @@ impl<T: Aio> Source<T> {
         let sigev = SigevNotify::SigevKeventFlags {
             kq,
             udata: udata as isize,
-            flags: EventFlag::EV_ONESHOT,
+            flags: self.notify_mode.flags(),
         };
         self.inner.set_sigev_notify(sigev);
     }
+
+    /// Choose how the kqueue delivers the completion event.
+    ///
+    /// Takes effect at the next registration.  See [`NotifyMode`].
+    pub fn set_notify_mode(&mut self, mode: NotifyMode) {
+        self.notify_mode = mode;
+    }
+
+    /// Choose how the kqueue delivers the completion event, for fluent
+    /// construction.
+    pub fn with_notify_mode(mut self, mode: NotifyMode) -> Self {
+        self.set_notify_mode(mode);
+        self
+    }
 }
 
-impl<T: Aio> SourceApi for Source<T> {
-    type Output = T::Output;
+impl<T: AioOp> Source<T> {
+    /// Get mutable access to the raw aiocb, for rebinding the operation.
+    ///
+    /// Fails with `EBUSY` if the kernel still owns the aiocb.
+    fn aiocb_mut(mut self: Pin<&mut Self>) -> nix::Result<&mut libc::aiocb> {
+        if self.in_progress() {
+            return Err(misuse(Errno::EBUSY, "rebinding while in progress"));
+        }
+        *self.as_mut().returned() = None;
+        // Safe because we don't move the inner operation.
+        let inner = unsafe { self.inner().get_unchecked_mut() };
+        Ok(inner.as_mut())
+    }
 
-    fn aio_return(self: Pin<&mut Self>) -> nix::Result<Self::Output> {
-        self.inner().aio_return()
+    fn _set_fd(self: Pin<&mut Self>, fd: RawFd) -> nix::Result<()> {
+        self.aiocb_mut()?.aio_fildes = fd;
+        Ok(())
     }
 
-    fn cancel(self: Pin<&mut Self>) -> nix::Result<aio::AioCancelStat> {
-        self.inner().cancel()
+    fn _set_offset(self: Pin<&mut Self>, offs: u64) -> nix::Result<()> {
+        let offs = checked_offset(offs)?;
+        self.aiocb_mut()?.aio_offset = offs;
+        Ok(())
     }
 
-    #[cfg(feature = "tokio")]
-    fn deregister_raw(&mut self) {
-        self._deregister_raw()
+    /// Point the operation at a new buffer, or iovec array.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be valid for the operation's lifetime.
+    unsafe fn _reset(
+        mut self: Pin<&mut Self>,
+        offs: u64,
+        buf: *mut libc::c_void,
+        nbytes: usize,
+        prio: i32,
+    ) -> nix::Result<()> {
+        let offs = checked_offset(offs)?;
+        let aiocb = self.as_mut().aiocb_mut()?;
+        aiocb.aio_offset = offs;
+        aiocb.aio_buf = buf;
+        aiocb.aio_nbytes = nbytes;
+        aiocb.aio_reqprio = prio;
+        let len = unsafe { self.inner.nbytes() };
+        *self.len() = len;
+        Ok(())
     }
 
-    fn error(self: Pin<&mut Self>) -> nix::Result<()> {
-        self.inner().error()
+    /// Choose how the kernel should notify the caller of completion.
+    ///
+    /// This is for standalone users that never register the operation with a
+    /// reactor, and would rather be notified by a signal, for example.
+    /// [`event::Source::register`] will override it with kqueue notification,
+    /// and [`event::Source::deregister`] will reset it to `SigevNone`.  Fails
+    /// with `EBUSY` if the operation is in progress.
+    pub fn set_notification(
+        self: Pin<&mut Self>,
+        sigev_notify: SigevNotify,
+    ) -> nix::Result<()> {
+        if self.in_progress() {
+            return Err(misuse(
+                Errno::EBUSY,
+                "set_notification while in progress",
+            ));
+        }
+        // Safe because we don't move the inner operation.
+        let inner = unsafe { self.inner().get_unchecked_mut() };
+        inner.set_sigev_notify(sigev_notify);
+        Ok(())
     }
 
-    fn in_progress(&self) -> bool {
-        self.inner.in_progress()
+    /// Choose how the kernel should notify the caller of completion, for
+    /// fluent construction.
+    ///
+    /// See [`Source::set_notification`].
+    pub fn with_notification(mut self, sigev_notify: SigevNotify) -> Self {
+        debug_assert!(!self.in_progress());
+        self.inner.set_sigev_notify(sigev_notify);
+        self
     }
 
-    #[cfg(feature = "tokio")]
-    fn register_raw(&mut self, kq: RawFd, udata: usize) {
-        self._register_raw(kq, udata)
+    /// Change the operation's priority, for fluent construction.
+    ///
+    /// This is handy when the priority is computed separately from the rest of
+    /// the operation's arguments.  Must not be called on an operation that's
+    /// in progress.
+    pub fn with_priority(mut self, prio: impl Into<AioPriority>) -> Self {
+        debug_assert!(!self.in_progress());
+        self.inner.as_mut().aio_reqprio = prio.into().get();
+        self
     }
+}
 
-    fn submit(self: Pin<&mut Self>) -> nix::Result<()> {
-        self.inner().submit()
+/// Describes the operation rather than its raw aiocb.  Fsyncs have no offset
+/// or length, so those fields are omitted for them.
+impl<T: AioOp> fmt::Debug for Source<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ds = f.debug_struct("Source");
+        ds.field("opcode", &T::OPCODE).field("fd", &self.fd());
+        if let Some(len) = self.len {
+            ds.field("offset", &self._offset()).field("len", &len);
+        }
+        ds.field("priority", &self.priority())
+            .field("state", &self.state())
+            .finish()
     }
 }
 
-impl<T: Aio> event::Source for Source<T> {
-    fn register(
-        &mut self,
-        registry: &Registry,
-        token: Token,
-        interests: Interest,
-    ) -> io::Result<()> {
-        assert!(interests.is_aio());
-        let udata = usize::from(token);
-        let kq = registry.as_raw_fd();
-        self._register_raw(kq, udata);
-        Ok(())
+impl<T: AioOp> Source<T> {
+    pub(crate) fn aiocb(&self) -> &libc::aiocb {
+        self.inner.as_ref()
     }
 
-    fn reregister(
+    /// What kind of operation is this?
+    pub fn opcode(&self) -> Opcode {
+        T::OPCODE
+    }
+
+    /// The file descriptor that this operation targets.
+    pub fn fd(&self) -> RawFd {
+        self.aiocb().aio_fildes
+    }
+
+    /// The operation's priority, as passed to its constructor.
+    pub fn priority(&self) -> i32 {
+        self.aiocb().aio_reqprio
+    }
+
+    /// How long ago the operation was submitted.
+    ///
+    /// Once the operation is reaped, by [`SourceApi::aio_return`] or
+    /// [`Source::reaped`], this stops advancing and reports its total
+    /// latency, until it's submitted again.  Returns `None` if the operation
+    /// has never been submitted.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.stopwatch.elapsed()
+    }
+
+    fn _offset(&self) -> u64 {
+        self.aiocb().aio_offset as u64
+    }
+
+    /// Submit the operation, first describing it to `sink`.
+    ///
+    /// This is a dependency-free hook for feeding the caller's own tracing or
+    /// metrics systems.  `sink` is called synchronously, immediately before
+    /// the operation is submitted to the kernel.  It's called even if
+    /// submission fails.
+    pub fn submit_observed(
+        self: Pin<&mut Self>,
+        sink: &mut dyn FnMut(OpDescriptor),
+    ) -> Result<(), AioError> {
+        let aiocb = self.aiocb();
+        let (offset, len) = match self.len {
+            Some(len) => (aiocb.aio_offset as u64, len),
+            None => (0, 0),
+        };
+        sink(OpDescriptor {
+            fd: aiocb.aio_fildes,
+            offset,
+            len,
+            opcode: T::OPCODE,
+            priority: aiocb.aio_reqprio,
+            timestamp: Instant::now(),
+        });
+        self.submit()
+    }
+
+    /// Submit the operation, holding `permit` until it's reaped.
+    ///
+    /// The permit is released when [`SourceApi::aio_return`] or
+    /// [`Source::reaped`] reaps the operation, or when the `Source` is
+    /// dropped.  If submission fails, it's released immediately.  See
+    /// [`AioQuota`](crate::AioQuota).
+    pub fn submit_with_permit(
+        mut self: Pin<&mut Self>,
+        permit: Permit,
+    ) -> Result<(), AioError> {
+        self.as_mut().submit()?;
+        *self.permit() = Some(permit);
+        Ok(())
+    }
+
+    /// Register this operation directly with a kqueue, bypassing Mio.
+    ///
+    /// This is for event loops that manage their own kqueue, rather than using
+    /// Mio or Tokio.  Upon completion, the kernel will post an `EVFILT_AIO`
+    /// event to `kq` with the given `udata`.  The caller owns `kq`, and must
+    /// keep it open until the operation has completed.
+    ///
+    /// It's also useful for designs with several reactors, to steer a
+    /// source's completion to a different kqueue than the one it was
+    /// registered with.  This method and [`event::Source::register`] simply
+    /// overwrite each other's settings, so whichever was called last before
+    /// submission determines where the event goes.  Likewise,
+    /// [`Source::deregister_kq`] and [`event::Source::deregister`] are
+    /// interchangeable.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `EBUSY` if the operation is already in progress and was
+    /// registered with a different kqueue.
+    pub fn register_kq(&mut self, kq: RawFd, udata: usize) -> io::Result<()> {
+        check_kq(self.inner.as_ref(), self.in_progress(), kq)?;
+        self._register_raw(kq, udata);
+        Ok(())
+    }
+
+    /// Undo [`Source::register_kq`].
+    pub fn deregister_kq(&mut self) {
+        self._deregister_raw();
+    }
+
+    /// Has this operation been registered with a reactor?
+    ///
+    /// Returns `true` after [`register`](event::Source::register) and `false`
+    /// after [`deregister`](event::Source::deregister), for a freshly
+    /// constructed operation, or for one configured with non-kqueue
+    /// notification by [`Source::set_notification`].
+    pub fn is_registered(&self) -> bool {
+        self.aiocb().aio_sigevent.sigev_notify == libc::SIGEV_KEVENT
+    }
+
+    /// Retrieve the status of an operation, without requiring it to be pinned.
+    ///
+    /// This is like [`SourceApi::error`], except that it won't cache the
+    /// final status.  Since it only needs a shared reference, it's convenient
+    /// for scanning many in-flight operations in a loop.
+    pub fn peek_error(&self) -> Result<(), AioError> {
+        self.peek_errno().map_err(AioError::Return)
+    }
+
+    /// Like [`Source::peek_error`], but untagged.
+    fn peek_errno(&self) -> nix::Result<()> {
+        if let Some(status) = self.status {
+            return status;
+        }
+        error_nointr(&self.inner)
+    }
+
+    /// Learn how the operation finished from its completion event, without a
+    /// syscall.
+    ///
+    /// On FreeBSD, an `EVFILT_AIO` event's `ident` is the address of the
+    /// aiocb, and its `data` is the value that `aio_error` would return: zero
+    /// on success, or an errno on failure.  Its `fflags` are unused.  So given
+    /// the event, there's no need to call [`SourceApi::error`].  The operation
+    /// must still be reaped with [`SourceApi::aio_return`].
+    ///
+    /// Mio doesn't expose these fields, so this is only useful with a kqueue
+    /// that the caller manages, as with [`Source::register_kq`].  Mio users
+    /// should call [`Source::peek_error`] instead.
+    pub fn completion_status(&self, ev: &KEvent) -> CompletionStatus {
+        let ident = self.aiocb() as *const libc::aiocb as usize;
+        if ev.filter() != Ok(EventFilter::EVFILT_AIO) || ev.ident() != ident {
+            return CompletionStatus::Unrelated;
+        }
+        match ev.data() {
+            0 => CompletionStatus::Succeeded,
+            e => CompletionStatus::Failed(Errno::from_raw(e as i32)),
+        }
+    }
+
+    /// Block until the operation completes, or until `timeout` elapses.
+    ///
+    /// On FreeBSD the kernel posts the completion kevent before waking any
+    /// threads blocked in `aio_suspend`.  So once this method returns `Ok`,
+    /// the event is guaranteed to be pending in the kqueue, and a subsequent
+    /// zero-timeout `poll` will deterministically return it.  That makes it
+    /// useful for testing code that processes completion notifications.
+    ///
+    /// Returns immediately if the operation isn't in progress.
+    ///
+    /// # Errors
+    ///
+    /// Errors are reported as [`AioError::Return`].
+    ///
+    /// - `EAGAIN`: the timeout elapsed before the operation completed.
+    /// - `EINTR`: a signal interrupted the wait.
+    pub fn suspend(&self, timeout: Option<Duration>) -> Result<(), AioError> {
+        if self.status.is_some() || !self.in_progress() {
+            return Ok(());
+        }
+        aio::aio_suspend(&[&self.inner], timeout.map(TimeSpec::from))
+            .map_err(AioError::Return)
+    }
+
+    /// Submit the operation, and block until it completes.
+    ///
+    /// This is for small programs and tests that don't want a full event
+    /// loop.  It registers the operation with a temporary kqueue, overriding
+    /// any existing registration, and deregisters it afterwards.  Returns the
+    /// result of [`SourceApi::aio_return`].
+    ///
+    /// Unlike `submit_and_wait` from the `async` feature, this doesn't need
+    /// an executor or a helper thread.
+    pub fn submit_and_wait_blocking(
+        self: Pin<&mut Self>,
+    ) -> Result<T::Output, AioError> {
+        let kq = Kqueue::new().map_err(AioError::Register)?;
+        self.submit_and_wait_kq(&kq)
+    }
+
+    /// Like [`Source::submit_and_wait_blocking`], but with a caller-supplied
+    /// kqueue, so that it may be reused.
+    pub(crate) fn submit_and_wait_kq(
+        mut self: Pin<&mut Self>,
+        kq: &Kqueue,
+    ) -> Result<T::Output, AioError> {
+        // Safe because registration doesn't move the operation.
+        let this = unsafe { self.as_mut().get_unchecked_mut() };
+        this.register_kq(kq.as_fd().as_raw_fd(), 0).map_err(|e| {
+            AioError::Register(Errno::from_raw(
+                e.raw_os_error().unwrap_or(libc::EINVAL),
+            ))
+        })?;
+        let r = self.as_mut().submit().and_then(|()| {
+            let ev = KEvent::new(
+                0,
+                EventFilter::EVFILT_AIO,
+                EventFlag::empty(),
+                FilterFlag::empty(),
+                0,
+                0,
+            );
+            let mut events = [ev];
+            // kevent may return early, so check the operation's own status.
+            while self.as_mut().error()
+                == Err(AioError::Return(Errno::EINPROGRESS))
+            {
+                match kq.kevent(&[], &mut events, None) {
+                    Ok(_) | Err(Errno::EINTR) => (),
+                    Err(e) => return Err(AioError::Return(e)),
+                }
+            }
+            self.as_mut().aio_return()
+        });
+        // Safe because deregistration doesn't move the operation.
+        unsafe { self.get_unchecked_mut() }.deregister_kq();
+        r
+    }
+
+    /// Arrange for the reactor to wake up if the operation takes longer than
+    /// `timeout`.
+    ///
+    /// This arms a one-shot `EVFILT_TIMER` on the same kqueue that the
+    /// operation is registered with, using the same token.  So a single
+    /// `poll` will return when the operation either completes or expires.  The
+    /// expiry event is not an AIO event, so [`mio::event::Event::is_aio`] will
+    /// return `false` for it.  At that point, if [`SourceApi::error`] still
+    /// reports `EINPROGRESS`, the caller may [`cancel`](SourceApi::cancel) the
+    /// operation.  Calling this again re-arms the timer.
+    ///
+    /// Most file systems can't cancel an operation once it has started, so
+    /// even after expiry `cancel` will usually return
+    /// [`CancelStat::AioNotCanceled`] and the caller must keep waiting.
+    ///
+    /// The timer is disarmed when the operation is reaped by
+    /// [`SourceApi::aio_return`], or when it's deregistered.  Dropping the
+    /// operation does not disarm it, because by then the kqueue might have
+    /// been closed and its descriptor reused.  So an operation that's dropped
+    /// while still registered may leave behind one stale expiry event.
+    ///
+    /// # Errors
+    ///
+    /// Errors are reported as [`AioError::Register`], since the timer is
+    /// registered with the operation's kqueue.  Fails with `EINVAL` if the
+    /// operation isn't registered with a kqueue.
+    pub fn cancel_after(
+        self: Pin<&mut Self>,
+        timeout: Duration,
+    ) -> Result<(), AioError> {
+        let kq = sigev_kq(self.aiocb()).ok_or_else(|| {
+            AioError::Register(misuse(
+                Errno::EINVAL,
+                "cancel_after before register",
+            ))
+        })?;
+        let udata = self.aiocb().aio_sigevent.sigev_value.sival_ptr as usize;
+        let nanos = timeout.as_nanos().min(libc::intptr_t::MAX as u128);
+        // Safe because we don't move anything.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.deadline_kq = Some(kq);
+        deadline_timer(
+            kq,
+            this.deadline_ident(),
+            libc::EV_ADD | libc::EV_ONESHOT,
+            nanos as libc::intptr_t,
+            udata,
+        )
+        .map_err(AioError::Register)
+    }
+
+    /// Identifies this operation's `cancel_after` timer within its kqueue.
+    fn deadline_ident(&self) -> usize {
+        &self.inner as *const T as usize
+    }
+
+    /// Remove any timer armed by `cancel_after`.
+    fn disarm_deadline(&mut self) {
+        if let Some(kq) = self.deadline_kq.take() {
+            // The timer might already have fired, or the kqueue might have
+            // been closed.  Either way, there's nothing left to disarm.
+            let _ = deadline_timer(
+                kq,
+                self.deadline_ident(),
+                libc::EV_DELETE,
+                0,
+                0,
+            );
+        }
+    }
+
+    /// Perform the operation synchronously, in lieu of submitting it, and
+    /// notify the reactor as though it had completed asynchronously.
+    fn emulate(mut self: Pin<&mut Self>) {
+        let r = self.inner.emulate();
+        *self.as_mut().status() = Some(r.as_ref().map(drop).map_err(|e| *e));
+        *self.as_mut().emulated() = Some(r);
+        let aiocb = self.aiocb();
+        if let Some(kq) = sigev_kq(aiocb) {
+            let udata = aiocb.aio_sigevent.sigev_value.sival_ptr as usize;
+            trigger(kq, NEXT_IDENT.fetch_add(1, Ordering::Relaxed), udata);
+        }
+    }
+
+    /// Finish an operation that was reaped by [`wait_any`].
+    ///
+    /// This updates the `Source`'s bookkeeping, so it may be resubmitted or
+    /// dropped, and returns the operation's result.  Subsequently,
+    /// [`SourceApi::error`] will report the operation's final status.
+    ///
+    /// Fails with `EINVAL` if `reaped` belongs to a different operation.
+    pub fn reaped(
+        mut self: Pin<&mut Self>,
+        reaped: &Reaped,
+    ) -> Result<usize, AioError> {
+        if !reaped.is_for(&self) || !self.in_progress() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "reaped a different operation",
+            )));
+        }
+        // The kernel has already forgotten the operation, so this fails.  But
+        // it also tells nix that the operation is no longer in progress.
+        let _ = self.as_mut().inner().aio_return();
+        // Safe because we don't move the operation.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.listed = false;
+        this.status = Some(reaped.result.map(drop));
+        this.returned = reaped.result.ok();
+        this.permit = None;
+        this.disarm_deadline();
+        #[cfg(feature = "metrics")]
+        {
+            this.stopwatch.stop();
+            metrics::record_return(&reaped.result);
+        }
+        reaped.result.map_err(AioError::Return)
+    }
+
+    /// Cancel the operation, and block until the kernel is done with it.
+    ///
+    /// Most file systems can't cancel an operation once it has started, in
+    /// which case [`SourceApi::cancel`] returns
+    /// [`CancelStat::AioNotCanceled`] and the buffer remains in use.  This
+    /// method handles that case by waiting for the operation to finish.  So
+    /// once it returns `Ok`, the kernel will never touch the buffer again.
+    /// The operation must still be reaped with [`SourceApi::aio_return`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with `EINVAL` if the operation was never submitted.
+    pub fn cancel_and_wait(
+        mut self: Pin<&mut Self>,
+    ) -> Result<CancelOutcome, AioError> {
+        self.as_mut().cancel()?;
+        loop {
+            match self.as_mut().error().map_err(AioError::errno) {
+                Ok(()) => return Ok(CancelOutcome::Completed),
+                Err(Errno::ECANCELED) => return Ok(CancelOutcome::Canceled),
+                Err(Errno::EINPROGRESS) => (),
+                Err(e) => return Ok(CancelOutcome::Failed(e)),
+            }
+            match self.suspend(None).map_err(AioError::errno) {
+                Ok(()) | Err(Errno::EINTR) => (),
+                Err(e) => return Err(AioError::Cancel(e)),
+            }
+        }
+    }
+
+    /// Describe a write that failed with `EFBIG`.
+    ///
+    /// The result includes the operation's offset and length, along with the
+    /// process's current file size limit.
+    pub fn file_too_large(&self) -> FileTooLarge {
+        let aiocb = self.aiocb();
+        FileTooLarge::new(aiocb.aio_offset as u64, self.len.unwrap_or(0))
+    }
+
+    #[cfg(feature = "log")]
+    fn log_short(&self, output: &T::Output) {
+        if let (Some(requested), Some(actual)) =
+            (self.len, T::transferred(output))
+        {
+            if actual < requested {
+                let aiocb = self.inner.as_ref();
+                log::debug!(
+                    "short AIO transfer: fd={} offset={} requested={} \
+                     actual={}",
+                    aiocb.aio_fildes,
+                    aiocb.aio_offset,
+                    requested,
+                    actual
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "log")]
+    fn log_submit_error(&self, e: Errno) {
+        if e == Errno::EAGAIN {
+            let aiocb = self.inner.as_ref();
+            log::debug!(
+                "AIO submission hit EAGAIN: fd={} offset={} requested={}",
+                aiocb.aio_fildes,
+                aiocb.aio_offset,
+                self.len.unwrap_or(0)
+            );
+        }
+    }
+}
+
+impl<T: AioOp> AsFd for Source<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safe because the constructor's BorrowedFd guarantees that the file
+        // remains open for at least as long as the operation.
+        unsafe { BorrowedFd::borrow_raw(self.fd()) }
+    }
+}
+
+impl<T: AioOp> AsRawFd for Source<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd()
+    }
+}
+
+impl<T: AioOp<Output = usize>> Source<T> {
+    /// Box and pin the operation as a trait object, so it may be stored
+    /// alongside operations of other types.
+    pub fn into_dyn<'a>(self) -> Pin<Box<dyn DynSource + 'a>>
+    where
+        T: 'a,
+    {
+        Box::pin(self)
+    }
+}
+
+impl<T: AioOp> Drop for Source<T> {
+    fn drop(&mut self) {
+        // An emulated operation has already finished, and the kernel never
+        // knew about it.
+        if !self.in_progress() || self.emulated.is_some() {
+            return;
+        }
+        // Safe because inner will be dropped in place, right after this.
+        let inner = unsafe { Pin::new_unchecked(&mut self.inner) };
+        // Reap the operation, freeing its kernel resources.
+        let _r = cancel_and_reap(inner);
+        #[cfg(feature = "metrics")]
+        metrics::record_return(&_r);
+    }
+}
+
+impl<T: AioOp> SourceApi for Source<T> {
+    type Output = T::Output;
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<Self::Output, AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "aio_return before submit",
+            )));
+        }
+        *self.as_mut().listed() = false;
+        let r = match self.as_mut().emulated().take() {
+            Some(r) => r,
+            None => aio_return_nointr(self.as_mut().inner()),
+        };
+        if !matches!(r, Err(Errno::EINPROGRESS)) {
+            // Safe because disarming the timer doesn't move the operation.
+            unsafe { self.as_mut().get_unchecked_mut() }.disarm_deadline();
+            *self.as_mut().permit() = None;
+            #[cfg(feature = "metrics")]
+            self.as_mut().stopwatch().stop();
+        }
+        #[cfg(feature = "log")]
+        match &r {
+            Ok(output) => self.log_short(output),
+            Err(Errno::EFBIG) => log::warn!("{}", self.file_too_large()),
+            Err(_) => (),
+        }
+        #[cfg(feature = "metrics")]
+        metrics::record_return(&r);
+        if let Ok(output) = &r {
+            *self.as_mut().returned() = T::transferred(output);
+        }
+        r.map_err(AioError::Return)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |o| T::transferred(&o).unwrap_or(0))
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Cancel(misuse(
+                Errno::EINVAL,
+                "cancel before submit",
+            )));
+        }
+        if self.emulated.is_some() {
+            return Ok(CancelStat::AioAllDone);
+        }
+        cancel_nointr(self.inner()).map_err(AioError::Cancel)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self._deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if !self.in_progress() && self.status.is_none() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "error before submit",
+            )));
+        }
+        let r = self.peek_errno();
+        if r != Err(Errno::EINPROGRESS) {
+            *self.status() = Some(r);
+        }
+        r.map_err(AioError::Return)
+    }
+
+    fn in_progress(&self) -> bool {
+        self.listed || self.inner.in_progress() || self.emulated.is_some()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self._register_raw(kq, udata)
+    }
+
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.aborted {
+            return Err(AioError::Submit(Errno::ECANCELED));
+        }
+        if self.in_progress() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        *self.as_mut().status() = None;
+        *self.as_mut().returned() = None;
+        let policy = FallbackPolicy::get();
+        let mut r = if policy.bypasses_aio() {
+            self.as_mut().emulate();
+            Ok(())
+        } else {
+            self.as_mut().inner().submit()
+        };
+        if r == Err(Errno::ENOSYS) && policy != FallbackPolicy::Never {
+            set_aio_unavailable();
+            self.as_mut().emulate();
+            r = Ok(());
+        }
+        #[cfg(feature = "log")]
+        if let Err(e) = r {
+            self.log_submit_error(e);
+        }
+        #[cfg(feature = "metrics")]
+        {
+            if r.is_ok() {
+                self.as_mut().stopwatch().start();
+            }
+            metrics::record_submit(&r);
+        }
+        r.map_err(AioError::Submit)
+    }
+}
+
+impl<T: AioOp> event::Source for Source<T> {
+    /// Register this operation with a Mio reactor.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn register(
         &mut self,
         registry: &Registry,
         token: Token,
         interests: Interest,
     ) -> io::Result<()> {
+        check_interests(interests)?;
+        let udata = usize::from(token);
+        let kq = registry.as_raw_fd();
+        check_kq(self.inner.as_ref(), self.in_progress(), kq)?;
+        self._register_raw(kq, udata);
+        Ok(())
+    }
+
+    /// Change the token, and possibly the reactor, of a registered
+    /// operation.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_token(
+            self.inner.as_ref(),
+            self.in_progress(),
+            usize::from(token),
+        )?;
         self.register(registry, token, interests)
     }
 
@@ -158,88 +1729,2268 @@ impl<T: Aio> event::Source for Source<T> {
 
 impl<'a> Source<aio::AioFsync<'a>> {
     /// Asynchronously fsync a file.
-    pub fn fsync(fd: BorrowedFd<'a>, mode: AioFsyncMode, prio: i32) -> Self {
-        let inner = aio::AioFsync::new(fd, mode, prio, SigevNotify::SigevNone);
-        Source { inner }
+    ///
+    /// The operation completes once every write to `fd` that was queued
+    /// before it, including other AIO writes, is durable.  `mode` selects
+    /// what "durable" means:
+    ///
+    /// * `O_SYNC` - like `fsync(2)`.  Flush the data and all of the file's
+    ///   metadata.
+    /// * `O_DSYNC` - like `fdatasync(2)`.  Flush the data and only as much
+    ///   metadata as is needed to read it back, such as the file's size, but
+    ///   not, for example, its modification time.  Requires FreeBSD 13.0 or
+    ///   later.  See also [`Source::fdatasync`].
+    pub fn fsync(
+        fd: BorrowedFd<'a>,
+        mode: AioFsyncMode,
+        prio: impl Into<AioPriority>,
+    ) -> Self {
+        let inner = aio::AioFsync::new(
+            fd,
+            mode,
+            prio.into().get(),
+            SigevNotify::SigevNone,
+        );
+        Source::new(inner)
+    }
+
+    /// Asynchronously fdatasync a file.
+    ///
+    /// This is shorthand for [`Source::fsync`] with `O_DSYNC`.  It's usually
+    /// cheaper than a full fsync, because it needn't flush metadata like
+    /// timestamps.  Like [`Source::readv_at`], it requires FreeBSD 13.0 or
+    /// later.
+    pub fn fdatasync(fd: BorrowedFd<'a>, prio: impl Into<AioPriority>) -> Self {
+        Source::fsync(fd, AioFsyncMode::O_DSYNC, prio)
+    }
+
+    /// Rebind this operation to a different file.
+    ///
+    /// See [Reuse](Source#reuse).
+    pub fn set_fd(self: Pin<&mut Self>, fd: BorrowedFd<'a>) -> nix::Result<()> {
+        self._set_fd(fd.as_raw_fd())
     }
 }
 
 impl<'a> Source<aio::AioRead<'a>> {
     /// Asynchronously read from a file.
+    ///
+    /// `offs` isn't checked: one that doesn't fit in an `off_t` will wrap
+    /// around.  Use [`Source::read_at_checked`] if it might be out of range.
     pub fn read_at(
         fd: BorrowedFd<'a>,
         offs: u64,
         buf: &'a mut [u8],
-        prio: i32,
+        prio: impl Into<AioPriority>,
     ) -> Self {
         let inner = aio::AioRead::new(
             fd,
             offs as off_t,
             buf,
-            prio,
+            prio.into().get(),
+            SigevNotify::SigevNone,
+        );
+        Source::new(inner)
+    }
+
+    /// Like [`Source::read_at`], but fails with `EOVERFLOW` if `offs` doesn't
+    /// fit in an `off_t`.
+    pub fn read_at_checked(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        checked_offset(offs)?;
+        Ok(Source::read_at(fd, offs, buf, prio))
+    }
+
+    /// Asynchronously read at most `n` bytes from a file.
+    ///
+    /// This is like [`Source::read_at`] on `&mut buf[..n]`, for callers that
+    /// keep one large buffer but sometimes want a smaller read.
+    ///
+    /// Fails with `EINVAL` if `n` is larger than `buf`.
+    pub fn read_at_n(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [u8],
+        n: usize,
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        let buf = buf.get_mut(..n).ok_or(Errno::EINVAL)?;
+        Ok(Source::read_at(fd, offs, buf, prio))
+    }
+
+    /// Asynchronously read from a file into an uninitialized buffer.
+    ///
+    /// This is like [`Source::read_at`], but it doesn't require the caller to
+    /// zero the buffer first.  Once [`SourceApi::aio_return`] succeeds, the
+    /// first `n` bytes of `buf` are initialized, where `n` is its return
+    /// value.
+    pub fn read_at_uninit(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [MaybeUninit<u8>],
+        prio: impl Into<AioPriority>,
+    ) -> Self {
+        // Construct with an empty buffer, because nix requires an initialized
+        // one, and then point the aiocb at the real one.  Neither nix nor the
+        // kernel ever reads from it.
+        let mut inner = aio::AioRead::new(
+            fd,
+            offs as off_t,
+            &mut [],
+            prio.into().get(),
             SigevNotify::SigevNone,
         );
-        Source { inner }
+        let aiocb: &mut libc::aiocb = inner.as_mut();
+        aiocb.aio_buf = buf.as_mut_ptr().cast();
+        aiocb.aio_nbytes = buf.len();
+        Source::new(inner)
+    }
+
+    /// Pass the data read to a closure, as soon as it's available.
+    ///
+    /// Whenever [`SourceApi::aio_return`] succeeds, `f` will be called
+    /// synchronously with the portion of the buffer that was filled.  That's
+    /// useful for checksumming data while it's still hot in the CPU cache.
+    pub fn on_complete<F>(self, f: F) -> OnComplete<'a, F>
+    where
+        F: FnMut(&[u8]),
+    {
+        OnComplete { source: self, f }
+    }
+
+    /// Asynchronously read from a file into one slot of a buffer pool.
+    ///
+    /// This is like [`Source::read_at`], but [`SourceApi::aio_return`] also
+    /// hands back `index`, which is otherwise uninterpreted.  That lets a
+    /// caller route the filled buffer back to its slot without keeping a
+    /// separate table keyed on the operation's token.
+    pub fn read_at_indexed(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [u8],
+        index: usize,
+        prio: impl Into<AioPriority>,
+    ) -> ReadAtIndexed<'a> {
+        ReadAtIndexed {
+            source: Source::read_at(fd, offs, buf, prio),
+            index,
+        }
+    }
+
+    /// Asynchronously read from a file into an owned buffer.
+    ///
+    /// Unlike [`Source::read_at`], the buffer's lifetime isn't tied to the
+    /// operation's.  The operation will try to fill all of `buf`, and
+    /// [`SourceApi::aio_return`] will hand it back, along with the number of
+    /// bytes read.
+    pub fn read_into(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        mut buf: Vec<u8>,
+        prio: impl Into<AioPriority>,
+    ) -> ReadInto<'a> {
+        // Safe because the returned operation owns buf.
+        let slice = unsafe { owned_slice_mut(&mut buf) };
+        ReadInto {
+            source: Source::read_at(fd, offs, slice, prio),
+            buf:    Some(buf),
+        }
+    }
+
+    /// Rebind this operation to a different file.
+    ///
+    /// See [Reuse](Source#reuse).
+    pub fn set_fd(self: Pin<&mut Self>, fd: BorrowedFd<'a>) -> nix::Result<()> {
+        self._set_fd(fd.as_raw_fd())
+    }
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self._offset()
+    }
+
+    /// Change the file offset for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress, or with
+    /// `EOVERFLOW` if `offs` doesn't fit in an `off_t`.
+    pub fn set_offset(self: Pin<&mut Self>, offs: u64) -> nix::Result<()> {
+        self._set_offset(offs)
+    }
+
+    /// Change the buffer for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress.
+    pub fn set_buffer(
+        mut self: Pin<&mut Self>,
+        buf: &'a mut [u8],
+    ) -> nix::Result<()> {
+        let aiocb = self.as_mut().aiocb_mut()?;
+        aiocb.aio_buf = buf.as_mut_ptr().cast();
+        aiocb.aio_nbytes = buf.len();
+        *self.len() = Some(buf.len());
+        Ok(())
+    }
+
+    /// Re-arm a completed operation to read somewhere else.
+    ///
+    /// The arguments are the same as for [`Source::read_at`].  See
+    /// [Reuse](Source#reuse).
+    pub fn reset_read_at(
+        self: Pin<&mut Self>,
+        offs: u64,
+        buf: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<()> {
+        let len = buf.len();
+        // Safe because buf is borrowed for the operation's lifetime.
+        unsafe {
+            self._reset(offs, buf.as_mut_ptr().cast(), len, prio.into().get())
+        }
     }
 }
 
 impl<'a> Source<aio::AioReadv<'a>> {
     /// Asynchronously read from a file to a scatter/gather list of buffers.
     ///
-    /// Requires FreeBSD 13.0 or later.
+    /// Requires FreeBSD 13.0 or later; see
+    /// [`supports_vectored`](crate::supports_vectored).  Like
+    /// [`Source::read_at`], `offs` isn't checked; see
+    /// [`Source::readv_at_checked`].
     pub fn readv_at(
         fd: BorrowedFd<'a>,
         offs: u64,
         bufs: &mut [IoSliceMut<'a>],
-        prio: i32,
+        prio: impl Into<AioPriority>,
     ) -> Self {
         let inner = aio::AioReadv::new(
             fd,
             offs as off_t,
             bufs,
-            prio,
+            prio.into().get(),
             SigevNotify::SigevNone,
         );
-        Source { inner }
+        Source::new(inner)
+    }
+
+    /// The total number of bytes that this operation will attempt to read.
+    ///
+    /// This is the sum of the lengths in the `IoSliceMut` array, as recorded
+    /// when the operation was constructed or last reset.
+    pub fn total_len(&self) -> usize {
+        self.len.unwrap_or(0)
+    }
+
+    /// Like [`Source::readv_at`], but fails with `EOVERFLOW` if `offs` doesn't
+    /// fit in an `off_t`.
+    pub fn readv_at_checked(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        bufs: &mut [IoSliceMut<'a>],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        checked_offset(offs)?;
+        Ok(Source::readv_at(fd, offs, bufs, prio))
+    }
+
+    /// Asynchronously read from a file to a scatter/gather list of buffers,
+    /// taking ownership of the list.
+    ///
+    /// With [`Source::readv_at`], the `IoSliceMut` array must remain valid
+    /// until the operation is submitted.  Here it's stored within the
+    /// returned operation instead, so the operation is self-contained.
+    pub fn readv_at_owned(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        bufs: Vec<IoSliceMut<'a>>,
+        prio: impl Into<AioPriority>,
+    ) -> ReadvAtOwned<'a> {
+        let mut iovs = bufs;
+        // Safe because the returned operation owns iovs.
+        let slice = unsafe { owned_slice_mut(&mut iovs) };
+        let source = Source::readv_at(fd, offs, slice, prio);
+        ReadvAtOwned { source, iovs }
+    }
+
+    /// Like [`Source::readv_at_owned`], but reads at most `max` bytes.
+    ///
+    /// If the buffers' total length exceeds `max`, the buffer that reaches the
+    /// cap is shortened, and any after it are dropped from the list.
+    pub fn readv_at_capped(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        mut bufs: Vec<IoSliceMut<'a>>,
+        max: usize,
+        prio: impl Into<AioPriority>,
+    ) -> ReadvAtOwned<'a> {
+        // Safe because IoSliceMut is ABI-compatible with iovec, and
+        // shortening an iovec keeps it within its buffer.
+        let iovecs = unsafe {
+            std::slice::from_raw_parts_mut(bufs.as_mut_ptr().cast(), bufs.len())
+        };
+        let n = cap_iovecs(iovecs, max);
+        bufs.truncate(n);
+        Source::readv_at_owned(fd, offs, bufs, prio)
+    }
+
+    /// Asynchronously read from a file into several buffers, such as
+    /// `Vec<u8>`s.
+    ///
+    /// This is shorthand for [`Source::readv_at_owned`] that builds the
+    /// `IoSliceMut` array itself.  Like [`Source::readv_at`], the buffers are
+    /// filled in order, and [`SourceApi::aio_return`] returns the total number
+    /// of bytes read.
+    pub fn readv_into<B: AsMut<[u8]>>(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        bufs: &'a mut [B],
+        prio: impl Into<AioPriority>,
+    ) -> ReadvAtOwned<'a> {
+        let iovs = bufs
+            .iter_mut()
+            .map(|buf| IoSliceMut::new(buf.as_mut()))
+            .collect();
+        Source::readv_at_owned(fd, offs, iovs, prio)
+    }
+
+    /// Rebind this operation to a different file.
+    ///
+    /// See [Reuse](Source#reuse).
+    pub fn set_fd(self: Pin<&mut Self>, fd: BorrowedFd<'a>) -> nix::Result<()> {
+        self._set_fd(fd.as_raw_fd())
+    }
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self._offset()
+    }
+
+    /// Change the file offset for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress, or with
+    /// `EOVERFLOW` if `offs` doesn't fit in an `off_t`.
+    pub fn set_offset(self: Pin<&mut Self>, offs: u64) -> nix::Result<()> {
+        self._set_offset(offs)
+    }
+
+    /// Re-arm a completed operation to read somewhere else.
+    ///
+    /// The arguments are the same as for [`Source::readv_at`], except that
+    /// `bufs` must outlive the operation.  See [Reuse](Source#reuse).
+    pub fn reset_readv_at(
+        self: Pin<&mut Self>,
+        offs: u64,
+        bufs: &'a mut [IoSliceMut<'a>],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<()> {
+        // Safe because bufs is borrowed for the operation's lifetime.
+        unsafe {
+            self._reset(
+                offs,
+                bufs.as_mut_ptr().cast(),
+                bufs.len(),
+                prio.into().get(),
+            )
+        }
     }
 }
 
 impl<'a> Source<aio::AioWrite<'a>> {
     /// Asynchronously write to a file.
+    ///
+    /// # Appending
+    ///
+    /// If `fd` was opened with `O_APPEND`, then `offs` is ignored and the data
+    /// will be written at the end of the file.  Each such write is atomic:
+    /// concurrent appends will never interleave with each other.  However,
+    /// concurrent appends may land in any order.  Callers that need records
+    /// to appear in submission order must wait for each append to complete
+    /// before submitting the next.
+    ///
+    /// `offs` isn't checked: one that doesn't fit in an `off_t` will wrap
+    /// around.  Use [`Source::write_at_checked`] if it might be out of range.
     pub fn write_at(
         fd: BorrowedFd<'a>,
         offs: u64,
         buf: &'a [u8],
-        prio: i32,
+        prio: impl Into<AioPriority>,
     ) -> Self {
         let inner = aio::AioWrite::new(
             fd,
             offs as off_t,
             buf,
-            prio,
+            prio.into().get(),
             SigevNotify::SigevNone,
         );
-        Source { inner }
+        Source::new(inner)
     }
-}
 
-impl<'a> Source<aio::AioWritev<'a>> {
+    /// Like [`Source::write_at`], but fails with `EOVERFLOW` if `offs` doesn't
+    /// fit in an `off_t`.
+    pub fn write_at_checked(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        checked_offset(offs)?;
+        Ok(Source::write_at(fd, offs, buf, prio))
+    }
+
+    /// Rebind this operation to a different file.
+    ///
+    /// See [Reuse](Source#reuse).
+    pub fn set_fd(self: Pin<&mut Self>, fd: BorrowedFd<'a>) -> nix::Result<()> {
+        self._set_fd(fd.as_raw_fd())
+    }
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self._offset()
+    }
+
+    /// Change the file offset for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress, or with
+    /// `EOVERFLOW` if `offs` doesn't fit in an `off_t`.
+    pub fn set_offset(self: Pin<&mut Self>, offs: u64) -> nix::Result<()> {
+        self._set_offset(offs)
+    }
+
+    /// Asynchronously write the first `n` bytes of `buf` to a file.
+    ///
+    /// This is like [`Source::write_at`] on `&buf[..n]`.
+    ///
+    /// Fails with `EINVAL` if `n` is larger than `buf`.
+    pub fn write_at_n(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        n: usize,
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        let buf = buf.get(..n).ok_or(Errno::EINVAL)?;
+        Ok(Source::write_at(fd, offs, buf, prio))
+    }
+
+    /// Like [`Source::write_at`], but fail fast if the write would exceed the
+    /// process's file size limit.
+    ///
+    /// Such a write would otherwise fail with `EFBIG` only after submission.
+    /// This checks the soft `RLIMIT_FSIZE` at construction time.  It can't
+    /// detect writes that exceed the file system's maximum file size.
+    pub fn write_at_within_limit(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> Result<Self, FileTooLarge> {
+        match fsize_limit() {
+            Some(limit) if offs.saturating_add(buf.len() as u64) > limit => {
+                Err(FileTooLarge::new(offs, buf.len()))
+            }
+            _ => Ok(Source::write_at(fd, offs, buf, prio)),
+        }
+    }
+
+    /// Asynchronously write to a file, and then fsync it.
+    ///
+    /// See [`WriteThenFsync`] for details.
+    pub fn write_then_fsync(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        mode: AioFsyncMode,
+        prio: impl Into<AioPriority>,
+    ) -> WriteThenFsync<'a> {
+        let prio = prio.into().get();
+        let write = aio::AioWrite::new(
+            fd,
+            offs as off_t,
+            buf,
+            prio,
+            SigevNotify::SigevNone,
+        );
+        let fsync = aio::AioFsync::new(fd, mode, prio, SigevNotify::SigevNone);
+        WriteThenFsync {
+            write,
+            fsync,
+            notify_mode: NotifyMode::default(),
+            written: None,
+        }
+    }
+
+    /// Asynchronously write to a file, and then fsync it, with a single
+    /// completion event.
+    ///
+    /// See [`WriteThenSync`] for details, and for how it differs from
+    /// [`Source::write_then_fsync`].
+    pub fn write_then_sync(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        mode: AioFsyncMode,
+        prio: impl Into<AioPriority>,
+    ) -> WriteThenSync<'a> {
+        let prio = prio.into().get();
+        let write = aio::AioWrite::new(
+            fd,
+            offs as off_t,
+            buf,
+            prio,
+            SigevNotify::SigevNone,
+        );
+        let fsync = aio::AioFsync::new(fd, mode, prio, SigevNotify::SigevNone);
+        WriteThenSync {
+            write,
+            fsync,
+            notify_mode: NotifyMode::default(),
+        }
+    }
+
+    /// Asynchronously write to a file, and then read the data back to verify
+    /// it.
+    ///
+    /// `scratch` must be at least as large as `buf`.  See [`WriteAtVerified`]
+    /// for details.
+    ///
+    /// Fails with `EINVAL` if `scratch` is smaller than `buf`.
+    pub fn write_at_verified(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        scratch: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<WriteAtVerified<'a>> {
+        let scratch = scratch.get_mut(..buf.len()).ok_or(Errno::EINVAL)?;
+        let prio = prio.into().get();
+        let write = aio::AioWrite::new(
+            fd,
+            offs as off_t,
+            buf,
+            prio,
+            SigevNotify::SigevNone,
+        );
+        let read = aio::AioRead::new(
+            fd,
+            offs as off_t,
+            scratch,
+            prio,
+            SigevNotify::SigevNone,
+        );
+        Ok(WriteAtVerified {
+            write,
+            read,
+            buf,
+            notify_mode: NotifyMode::default(),
+            written: None,
+        })
+    }
+
+    /// Asynchronously write an owned buffer to a file.
+    ///
+    /// Unlike [`Source::write_at`], the buffer's lifetime isn't tied to the
+    /// operation's.  [`SourceApi::aio_return`] will hand it back, along with
+    /// the number of bytes written.
+    pub fn write_from(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: Box<[u8]>,
+        prio: impl Into<AioPriority>,
+    ) -> WriteFrom<'a> {
+        let buf = buf.into_vec();
+        // Safe because the returned operation owns buf.
+        let slice = unsafe { owned_slice(&buf) };
+        WriteFrom {
+            source: Source::write_at(fd, offs, slice, prio),
+            buf:    Some(buf),
+        }
+    }
+
+    /// Change the buffer for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress.
+    pub fn set_buffer(
+        mut self: Pin<&mut Self>,
+        buf: &'a [u8],
+    ) -> nix::Result<()> {
+        let aiocb = self.as_mut().aiocb_mut()?;
+        aiocb.aio_buf = buf.as_ptr() as *mut _;
+        aiocb.aio_nbytes = buf.len();
+        *self.len() = Some(buf.len());
+        Ok(())
+    }
+
+    /// Build a write of whatever a short write left unwritten.
+    ///
+    /// After [`SourceApi::aio_return`] reports that fewer bytes were written
+    /// than requested, this returns a new operation that writes the rest of
+    /// the buffer to the same file, at the offset where this one left off,
+    /// with the same priority.  The new operation hasn't been registered or
+    /// submitted.  Returns `None` if the write was complete, or if it hasn't
+    /// been successfully reaped since it was last submitted.
+    pub fn continuation(self: Pin<&mut Self>) -> Option<WriteAt<'a>> {
+        let written = self.returned?;
+        let aiocb = self.aiocb();
+        let len = aiocb.aio_nbytes;
+        if written >= len {
+            return None;
+        }
+        // Safe because the buffer was borrowed for 'a, when it was passed to
+        // write_at or to another method that rebinds the buffer.
+        let buf = unsafe {
+            std::slice::from_raw_parts(
+                (aiocb.aio_buf as *const u8).add(written),
+                len - written,
+            )
+        };
+        // Safe because the file was likewise borrowed for 'a.
+        let fd = unsafe { BorrowedFd::borrow_raw(aiocb.aio_fildes) };
+        let offs = self._offset() + written as u64;
+        Some(Source::write_at(fd, offs, buf, aiocb.aio_reqprio))
+    }
+
+    /// Re-arm a completed operation to write somewhere else.
+    ///
+    /// The arguments are the same as for [`Source::write_at`].  See
+    /// [Reuse](Source#reuse).
+    pub fn reset_write_at(
+        self: Pin<&mut Self>,
+        offs: u64,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<()> {
+        let len = buf.len();
+        // Safe because buf is borrowed for the operation's lifetime.
+        unsafe {
+            self._reset(offs, buf.as_ptr() as *mut _, len, prio.into().get())
+        }
+    }
+}
+
+impl<'a> Source<aio::AioWritev<'a>> {
     /// Asynchronously write to a file to a scatter/gather list of buffers.
     ///
-    /// Requires FreeBSD 13.0 or later.
+    /// Requires FreeBSD 13.0 or later; see
+    /// [`supports_vectored`](crate::supports_vectored).  Appending to a file
+    /// opened with `O_APPEND` has the same semantics as for
+    /// [`Source::write_at`].  Like [`Source::write_at`], `offs` isn't checked;
+    /// see [`Source::writev_at_checked`].
     pub fn writev_at(
         fd: BorrowedFd<'a>,
         offs: u64,
         bufs: &[IoSlice<'a>],
-        prio: i32,
+        prio: impl Into<AioPriority>,
     ) -> Self {
         let inner = aio::AioWritev::new(
             fd,
             offs as off_t,
             bufs,
-            prio,
+            prio.into().get(),
             SigevNotify::SigevNone,
         );
-        Source { inner }
+        Source::new(inner)
+    }
+
+    /// The total number of bytes that this operation will attempt to write.
+    ///
+    /// This is the sum of the lengths in the `IoSlice` array, as recorded
+    /// when the operation was constructed or last reset.
+    pub fn total_len(&self) -> usize {
+        self.len.unwrap_or(0)
+    }
+
+    /// Like [`Source::writev_at`], but fails with `EOVERFLOW` if `offs`
+    /// doesn't fit in an `off_t`.
+    pub fn writev_at_checked(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        bufs: &[IoSlice<'a>],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        checked_offset(offs)?;
+        Ok(Source::writev_at(fd, offs, bufs, prio))
+    }
+
+    /// Asynchronously write to a file from a scatter/gather list of buffers,
+    /// taking ownership of the list.
+    ///
+    /// With [`Source::writev_at`], the `IoSlice` array must remain valid until
+    /// the operation is submitted.  Here it's stored within the returned
+    /// operation instead, so the operation is self-contained.
+    pub fn writev_at_owned(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        bufs: Vec<IoSlice<'a>>,
+        prio: impl Into<AioPriority>,
+    ) -> WritevAtOwned<'a> {
+        let iovs = bufs;
+        // Safe because the returned operation owns iovs.
+        let slice = unsafe { owned_slice(&iovs) };
+        let source = Source::writev_at(fd, offs, slice, prio);
+        WritevAtOwned { source, iovs }
+    }
+
+    /// Like [`Source::writev_at_owned`], but writes at most `max` bytes.
+    ///
+    /// If the buffers' total length exceeds `max`, the buffer that reaches the
+    /// cap is shortened, and any after it are dropped from the list.
+    pub fn writev_at_capped(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        mut bufs: Vec<IoSlice<'a>>,
+        max: usize,
+        prio: impl Into<AioPriority>,
+    ) -> WritevAtOwned<'a> {
+        // Safe because IoSlice is ABI-compatible with iovec, and shortening an
+        // iovec keeps it within its buffer.
+        let iovecs = unsafe {
+            std::slice::from_raw_parts_mut(bufs.as_mut_ptr().cast(), bufs.len())
+        };
+        let n = cap_iovecs(iovecs, max);
+        bufs.truncate(n);
+        Source::writev_at_owned(fd, offs, bufs, prio)
+    }
+
+    /// Rebind this operation to a different file.
+    ///
+    /// See [Reuse](Source#reuse).
+    pub fn set_fd(self: Pin<&mut Self>, fd: BorrowedFd<'a>) -> nix::Result<()> {
+        self._set_fd(fd.as_raw_fd())
+    }
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self._offset()
+    }
+
+    /// Change the file offset for the next submission.
+    ///
+    /// Fails with `EBUSY` if the operation is still in progress, or with
+    /// `EOVERFLOW` if `offs` doesn't fit in an `off_t`.
+    pub fn set_offset(self: Pin<&mut Self>, offs: u64) -> nix::Result<()> {
+        self._set_offset(offs)
+    }
+
+    /// Re-arm a completed operation to write somewhere else.
+    ///
+    /// The arguments are the same as for [`Source::writev_at`], except that
+    /// `bufs` must outlive the operation.  See [Reuse](Source#reuse).
+    pub fn reset_writev_at(
+        self: Pin<&mut Self>,
+        offs: u64,
+        bufs: &'a [IoSlice<'a>],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<()> {
+        // Safe because bufs is borrowed for the operation's lifetime.
+        unsafe {
+            self._reset(
+                offs,
+                bufs.as_ptr() as *mut _,
+                bufs.len(),
+                prio.into().get(),
+            )
+        }
+    }
+}
+
+/// Cancel every outstanding operation on a file descriptor.
+///
+/// This wraps `aio_cancel(fd, NULL)`, and is the safe way to prepare a file
+/// descriptor for closing.  Otherwise, the kernel might write into buffers
+/// after they've been freed, or an operation might complete on an unrelated
+/// file that reused the descriptor.  If the syscall is interrupted by a
+/// signal, it will be retried.
+///
+/// Some operations may not be cancellable, in which case this returns
+/// [`CancelStat::AioNotCanceled`].  The caller must still wait for those
+/// operations to complete, and reap every operation with
+/// [`SourceApi::aio_return`], before closing the file.
+pub fn cancel_fd(fd: BorrowedFd) -> Result<CancelStat, AioError> {
+    loop {
+        match aio::aio_cancel_all(fd) {
+            Err(Errno::EINTR) => continue,
+            r => return r.map(CancelStat::from).map_err(AioError::Cancel),
+        }
+    }
+}
+
+/// Submit many operations of the same type with a single `lio_listio(2)` call.
+///
+/// This saves syscalls compared to submitting each operation individually.
+/// Each operation keeps its own registration, and will deliver its own
+/// completion event, just as if it had been submitted with
+/// [`SourceApi::submit`].  Reap each one with [`SourceApi::aio_return`] as
+/// usual.  For a batch that needs only one registration, see
+/// [`LioCb`](crate::LioCb) instead.
+///
+/// # Errors
+///
+/// - `EBUSY`: an operation is already in progress.  Nothing was submitted.
+/// - `ECANCELED`: an operation was aborted by [`Source::abort_pending`].
+///   Nothing was submitted.
+/// - `EINVAL`: the operations are fsyncs, which `lio_listio` can't submit.
+///
+/// Otherwise, if `lio_listio` fails, the kernel may still have initiated some
+/// of the operations, usually when it hits a resource limit partway through
+/// the list.  Check each operation's [`Source::state`]: those that were
+/// initiated are `InProgress`, and the rest are `Idle`.  An idle operation
+/// that failed for a reason of its own will report that reason from
+/// [`SourceApi::error`].  The rest may be resubmitted later.
+pub fn submit_all<T: AioOp>(
+    sources: &mut [Pin<&mut Source<T>>],
+) -> Result<(), AioError> {
+    if T::OPCODE.is_sync() {
+        return Err(AioError::Submit(misuse(
+            Errno::EINVAL,
+            "submit_all with fsync",
+        )));
+    }
+    for source in sources.iter() {
+        if source.aborted {
+            return Err(AioError::Submit(Errno::ECANCELED));
+        }
+        if source.in_progress() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit_all while in progress",
+            )));
+        }
+    }
+    let r = {
+        let mut list = sources
+            .iter_mut()
+            .map(|source| {
+                *source.as_mut().status() = None;
+                *source.as_mut().returned() = None;
+                source.as_mut().inner() as Pin<&mut dyn AsMut<libc::aiocb>>
+            })
+            .collect::<Vec<_>>();
+        aio::lio_listio(LioMode::LIO_NOWAIT, &mut list, SigevNotify::SigevNone)
+    };
+    if r.is_ok() {
+        for source in sources.iter_mut() {
+            *source.as_mut().listed() = true;
+            #[cfg(feature = "metrics")]
+            source.as_mut().stopwatch().start();
+        }
+        #[cfg(feature = "metrics")]
+        metrics::record_submitted(sources.len() as u64);
+        return Ok(());
+    }
+    // Some operations may have been initiated.  Figure out which.
+    for source in sources.iter_mut() {
+        match raw_aio_error(&source.inner) {
+            libc::EAGAIN => (),
+            0 | libc::EINPROGRESS => {
+                *source.as_mut().listed() = true;
+                #[cfg(feature = "metrics")]
+                source.as_mut().stopwatch().start();
+            }
+            // Either the operation failed to queue, in which case the kernel
+            // stashed its error in the aiocb, or else it was queued and has
+            // already failed.  Only in the latter case must it be reaped.
+            err => {
+                let status = match aio_return_nointr(source.as_mut().inner()) {
+                    Err(Errno::EINVAL) => Err(Errno::from_raw(err)),
+                    r => r.map(drop),
+                };
+                #[cfg(feature = "metrics")]
+                metrics::record_return(&status);
+                *source.as_mut().status() = Some(status);
+            }
+        }
+    }
+    #[cfg(feature = "metrics")]
+    {
+        let initiated = sources.iter().filter(|s| s.listed).count() as u64;
+        if initiated > 0 {
+            metrics::record_submitted(initiated);
+            metrics::record_lio_requeue();
+        }
+    }
+    r.map_err(AioError::Submit)
+}
+
+/// An operation reaped by [`wait_any`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reaped {
+    aiocb:  usize,
+    result: nix::Result<usize>,
+}
+
+impl Reaped {
+    /// The address of the reaped operation's aiocb.
+    pub fn aiocb(&self) -> *const libc::aiocb {
+        self.aiocb as *const libc::aiocb
+    }
+
+    /// Was this `source`'s operation?
+    pub fn is_for<T: AioOp>(&self, source: &Source<T>) -> bool {
+        self.aiocb == source.aiocb() as *const libc::aiocb as usize
+    }
+
+    /// The operation's final result, as `aio_return` would have reported it.
+    ///
+    /// For fsync operations, the count is meaningless.
+    pub fn result(&self) -> Result<usize, AioError> {
+        self.result.map_err(AioError::Return)
+    }
+}
+
+/// Block until any of the process's operations completes, and reap it.
+///
+/// This wraps FreeBSD's `aio_waitcomplete(2)`.  It's an alternative to
+/// per-operation notification, for pull-based designs whose operations aren't
+/// registered with any reactor.  Since it reaps operations indiscriminately,
+/// it shouldn't be mixed with registered operations in the same process.
+///
+/// Find the operation that completed with [`Reaped::is_for`], and then pass
+/// the result to that operation's [`Source::reaped`] before reusing or
+/// dropping it.  That takes the place of [`SourceApi::aio_return`].
+///
+/// `timeout` of `None` waits forever, and `Some(Duration::ZERO)` doesn't wait
+/// at all.
+///
+/// # Errors
+///
+/// Errors are reported as [`AioError::Return`].
+///
+/// - `EAGAIN`: the timeout elapsed before any operation completed, or the
+///   process has never submitted any operations.
+/// - `EINTR`: a signal interrupted the wait.
+pub fn wait_any(timeout: Option<Duration>) -> Result<Reaped, AioError> {
+    let mut aiocbp: *mut libc::aiocb = ptr::null_mut();
+    let mut ts = timeout.map(|t| *TimeSpec::from(t).as_ref());
+    let tsp = ts.as_mut().map_or(ptr::null_mut(), |ts| ts as *mut _);
+    // Safe because both pointers are valid for the duration of the call.
+    let r = unsafe { libc::aio_waitcomplete(&mut aiocbp, tsp) };
+    let result = Errno::result(r).map(|n| n as usize);
+    if aiocbp.is_null() {
+        // The call failed without reaping anything.
+        return Err(AioError::Return(result.unwrap_err()));
+    }
+    Ok(Reaped {
+        aiocb: aiocbp as usize,
+        result,
+    })
+}
+
+/// The maximum number of iovecs accepted by a single vectored operation.
+fn iov_max() -> usize {
+    // Safe because sysconf has no side effects
+    let r = unsafe { libc::sysconf(libc::_SC_IOV_MAX) };
+    // POSIX requires IOV_MAX to be at least 16.
+    if r > 0 {
+        r as usize
+    } else {
+        16
+    }
+}
+
+/// Plan the vectored writes needed to write many buffers to consecutive
+/// offsets.
+///
+/// `bufs` will be written contiguously to the file, beginning at `offs`.  Each
+/// returned operation covers as many consecutive buffers as the system's
+/// `IOV_MAX` allows, at the offset where the previous operation left off.
+/// None of the operations have yet been registered or submitted.
+pub fn plan_vectored_writes<'a>(
+    fd: BorrowedFd<'a>,
+    offs: u64,
+    bufs: &'a [IoSlice<'a>],
+    prio: impl Into<AioPriority>,
+) -> Vec<WritevAt<'a>> {
+    let prio = prio.into();
+    let mut offs = offs;
+    bufs.chunks(iov_max())
+        .map(|chunk| {
+            let op = Source::writev_at(fd, offs, chunk, prio);
+            offs += chunk.iter().map(|buf| buf.len() as u64).sum::<u64>();
+            op
+        })
+        .collect()
+}
+
+/// A write followed by an fsync of the same file, as a single Mio source.
+///
+/// The fsync is not submitted until the write has completed.  Both stages
+/// notify the same kqueue with the same token.  When the write's event
+/// arrives, [`SourceApi::aio_return`] will reap the write, submit the fsync,
+/// and fail with `EINPROGRESS`; the caller should simply keep polling.  Once
+/// the fsync's event arrives, `aio_return` will return the number of bytes
+/// written.  An error from either stage is returned by `aio_return`.
+///
+/// [`WriteThenSync`] does the same thing, but delivers only one event.  Prefer
+/// `WriteThenFsync` when the write might fail, or when only one of the
+/// process's AIO queue slots may be used at a time, because it doesn't
+/// submit the fsync unless the write succeeds.
+///
+/// Dropping an in-progress `WriteThenFsync` cancels whichever stage is in
+/// flight, and blocks until the kernel is done with it.
+///
+/// Create one with [`Source::write_then_fsync`].
+#[derive(Debug)]
+pub struct WriteThenFsync<'a> {
+    write:       aio::AioWrite<'a>,
+    fsync:       aio::AioFsync<'a>,
+    notify_mode: NotifyMode,
+    /// Result of the write stage, once it has been reaped.
+    written:     Option<usize>,
+}
+
+impl<'a> WriteThenFsync<'a> {
+    pin_utils::unsafe_pinned!(write: aio::AioWrite<'a>);
+
+    pin_utils::unsafe_pinned!(fsync: aio::AioFsync<'a>);
+
+    pin_utils::unsafe_unpinned!(written: Option<usize>);
+
+    /// Reap the completed write and submit the fsync.
+    fn advance(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        let written = aio_return_nointr(self.as_mut().write())
+            .map_err(AioError::Return)?;
+        *self.as_mut().written() = Some(written);
+        self.fsync().submit().map_err(AioError::Submit)
+    }
+
+    fn _deregister_raw(&mut self) {
+        self.write.set_sigev_notify(SigevNotify::SigevNone);
+        self.fsync.set_sigev_notify(SigevNotify::SigevNone);
+    }
+
+    fn _register_raw(&mut self, kq: RawFd, udata: usize) {
+        let sigev = SigevNotify::SigevKeventFlags {
+            kq,
+            udata: udata as isize,
+            flags: self.notify_mode.flags(),
+        };
+        self.write.set_sigev_notify(sigev);
+        self.fsync.set_sigev_notify(sigev);
+    }
+
+    /// Choose how the kqueue delivers both stages' completion events.
+    ///
+    /// Takes effect at the next registration.  See [`NotifyMode`].
+    pub fn set_notify_mode(&mut self, mode: NotifyMode) {
+        self.notify_mode = mode;
+    }
+
+    /// Choose how the kqueue delivers both stages' completion events, for
+    /// fluent construction.
+    pub fn with_notify_mode(mut self, mode: NotifyMode) -> Self {
+        self.set_notify_mode(mode);
+        self
+    }
+}
+
+impl<'a> Drop for WriteThenFsync<'a> {
+    fn drop(&mut self) {
+        // Safe because both stages will be dropped in place, right after this.
+        let write = unsafe { Pin::new_unchecked(&mut self.write) };
+        if write.in_progress() {
+            let _ = cancel_and_reap(write);
+        }
+        let fsync = unsafe { Pin::new_unchecked(&mut self.fsync) };
+        if fsync.in_progress() {
+            let _ = cancel_and_reap(fsync);
+        }
+    }
+}
+
+impl<'a> SourceApi for WriteThenFsync<'a> {
+    type Output = usize;
+
+    /// Reap whichever stage has finished.
+    ///
+    /// If the write has finished, this reaps it, submits the fsync, and fails
+    /// with `EINPROGRESS`.  If the fsync has finished, it returns the number
+    /// of bytes written.  A stage that's still running is never reaped.
+    fn aio_return(self: Pin<&mut Self>) -> Result<usize, AioError> {
+        if let Some(written) = self.written {
+            if error_nointr(&self.fsync) == Err(Errno::EINPROGRESS) {
+                return Err(AioError::Return(Errno::EINPROGRESS));
+            }
+            aio_return_nointr(self.fsync())
+                .map(|_| written)
+                .map_err(AioError::Return)
+        } else {
+            if error_nointr(&self.write) == Err(Errno::EINPROGRESS) {
+                return Err(AioError::Return(Errno::EINPROGRESS));
+            }
+            self.advance()?;
+            Err(AioError::Return(Errno::EINPROGRESS))
+        }
+    }
+
+    /// If the fsync stage fails, this reports the number of bytes written,
+    /// even though they might not be durable.
+    fn bytes_transferred(mut self: Pin<&mut Self>) -> Option<usize> {
+        let written = self.written;
+        staged_transferred(self.as_mut().aio_return(), written)
+    }
+
+    fn cancel(mut self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if self.written.is_some() {
+            cancel_nointr(self.fsync())
+        } else {
+            cancel_nointr(self.as_mut().write())
+        }
+        .map_err(AioError::Cancel)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self._deregister_raw()
+    }
+
+    /// Fails with `EINPROGRESS` until the fsync stage is done, or with the
+    /// write's error if that failed.
+    ///
+    /// This never advances from the write stage to the fsync stage; only
+    /// [`SourceApi::aio_return`] does that.  So after the write's event,
+    /// `error` will keep reporting `EINPROGRESS` until `aio_return` is called.
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.written.is_some() {
+            error_nointr(&self.fsync)
+        } else {
+            error_nointr(&self.write).and(Err(Errno::EINPROGRESS))
+        }
+        .map_err(AioError::Return)
+    }
+
+    fn in_progress(&self) -> bool {
+        self.write.in_progress() || self.fsync.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self._register_raw(kq, udata)
+    }
+
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        *self.as_mut().written() = None;
+        self.write().submit().map_err(AioError::Submit)
+    }
+}
+
+impl<'a> event::Source for WriteThenFsync<'a> {
+    /// Register this operation with a Mio reactor.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_interests(interests)?;
+        let udata = usize::from(token);
+        let kq = registry.as_raw_fd();
+        check_kq(self.write.as_ref(), self.in_progress(), kq)?;
+        self._register_raw(kq, udata);
+        Ok(())
+    }
+
+    /// Change the token, and possibly the reactor, of a registered
+    /// operation.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_token(
+            self.write.as_ref(),
+            self.in_progress(),
+            usize::from(token),
+        )?;
+        self.register(registry, token, interests)
+    }
+
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self._deregister_raw();
+        Ok(())
+    }
+}
+
+/// A write followed by an fsync of the same file, delivering a single event.
+///
+/// Unlike [`WriteThenFsync`], both stages are submitted together, rather than
+/// submitting the fsync when the write completes.  Nothing in this crate runs
+/// upon an operation's completion except the caller's event loop, so the
+/// latter would require an intermediate event.  Submitting both stages at
+/// once is safe because `aio_fsync` syncs every operation on the same file
+/// that was queued before it, so the kernel doesn't start the fsync until the
+/// write has completed.  Only the fsync notifies the reactor, so the caller
+/// sees exactly one event, after both stages are done.
+///
+/// The cost is that the fsync occupies an AIO queue slot for the whole
+/// operation, and runs even if the write fails.  Prefer `WriteThenSync` to
+/// halve the number of poll cycles, and [`WriteThenFsync`] when the write
+/// is likely to fail or queue slots are scarce.
+///
+/// Once both stages are done, [`SourceApi::aio_return`] reaps them, returning
+/// the number of bytes written, or the write's error, or else the fsync's
+/// error.
+///
+/// If the write is submitted but the fsync can't be, then
+/// [`SourceApi::submit`] cancels the write and blocks until it's finished
+/// before returning the error.
+///
+/// Dropping an in-progress `WriteThenSync` cancels both stages, and blocks
+/// until the kernel is done with them.
+///
+/// Create one with [`Source::write_then_sync`].
+#[derive(Debug)]
+pub struct WriteThenSync<'a> {
+    write:       aio::AioWrite<'a>,
+    fsync:       aio::AioFsync<'a>,
+    notify_mode: NotifyMode,
+}
+
+impl<'a> WriteThenSync<'a> {
+    pin_utils::unsafe_pinned!(write: aio::AioWrite<'a>);
+
+    pin_utils::unsafe_pinned!(fsync: aio::AioFsync<'a>);
+
+    fn _deregister_raw(&mut self) {
+        self.fsync.set_sigev_notify(SigevNotify::SigevNone);
+    }
+
+    /// Only the fsync notifies the reactor.
+    fn _register_raw(&mut self, kq: RawFd, udata: usize) {
+        let sigev = SigevNotify::SigevKeventFlags {
+            kq,
+            udata: udata as isize,
+            flags: self.notify_mode.flags(),
+        };
+        self.fsync.set_sigev_notify(sigev);
+    }
+
+    /// Choose how the kqueue delivers the completion event.
+    ///
+    /// Takes effect at the next registration.  See [`NotifyMode`].
+    pub fn set_notify_mode(&mut self, mode: NotifyMode) {
+        self.notify_mode = mode;
+    }
+
+    /// Choose how the kqueue delivers the completion event, for fluent
+    /// construction.
+    pub fn with_notify_mode(mut self, mode: NotifyMode) -> Self {
+        self.set_notify_mode(mode);
+        self
+    }
+}
+
+impl<'a> Drop for WriteThenSync<'a> {
+    fn drop(&mut self) {
+        // Safe because both stages will be dropped in place, right after this.
+        let write = unsafe { Pin::new_unchecked(&mut self.write) };
+        if write.in_progress() {
+            let _ = cancel_and_reap(write);
+        }
+        let fsync = unsafe { Pin::new_unchecked(&mut self.fsync) };
+        if fsync.in_progress() {
+            let _ = cancel_and_reap(fsync);
+        }
+    }
+}
+
+impl<'a> SourceApi for WriteThenSync<'a> {
+    type Output = usize;
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<usize, AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "aio_return before submit",
+            )));
+        }
+        if self.as_mut().error() == Err(AioError::Return(Errno::EINPROGRESS)) {
+            return Err(AioError::Return(Errno::EINPROGRESS));
+        }
+        let written = aio_return_nointr(self.as_mut().write());
+        let synced = aio_return_nointr(self.fsync());
+        let written = written.map_err(AioError::Return)?;
+        synced.map(|_| written).map_err(AioError::Return)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |n| n)
+    }
+
+    fn cancel(mut self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Cancel(misuse(
+                Errno::EINVAL,
+                "cancel before submit",
+            )));
+        }
+        let w =
+            cancel_nointr(self.as_mut().write()).map_err(AioError::Cancel)?;
+        let f = cancel_nointr(self.fsync()).map_err(AioError::Cancel)?;
+        Ok(match (w, f) {
+            (CancelStat::AioNotCanceled, _)
+            | (_, CancelStat::AioNotCanceled) => CancelStat::AioNotCanceled,
+            (CancelStat::AioAllDone, CancelStat::AioAllDone) => {
+                CancelStat::AioAllDone
+            }
+            _ => CancelStat::AioCanceled,
+        })
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self._deregister_raw()
+    }
+
+    /// Fails with `EINPROGRESS` until both stages are done.  Then returns the
+    /// write's error, if any, or else the fsync's.
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if !self.in_progress() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "error before submit",
+            )));
+        }
+        let synced = error_nointr(&self.fsync).map_err(AioError::Return);
+        if synced == Err(AioError::Return(Errno::EINPROGRESS)) {
+            return synced;
+        }
+        error_nointr(&self.write).map_err(AioError::Return)?;
+        synced
+    }
+
+    fn in_progress(&self) -> bool {
+        self.write.in_progress() || self.fsync.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self._register_raw(kq, udata)
+    }
+
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        self.as_mut().write().submit().map_err(AioError::Submit)?;
+        if let Err(e) = self.as_mut().fsync().submit() {
+            let _ = cancel_and_reap(self.write());
+            return Err(AioError::Submit(e));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> event::Source for WriteThenSync<'a> {
+    /// Register this operation with a Mio reactor.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_interests(interests)?;
+        let udata = usize::from(token);
+        let kq = registry.as_raw_fd();
+        check_kq(self.fsync.as_ref(), self.in_progress(), kq)?;
+        self._register_raw(kq, udata);
+        Ok(())
+    }
+
+    /// Change the token, and possibly the reactor, of a registered
+    /// operation.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_token(
+            self.fsync.as_ref(),
+            self.in_progress(),
+            usize::from(token),
+        )?;
+        self.register(registry, token, interests)
+    }
+
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self._deregister_raw();
+        Ok(())
+    }
+}
+
+/// A write followed by a read of the same region, to verify the data.
+///
+/// This is useful for detecting bad media.  The read is not submitted until
+/// the write has completed, and both stages notify the same kqueue with the
+/// same token, just like [`WriteThenFsync`].  When the write's event arrives,
+/// [`SourceApi::aio_return`] will reap the write, submit the read, and fail
+/// with `EINPROGRESS`; the caller should simply keep polling.  Once the read's
+/// event arrives, `aio_return` will return the number of bytes written, or
+/// fail with `EINTEGRITY` if the data read back didn't match.
+///
+/// Unless the file was opened with `O_DIRECT`, the read will most likely be
+/// satisfied from the cache, verifying little.
+///
+/// Dropping an in-progress `WriteAtVerified` cancels whichever stage is in
+/// flight, and blocks until the kernel is done with it.
+///
+/// Create one with [`Source::write_at_verified`].
+#[derive(Debug)]
+pub struct WriteAtVerified<'a> {
+    write:       aio::AioWrite<'a>,
+    read:        aio::AioRead<'a>,
+    /// The data that should have been written
+    buf:         &'a [u8],
+    notify_mode: NotifyMode,
+    /// Result of the write stage, once it has been reaped.
+    written:     Option<usize>,
+}
+
+impl<'a> WriteAtVerified<'a> {
+    pin_utils::unsafe_pinned!(write: aio::AioWrite<'a>);
+
+    pin_utils::unsafe_pinned!(read: aio::AioRead<'a>);
+
+    pin_utils::unsafe_unpinned!(written: Option<usize>);
+
+    /// Reap the completed write and submit the read.
+    fn advance(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        let written = aio_return_nointr(self.as_mut().write())
+            .map_err(AioError::Return)?;
+        *self.as_mut().written() = Some(written);
+        // Only read back what was actually written.  Safe because we don't
+        // move the read, and it isn't in progress.
+        unsafe { self.as_mut().read().get_unchecked_mut() }
+            .as_mut()
+            .aio_nbytes = written;
+        self.read().submit().map_err(AioError::Submit)
+    }
+
+    fn _deregister_raw(&mut self) {
+        self.write.set_sigev_notify(SigevNotify::SigevNone);
+        self.read.set_sigev_notify(SigevNotify::SigevNone);
+    }
+
+    fn _register_raw(&mut self, kq: RawFd, udata: usize) {
+        let sigev = SigevNotify::SigevKeventFlags {
+            kq,
+            udata: udata as isize,
+            flags: self.notify_mode.flags(),
+        };
+        self.write.set_sigev_notify(sigev);
+        self.read.set_sigev_notify(sigev);
+    }
+
+    /// Choose how the kqueue delivers both stages' completion events.
+    ///
+    /// Takes effect at the next registration.  See [`NotifyMode`].
+    pub fn set_notify_mode(&mut self, mode: NotifyMode) {
+        self.notify_mode = mode;
+    }
+
+    /// Choose how the kqueue delivers both stages' completion events, for
+    /// fluent construction.
+    pub fn with_notify_mode(mut self, mode: NotifyMode) -> Self {
+        self.set_notify_mode(mode);
+        self
+    }
+}
+
+impl<'a> Drop for WriteAtVerified<'a> {
+    fn drop(&mut self) {
+        // Safe because both stages will be dropped in place, right after this.
+        let write = unsafe { Pin::new_unchecked(&mut self.write) };
+        if write.in_progress() {
+            let _ = cancel_and_reap(write);
+        }
+        let read = unsafe { Pin::new_unchecked(&mut self.read) };
+        if read.in_progress() {
+            let _ = cancel_and_reap(read);
+        }
+    }
+}
+
+impl<'a> SourceApi for WriteAtVerified<'a> {
+    type Output = usize;
+
+    /// Reap whichever stage has finished.
+    ///
+    /// If the write has finished, this reaps it, submits the read, and fails
+    /// with `EINPROGRESS`.  If the read has finished, it verifies the data and
+    /// returns the number of bytes written.  A stage that's still running is
+    /// never reaped.
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<usize, AioError> {
+        if let Some(written) = self.written {
+            if error_nointr(&self.read) == Err(Errno::EINPROGRESS) {
+                return Err(AioError::Return(Errno::EINPROGRESS));
+            }
+            let nread = aio_return_nointr(self.as_mut().read())
+                .map_err(AioError::Return)?;
+            let aiocb: &libc::aiocb = self.read.as_ref();
+            // Safe because the kernel is done with the buffer, and it's
+            // borrowed for 'a.
+            let readback = unsafe {
+                std::slice::from_raw_parts(aiocb.aio_buf as *const u8, nread)
+            };
+            if readback == &self.buf[..written] {
+                Ok(written)
+            } else {
+                Err(AioError::Return(Errno::EINTEGRITY))
+            }
+        } else {
+            if error_nointr(&self.write) == Err(Errno::EINPROGRESS) {
+                return Err(AioError::Return(Errno::EINPROGRESS));
+            }
+            self.advance()?;
+            Err(AioError::Return(Errno::EINPROGRESS))
+        }
+    }
+
+    /// If the verification stage fails, this reports the number of bytes
+    /// written, even though they might not be correct.
+    fn bytes_transferred(mut self: Pin<&mut Self>) -> Option<usize> {
+        let written = self.written;
+        staged_transferred(self.as_mut().aio_return(), written)
+    }
+
+    fn cancel(mut self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if self.written.is_some() {
+            cancel_nointr(self.read())
+        } else {
+            cancel_nointr(self.as_mut().write())
+        }
+        .map_err(AioError::Cancel)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self._deregister_raw()
+    }
+
+    /// Fails with `EINPROGRESS` until the read stage is done, or with the
+    /// write's error if that failed.
+    ///
+    /// Like [`WriteThenFsync`]'s, this never advances to the next stage, and
+    /// it doesn't verify the data; only [`SourceApi::aio_return`] does those.
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.written.is_some() {
+            error_nointr(&self.read)
+        } else {
+            error_nointr(&self.write).and(Err(Errno::EINPROGRESS))
+        }
+        .map_err(AioError::Return)
+    }
+
+    fn in_progress(&self) -> bool {
+        self.write.in_progress() || self.read.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self._register_raw(kq, udata)
+    }
+
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        *self.as_mut().written() = None;
+        self.write().submit().map_err(AioError::Submit)
+    }
+}
+
+impl<'a> event::Source for WriteAtVerified<'a> {
+    /// Register this operation with a Mio reactor.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_interests(interests)?;
+        let udata = usize::from(token);
+        let kq = registry.as_raw_fd();
+        check_kq(self.write.as_ref(), self.in_progress(), kq)?;
+        self._register_raw(kq, udata);
+        Ok(())
+    }
+
+    /// Change the token, and possibly the reactor, of a registered
+    /// operation.
+    ///
+    /// See [`Source`'s registration rules](Source#registration).
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_token(
+            self.write.as_ref(),
+            self.in_progress(),
+            usize::from(token),
+        )?;
+        self.register(registry, token, interests)
+    }
+
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self._deregister_raw();
+        Ok(())
+    }
+}
+
+/// A read that passes its data to a closure upon completion.
+///
+/// Create one with [`Source::on_complete`].
+pub struct OnComplete<'a, F> {
+    source: ReadAt<'a>,
+    f:      F,
+}
+
+impl<'a, F> OnComplete<'a, F> {
+    pin_utils::unsafe_pinned!(source: ReadAt<'a>);
+
+    pin_utils::unsafe_unpinned!(f: F);
+}
+
+impl<'a, F> fmt::Debug for OnComplete<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnComplete")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, F: FnMut(&[u8])> SourceApi for OnComplete<'a, F> {
+    type Output = usize;
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<usize, AioError> {
+        let nbytes = self.as_mut().source().aio_return()?;
+        let aiocb: &libc::aiocb = self.source.inner.as_ref();
+        // Safe because the kernel is done with the buffer, and it's borrowed
+        // for 'a.
+        let data = unsafe {
+            std::slice::from_raw_parts(aiocb.aio_buf as *const u8, nbytes)
+        };
+        (self.f())(data);
+        Ok(nbytes)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |n| n)
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().submit()
+    }
+}
+
+impl<'a, F> event::Source for OnComplete<'a, F> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A read that carries a caller-supplied index, such as a buffer pool slot.
+///
+/// Upon completion, [`SourceApi::aio_return`] returns the index along with
+/// the number of bytes read.  Create one with [`Source::read_at_indexed`].
+#[derive(Debug)]
+pub struct ReadAtIndexed<'a> {
+    source: ReadAt<'a>,
+    index:  usize,
+}
+
+impl<'a> ReadAtIndexed<'a> {
+    pin_utils::unsafe_pinned!(source: ReadAt<'a>);
+
+    /// The index supplied when the operation was created.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<'a> SourceApi for ReadAtIndexed<'a> {
+    /// The index, and the number of bytes read.
+    type Output = (usize, usize);
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<Self::Output, AioError> {
+        let nbytes = self.as_mut().source().aio_return()?;
+        Ok((self.index, nbytes))
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for ReadAtIndexed<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A read into an owned buffer.
+///
+/// Create one with [`Source::read_into`].
+#[derive(Debug)]
+pub struct ReadInto<'a> {
+    // Must be declared before buf, so that it gets dropped first.
+    source: ReadAt<'a>,
+    buf:    Option<Vec<u8>>,
+}
+
+impl<'a> ReadInto<'a> {
+    pin_utils::unsafe_pinned!(source: ReadAt<'a>);
+
+    pin_utils::unsafe_unpinned!(buf: Option<Vec<u8>>);
+
+    /// Take the operation apart, so it can be rebuilt and retried.
+    ///
+    /// This is for recovering from a failed [`SourceApi::aio_return`], for
+    /// example after remounting a file system, without reallocating the
+    /// buffer.  Returns the buffer, file descriptor, offset, and priority, as
+    /// passed to [`Source::read_into`].
+    ///
+    /// # Errors
+    ///
+    /// - `EBUSY`: the operation is still in progress.
+    /// - `EINVAL`: the buffer was already reclaimed by a successful
+    ///   `aio_return`.
+    pub fn into_retry_parts(
+        self: Pin<Box<Self>>,
+    ) -> nix::Result<(Vec<u8>, BorrowedFd<'a>, u64, i32)> {
+        if self.in_progress() {
+            return Err(misuse(
+                Errno::EBUSY,
+                "into_retry_parts while in progress",
+            ));
+        }
+        if self.buf.is_none() {
+            return Err(misuse(
+                Errno::EINVAL,
+                "into_retry_parts after reclaiming buffer",
+            ));
+        }
+        // Safe because the operation isn't in progress, so the kernel has no
+        // pointers to it.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+        let ReadInto { source, buf } = *this;
+        // Safe because the constructor's BorrowedFd guarantees that the file
+        // remains open for 'a.
+        let fd = unsafe { BorrowedFd::borrow_raw(source.fd()) };
+        let offs = source._offset();
+        let prio = source.priority();
+        // Drop the operation before returning the buffer that it borrows.
+        drop(source);
+        Ok((buf.unwrap(), fd, offs, prio))
+    }
+}
+
+impl<'a> SourceApi for ReadInto<'a> {
+    type Output = (Vec<u8>, usize);
+
+    /// Read the final result of the operation, and reclaim the buffer.
+    ///
+    /// Once this succeeds, the buffer is gone, so the operation may not be
+    /// submitted again.
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<Self::Output, AioError> {
+        let nbytes = self.as_mut().source().aio_return()?;
+        let buf = self.buf().take().expect("buffer already reclaimed");
+        Ok((buf, nbytes))
+    }
+
+    /// Unlike [`SourceApi::aio_return`], this doesn't reclaim the buffer.
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    /// Start the operation.
+    ///
+    /// Fails with `EINVAL` if the buffer has already been reclaimed by
+    /// [`SourceApi::aio_return`].
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.buf.is_none() {
+            return Err(AioError::Submit(misuse(
+                Errno::EINVAL,
+                "submit after reclaiming buffer",
+            )));
+        }
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for ReadInto<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A write from an owned buffer.
+///
+/// Create one with [`Source::write_from`].
+#[derive(Debug)]
+pub struct WriteFrom<'a> {
+    // Must be declared before buf, so that it gets dropped first.
+    source: WriteAt<'a>,
+    buf:    Option<Vec<u8>>,
+}
+
+impl<'a> WriteFrom<'a> {
+    pin_utils::unsafe_pinned!(source: WriteAt<'a>);
+
+    pin_utils::unsafe_unpinned!(buf: Option<Vec<u8>>);
+
+    /// Take the operation apart, so it can be rebuilt and retried.
+    ///
+    /// This is for recovering from a failed [`SourceApi::aio_return`], for
+    /// example after remounting a file system, without reallocating the
+    /// buffer.  Returns the buffer, file descriptor, offset, and priority, as
+    /// passed to [`Source::write_from`].
+    ///
+    /// # Errors
+    ///
+    /// - `EBUSY`: the operation is still in progress.
+    /// - `EINVAL`: the buffer was already reclaimed by a successful
+    ///   `aio_return`.
+    pub fn into_retry_parts(
+        self: Pin<Box<Self>>,
+    ) -> nix::Result<(Box<[u8]>, BorrowedFd<'a>, u64, i32)> {
+        if self.in_progress() {
+            return Err(misuse(
+                Errno::EBUSY,
+                "into_retry_parts while in progress",
+            ));
+        }
+        if self.buf.is_none() {
+            return Err(misuse(
+                Errno::EINVAL,
+                "into_retry_parts after reclaiming buffer",
+            ));
+        }
+        // Safe because the operation isn't in progress, so the kernel has no
+        // pointers to it.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+        let WriteFrom { source, buf } = *this;
+        // Safe because the constructor's BorrowedFd guarantees that the file
+        // remains open for 'a.
+        let fd = unsafe { BorrowedFd::borrow_raw(source.fd()) };
+        let offs = source._offset();
+        let prio = source.priority();
+        // Drop the operation before returning the buffer that it borrows.
+        drop(source);
+        Ok((buf.unwrap().into_boxed_slice(), fd, offs, prio))
+    }
+}
+
+impl<'a> SourceApi for WriteFrom<'a> {
+    type Output = (Box<[u8]>, usize);
+
+    /// Read the final result of the operation, and reclaim the buffer.
+    ///
+    /// Once this succeeds, the buffer is gone, so the operation may not be
+    /// submitted again.
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<Self::Output, AioError> {
+        let nbytes = self.as_mut().source().aio_return()?;
+        let buf = self.buf().take().expect("buffer already reclaimed");
+        Ok((buf.into_boxed_slice(), nbytes))
+    }
+
+    /// Unlike [`SourceApi::aio_return`], this doesn't reclaim the buffer.
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    /// Start the operation.
+    ///
+    /// Fails with `EINVAL` if the buffer has already been reclaimed by
+    /// [`SourceApi::aio_return`].
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.buf.is_none() {
+            return Err(AioError::Submit(misuse(
+                Errno::EINVAL,
+                "submit after reclaiming buffer",
+            )));
+        }
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for WriteFrom<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A vectored read whose `IoSliceMut` array is owned by the operation.
+///
+/// Create one with [`Source::readv_at_owned`].
+#[derive(Debug)]
+pub struct ReadvAtOwned<'a> {
+    // Must be declared before iovs, so that it gets dropped first.
+    source: ReadvAt<'a>,
+    iovs:   Vec<IoSliceMut<'a>>,
+}
+
+impl<'a> ReadvAtOwned<'a> {
+    pin_utils::unsafe_pinned!(source: ReadvAt<'a>);
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self.source.offset()
+    }
+
+    /// How many buffers does this operation fill?
+    pub fn iovcnt(&self) -> usize {
+        self.iovs.len()
+    }
+
+    /// The total number of bytes that this operation will attempt to read.
+    pub fn total_len(&self) -> usize {
+        self.source.total_len()
+    }
+}
+
+impl<'a> SourceApi for ReadvAtOwned<'a> {
+    type Output = usize;
+
+    fn aio_return(self: Pin<&mut Self>) -> Result<usize, AioError> {
+        self.source().aio_return()
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for ReadvAtOwned<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A vectored write whose `IoSlice` array is owned by the operation.
+///
+/// Create one with [`Source::writev_at_owned`].
+#[derive(Debug)]
+pub struct WritevAtOwned<'a> {
+    // Must be declared before iovs, so that it gets dropped first.
+    source: WritevAt<'a>,
+    iovs:   Vec<IoSlice<'a>>,
+}
+
+impl<'a> WritevAtOwned<'a> {
+    pin_utils::unsafe_pinned!(source: WritevAt<'a>);
+
+    /// The file offset at which this operation will start.
+    pub fn offset(&self) -> u64 {
+        self.source.offset()
+    }
+
+    /// How many buffers does this operation write from?
+    pub fn iovcnt(&self) -> usize {
+        self.iovs.len()
+    }
+
+    /// The total number of bytes that this operation will attempt to write.
+    pub fn total_len(&self) -> usize {
+        self.source.total_len()
+    }
+}
+
+impl<'a> SourceApi for WritevAtOwned<'a> {
+    type Output = usize;
+
+    fn aio_return(self: Pin<&mut Self>) -> Result<usize, AioError> {
+        self.source().aio_return()
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for WritevAtOwned<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
     }
 }
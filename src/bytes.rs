@@ -0,0 +1,304 @@
+// vim: tw=80
+#[cfg(feature = "tokio")]
+use std::os::unix::io::RawFd;
+use std::{io, mem::MaybeUninit, os::unix::io::BorrowedFd, pin::Pin};
+
+use ::bytes::{Bytes, BytesMut};
+use mio::{event, Interest, Registry, Token};
+use nix::{errno::Errno, sys::aio};
+
+use crate::{
+    aio::{CancelStat, ReadAt, Source, SourceApi, WriteAt},
+    error::{misuse, AioError},
+    priority::AioPriority,
+};
+
+impl<'a> Source<aio::AioRead<'a>> {
+    /// Asynchronously read from a file into the spare capacity of a
+    /// `BytesMut`.
+    ///
+    /// The operation will try to fill everything between `buf.len()` and
+    /// `buf.capacity()`, so reserve space first.  [`SourceApi::aio_return`]
+    /// will hand back `buf`, with its length advanced by the number of bytes
+    /// read.
+    pub fn read_bytes(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        mut buf: BytesMut,
+        prio: impl Into<AioPriority>,
+    ) -> ReadBytes<'a> {
+        let len = buf.len();
+        let spare = buf.capacity() - len;
+        // Safe because the BytesMut's heap allocation will outlive the
+        // operation, and won't move even if the BytesMut itself does.
+        let slice = unsafe {
+            std::slice::from_raw_parts_mut(
+                buf.as_mut_ptr().add(len).cast::<MaybeUninit<u8>>(),
+                spare,
+            )
+        };
+        ReadBytes {
+            source: Source::read_at_uninit(fd, offs, slice, prio),
+            buf:    Some(buf),
+        }
+    }
+}
+
+impl<'a> Source<aio::AioWrite<'a>> {
+    /// Asynchronously write the contents of a `Bytes` to a file.
+    ///
+    /// The operation holds a reference to `buf` until it's dropped, so `buf`
+    /// needn't be uniquely owned, and no copy is made.
+    pub fn write_bytes(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: Bytes,
+        prio: impl Into<AioPriority>,
+    ) -> WriteBytes<'a> {
+        // Safe because the data is immutable and will outlive the operation,
+        // and won't move even if the Bytes itself does.
+        let slice =
+            unsafe { std::slice::from_raw_parts(buf.as_ptr(), buf.len()) };
+        WriteBytes {
+            source: Source::write_at(fd, offs, slice, prio),
+            _buf:   buf,
+        }
+    }
+}
+
+/// A read into the spare capacity of a `BytesMut`.
+///
+/// Create one with [`Source::read_bytes`].
+#[derive(Debug)]
+pub struct ReadBytes<'a> {
+    // Must be declared before buf, so that it gets dropped first.
+    source: ReadAt<'a>,
+    buf:    Option<BytesMut>,
+}
+
+impl<'a> ReadBytes<'a> {
+    pin_utils::unsafe_pinned!(source: ReadAt<'a>);
+
+    pin_utils::unsafe_unpinned!(buf: Option<BytesMut>);
+
+    /// Take the operation apart, so it can be rebuilt and retried.
+    ///
+    /// Like [`ReadInto::into_retry_parts`](crate::ReadInto::into_retry_parts),
+    /// but for a `BytesMut`, whose length is unchanged.
+    pub fn into_retry_parts(
+        self: Pin<Box<Self>>,
+    ) -> nix::Result<(BytesMut, BorrowedFd<'a>, u64, i32)> {
+        if self.in_progress() {
+            return Err(misuse(
+                Errno::EBUSY,
+                "into_retry_parts while in progress",
+            ));
+        }
+        if self.buf.is_none() {
+            return Err(misuse(
+                Errno::EINVAL,
+                "into_retry_parts after reclaiming buffer",
+            ));
+        }
+        // Safe because the operation isn't in progress, so the kernel has no
+        // pointers to it.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+        let ReadBytes { source, buf } = *this;
+        // Safe because the constructor's BorrowedFd guarantees that the file
+        // remains open for 'a.
+        let fd = unsafe { BorrowedFd::borrow_raw(source.fd()) };
+        let offs = source.offset();
+        let prio = source.priority();
+        // Drop the operation before returning the buffer that it borrows.
+        drop(source);
+        Ok((buf.unwrap(), fd, offs, prio))
+    }
+}
+
+impl<'a> SourceApi for ReadBytes<'a> {
+    type Output = BytesMut;
+
+    /// Read the final result of the operation, and reclaim the buffer.
+    ///
+    /// The buffer's length will have been advanced by the number of bytes
+    /// read.  Once this succeeds, the buffer is gone, so the operation may
+    /// not be submitted again.
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<BytesMut, AioError> {
+        let nbytes = self.as_mut().source().aio_return()?;
+        let mut buf = self.buf().take().expect("buffer already reclaimed");
+        // Safe because the kernel initialized this many bytes past the old
+        // length.
+        unsafe { buf.set_len(buf.len() + nbytes) };
+        Ok(buf)
+    }
+
+    /// Unlike [`SourceApi::aio_return`], this doesn't reclaim the buffer.
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    /// Start the operation.
+    ///
+    /// Fails with `EINVAL` if the buffer has already been reclaimed by
+    /// [`SourceApi::aio_return`].
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.buf.is_none() {
+            return Err(AioError::Submit(misuse(
+                Errno::EINVAL,
+                "submit after reclaiming buffer",
+            )));
+        }
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for ReadBytes<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
+
+/// A write from a reference-counted `Bytes` buffer.
+///
+/// Create one with [`Source::write_bytes`].  Since the operation keeps its
+/// reference to the buffer, it may be submitted again after completion.
+#[derive(Debug)]
+pub struct WriteBytes<'a> {
+    // Must be declared before _buf, so that it gets dropped first.
+    source: WriteAt<'a>,
+    _buf:   Bytes,
+}
+
+impl<'a> WriteBytes<'a> {
+    pin_utils::unsafe_pinned!(source: WriteAt<'a>);
+
+    /// Take the operation apart, so it can be rebuilt and retried.
+    ///
+    /// Like [`WriteFrom::into_retry_parts`](crate::WriteFrom::into_retry_parts),
+    /// but for a `Bytes`.  Fails with `EBUSY` if the operation is still in
+    /// progress.
+    pub fn into_retry_parts(
+        self: Pin<Box<Self>>,
+    ) -> nix::Result<(Bytes, BorrowedFd<'a>, u64, i32)> {
+        if self.in_progress() {
+            return Err(misuse(
+                Errno::EBUSY,
+                "into_retry_parts while in progress",
+            ));
+        }
+        // Safe because the operation isn't in progress, so the kernel has no
+        // pointers to it.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+        let WriteBytes { source, _buf: buf } = *this;
+        // Safe because the constructor's BorrowedFd guarantees that the file
+        // remains open for 'a.
+        let fd = unsafe { BorrowedFd::borrow_raw(source.fd()) };
+        let offs = source.offset();
+        let prio = source.priority();
+        // Drop the operation before returning the buffer that it borrows.
+        drop(source);
+        Ok((buf, fd, offs, prio))
+    }
+}
+
+impl<'a> SourceApi for WriteBytes<'a> {
+    type Output = usize;
+
+    fn aio_return(self: Pin<&mut Self>) -> Result<usize, AioError> {
+        self.source().aio_return()
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        self.source().bytes_transferred()
+    }
+
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        self.source().cancel()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.source.deregister_raw()
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().error()
+    }
+
+    fn in_progress(&self) -> bool {
+        self.source.in_progress()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.source.register_raw(kq, udata)
+    }
+
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        self.source().submit()
+    }
+}
+
+impl<'a> event::Source for WriteBytes<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.source.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.source.deregister(registry)
+    }
+}
@@ -0,0 +1,191 @@
+// vim: tw=80
+use std::{
+    cell::UnsafeCell,
+    mem,
+    os::unix::io::{AsRawFd, BorrowedFd},
+    panic::{self, AssertUnwindSafe},
+    process,
+    ptr,
+    sync::Arc,
+};
+
+use nix::{errno::Errno, libc, sys::aio};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::{
+    aio::{checked_offset, owned_slice, Source},
+    error::AioError,
+    priority::AioPriority,
+};
+
+/// Signature of a `SIGEV_THREAD` notification function.
+type NotifyFn = extern "C" fn(libc::sigval);
+
+/// FreeBSD's `struct sigevent`, with its union's `_sigev_thread` member.
+///
+/// The `libc` crate exposes only the union's `sigev_notify_thread_id` member,
+/// which can't hold the notification function.
+#[repr(C)]
+struct SigevThread {
+    sigev_notify: libc::c_int,
+    sigev_signo: libc::c_int,
+    sigev_value: libc::sigval,
+    sigev_notify_function: Option<NotifyFn>,
+    /// Null selects the default thread attributes.
+    sigev_notify_attributes: *mut libc::c_void,
+    /// The rest of the union
+    _spare: [libc::c_long; 6],
+}
+
+const _: () =
+    assert!(mem::size_of::<SigevThread>() == mem::size_of::<libc::sigevent>());
+const _: () = assert!(
+    mem::align_of::<SigevThread>() == mem::align_of::<libc::sigevent>()
+);
+
+/// An aiocb shared by the submitting thread and [`notify`].
+///
+/// The C library writes to the aiocb after the `aio_write` syscall returns,
+/// possibly after the operation has completed and `notify` has run.  So it's
+/// freed by whichever of the two is done with it last.
+struct SharedAiocb(UnsafeCell<libc::aiocb>);
+
+// Safe because the aiocb is only accessed through raw pointers, by the
+// submitting thread until `aio_write` returns and by `notify` afterwards.
+// Any overlap is between the C library and the kernel, not Rust code.
+unsafe impl Send for SharedAiocb {}
+unsafe impl Sync for SharedAiocb {}
+
+/// Everything [`notify`] needs.
+///
+/// It's handed to `notify` through the sigevent before submission, and
+/// reclaimed by `notify` upon completion.
+struct Pending<F> {
+    aiocb:    Arc<SharedAiocb>,
+    buf:      Vec<u8>,
+    callback: F,
+}
+
+/// Completion handler for [`Source::write_from_with_callback`].
+///
+/// Runs on a thread owned by the C library.
+extern "C" fn notify<F>(sv: libc::sigval)
+where
+    F: FnOnce(Result<usize, AioError>) + Send + 'static,
+{
+    // Safe because write_from_with_callback handed this Box to us, and the
+    // kernel notifies only once.
+    let pending = unsafe { Box::from_raw(sv.sival_ptr as *mut Pending<F>) };
+    let Pending {
+        aiocb,
+        buf,
+        callback,
+    } = *pending;
+    // Safe because the operation has completed, and the aiocb is still live.
+    let r = Errno::result(unsafe { libc::aio_return(aiocb.0.get()) })
+        .map(|n| n as usize);
+    #[cfg(feature = "metrics")]
+    metrics::record_return(&r);
+    // The kernel is done with the buffer, so free it before calling back.
+    drop((aiocb, buf));
+    let r = r.map_err(AioError::Return);
+    // Unwinding into C would be undefined behavior.
+    if panic::catch_unwind(AssertUnwindSafe(|| callback(r))).is_err() {
+        process::abort();
+    }
+}
+
+impl<'a> Source<aio::AioWrite<'a>> {
+    /// Submit a fire-and-forget write of an owned buffer, and call `callback`
+    /// with its result once it completes.
+    ///
+    /// Unlike every other operation in this crate, the write isn't a Mio
+    /// source and needn't be polled.  Instead, it's submitted immediately with
+    /// `SIGEV_THREAD` notification, and upon completion the C library calls
+    /// `callback` with the number of bytes written, or the error.  The buffer
+    /// is dropped just before `callback` runs.
+    ///
+    /// If submission fails, the error is returned as [`AioError::Submit`], and
+    /// `callback` is dropped without being called.  Fails with `EOVERFLOW` if
+    /// `offs` doesn't fit in an `off_t`.  An error from the write itself is
+    /// passed to `callback` as [`AioError::Return`].
+    ///
+    /// # Thread safety
+    ///
+    /// `callback` runs on a thread created by the C library, not on the
+    /// calling thread, and possibly before this function even returns.  That's
+    /// why it must be `Send + 'static`: it can't borrow anything from the
+    /// caller's stack, and any state it shares with the caller must be
+    /// synchronized, for example with a channel or a `Mutex`.  The thread may
+    /// be shared with other notifications, so `callback` should return
+    /// promptly rather than block.  If it panics, the process aborts.
+    ///
+    /// The file descriptor may be closed before `callback` runs, because the
+    /// kernel holds its own reference to the file until the write completes.
+    /// Only the descriptor's number is recorded, and it isn't used again after
+    /// this function returns.
+    pub fn write_from_with_callback<F>(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: Box<[u8]>,
+        prio: impl Into<AioPriority>,
+        callback: F,
+    ) -> Result<(), AioError>
+    where
+        F: FnOnce(Result<usize, AioError>) + Send + 'static,
+    {
+        let offs = checked_offset(offs).map_err(AioError::Submit)?;
+        let buf = buf.into_vec();
+        // Safe because the Pending owns buf until notify frees it.
+        let slice = unsafe { owned_slice(&buf) };
+        // Safe because every field of an aiocb may be zero.
+        let aiocb =
+            Arc::new(SharedAiocb(UnsafeCell::new(unsafe { mem::zeroed() })));
+        let cb = aiocb.0.get();
+        // Safe because nothing else can access the aiocb until it's
+        // submitted.
+        unsafe {
+            (*cb).aio_fildes = fd.as_raw_fd();
+            (*cb).aio_offset = offs;
+            (*cb).aio_buf = slice.as_ptr() as *mut libc::c_void;
+            (*cb).aio_nbytes = slice.len();
+            (*cb).aio_reqprio = prio.into().get();
+        }
+        let pending = Box::into_raw(Box::new(Pending {
+            aiocb: aiocb.clone(),
+            buf,
+            callback,
+        }));
+        let sigev = SigevThread {
+            sigev_notify: libc::SIGEV_THREAD,
+            sigev_signo: 0,
+            sigev_value: libc::sigval {
+                sival_ptr: pending.cast(),
+            },
+            sigev_notify_function: Some(notify::<F>),
+            sigev_notify_attributes: ptr::null_mut(),
+            _spare: [0; 6],
+        };
+        // Safe because SigevThread has the same size and alignment as
+        // sigevent, and the aiocb isn't submitted yet.
+        unsafe {
+            ptr::addr_of_mut!((*cb).aio_sigevent)
+                .cast::<SigevThread>()
+                .write(sigev)
+        };
+        // Use libc directly, because nix doesn't support SIGEV_THREAD.  From
+        // here on, notify owns the Pending.
+        let r = Errno::result(unsafe { libc::aio_write(cb) }).map(drop);
+        #[cfg(feature = "metrics")]
+        metrics::record_submit(&r);
+        if r.is_err() {
+            // Safe because the kernel never accepted the operation, so notify
+            // will never run.
+            drop(unsafe { Box::from_raw(pending) });
+        }
+        // The C library is done with the aiocb, so release our reference.
+        drop(aiocb);
+        r.map_err(AioError::Submit)
+    }
+}
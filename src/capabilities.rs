@@ -0,0 +1,391 @@
+// vim: tw=80
+use std::{
+    alloc::{self, Layout},
+    ffi::CStr,
+    fmt,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    os::unix::io::{AsRawFd, BorrowedFd},
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicI8, AtomicU8, Ordering},
+};
+
+use nix::{errno::Errno, libc, sys::aio};
+
+use crate::{aio::Source, priority::AioPriority};
+
+/// `_IOR('d', 128, u_int)` from `<sys/disk.h>`
+const DIOCGSECTORSIZE: libc::c_ulong = 0x4004_6480;
+
+/// Report the buffer and offset alignment required for direct I/O on a file.
+///
+/// For disk devices, this is the device's sector size.  For files on a file
+/// system, it's the file system's fundamental block size, which is always a
+/// multiple of the underlying device's sector size.  Callers using `O_DIRECT`
+/// should align their buffers, offsets, and lengths to this value.
+pub fn required_alignment(fd: BorrowedFd) -> nix::Result<usize> {
+    let raw = fd.as_raw_fd();
+    let mut sb = MaybeUninit::<libc::stat>::uninit();
+    // Safe because sb is large enough
+    Errno::result(unsafe { libc::fstat(raw, sb.as_mut_ptr()) })?;
+    let sb = unsafe { sb.assume_init() };
+    if sb.st_mode & libc::S_IFMT == libc::S_IFCHR {
+        let mut sectorsize: libc::c_uint = 0;
+        // Safe because DIOCGSECTORSIZE writes a single u_int
+        Errno::result(unsafe {
+            libc::ioctl(raw, DIOCGSECTORSIZE, &mut sectorsize)
+        })?;
+        Ok(sectorsize as usize)
+    } else {
+        let mut sfs = MaybeUninit::<libc::statfs>::uninit();
+        // Safe because sfs is large enough
+        Errno::result(unsafe { libc::fstatfs(raw, sfs.as_mut_ptr()) })?;
+        let sfs = unsafe { sfs.assume_init() };
+        Ok(sfs.f_bsize as usize)
+    }
+}
+
+/// System limits on how many AIO operations may be in flight, as returned by
+/// [`aio_limits`].
+///
+/// Any limit that can't be determined is reported as the minimum that POSIX
+/// allows, so each is always at least 1.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct AioLimits {
+    /// The most operations that a single `lio_listio` call may submit, or
+    /// `AIO_LISTIO_MAX`.  Larger [`LioCb`](crate::LioCb)s will fail with
+    /// `EINVAL`.
+    pub listio_max:         usize,
+    /// The most operations that a single process may have queued at once, or
+    /// the `vfs.aio.max_aio_queue_per_proc` sysctl.  Beyond this,
+    /// submission fails with `EAGAIN`.
+    pub max_queue_per_proc: usize,
+    /// The most operations that may be queued at once, system-wide, or the
+    /// `vfs.aio.max_aio_queue` sysctl.
+    pub max_queue:          usize,
+}
+
+/// Read a positive integer sysctl by its nul-terminated name.
+fn sysctl_int(name: &[u8]) -> Option<usize> {
+    let name = CStr::from_bytes_with_nul(name).ok()?;
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of_val(&value);
+    // Safe because value is large enough, and sysctlbyname won't write more
+    // than len bytes.
+    let r = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            (&mut value as *mut libc::c_int).cast(),
+            &mut len,
+            ptr::null(),
+            0,
+        )
+    };
+    (r == 0 && value > 0).then_some(value as usize)
+}
+
+/// Read a positive `sysconf` variable.
+fn sysconf_positive(name: libc::c_int) -> Option<usize> {
+    // Safe because sysconf has no side effects.
+    let r = unsafe { libc::sysconf(name) };
+    (r > 0).then_some(r as usize)
+}
+
+/// Report the system's limits on queued AIO operations, for sizing batches.
+///
+/// The limits are read afresh on every call, since an administrator may
+/// change the sysctls at any time.
+pub fn aio_limits() -> AioLimits {
+    // The minimum values of _POSIX_AIO_LISTIO_MAX and _POSIX_AIO_MAX
+    const POSIX_AIO_LISTIO_MAX: usize = 2;
+    const POSIX_AIO_MAX: usize = 1;
+
+    let listio_max = sysconf_positive(libc::_SC_AIO_LISTIO_MAX)
+        .unwrap_or(POSIX_AIO_LISTIO_MAX);
+    let max_queue = sysctl_int(b"vfs.aio.max_aio_queue\0")
+        .or_else(|| sysconf_positive(libc::_SC_AIO_MAX))
+        .unwrap_or(POSIX_AIO_MAX);
+    let max_queue_per_proc =
+        sysctl_int(b"vfs.aio.max_aio_queue_per_proc\0").unwrap_or(max_queue);
+    AioLimits {
+        listio_max,
+        max_queue_per_proc,
+        max_queue,
+    }
+}
+
+/// The first `__FreeBSD_version` with `aio_readv` and `aio_writev`
+const VECTORED_OSRELDATE: usize = 1_300_000;
+
+/// Cached result of [`supports_vectored`]: 1 for true, 0 for false, or -1 if
+/// not yet known.
+static VECTORED: AtomicI8 = AtomicI8::new(-1);
+
+/// Does the running kernel support vectored operations?
+///
+/// [`Source::readv_at`] and [`Source::writev_at`] require FreeBSD 13.0 or
+/// later.  On older kernels, applications can instead issue one operation per
+/// buffer.  This checks the running kernel's version rather than probing with
+/// an actual operation, because calling a missing syscall would kill the
+/// process with `SIGSYS`.  The result is cached after the first call.
+pub fn supports_vectored() -> bool {
+    match VECTORED.load(Ordering::Relaxed) {
+        -1 => {
+            let supported = sysctl_int(b"kern.osreldate\0")
+                .map_or(false, |v| v >= VECTORED_OSRELDATE);
+            VECTORED.store(supported.into(), Ordering::Relaxed);
+            supported
+        }
+        cached => cached > 0,
+    }
+}
+
+/// Cached result of [`aio_available`], encoded like [`VECTORED`].
+static AVAILABLE: AtomicI8 = AtomicI8::new(-1);
+
+/// Is the kernel's AIO subsystem available?
+///
+/// FreeBSD's AIO can be compiled out of the kernel or unloaded, in which case
+/// every submission fails with `ENOSYS`.  Like [`supports_vectored`], this
+/// checks without issuing a syscall that might not exist, by looking for the
+/// `vfs.aio` sysctls.  The result is cached after the first call.
+pub fn aio_available() -> bool {
+    match AVAILABLE.load(Ordering::Relaxed) {
+        -1 => {
+            let available = sysctl_int(b"vfs.aio.max_aio_queue\0").is_some();
+            AVAILABLE.store(available.into(), Ordering::Relaxed);
+            available
+        }
+        cached => cached > 0,
+    }
+}
+
+/// Record that a submission failed with `ENOSYS`, so [`aio_available`] was
+/// wrong.
+pub(crate) fn set_aio_unavailable() {
+    AVAILABLE.store(0, Ordering::Relaxed);
+}
+
+/// The crate-wide [`FallbackPolicy`], as its discriminant.
+static FALLBACK: AtomicU8 = AtomicU8::new(FallbackPolicy::Never as u8);
+
+/// What [`SourceApi::submit`](crate::SourceApi::submit) should do when AIO
+/// isn't available.
+///
+/// When falling back, a [`Source`] performs its operation synchronously
+/// during submission, with `pread`, `pwrite`, `preadv`, `pwritev`, `fsync`,
+/// or `fdatasync`, and then immediately posts a completion notification to
+/// its reactor, if registered.  The result is reported by
+/// [`SourceApi::aio_return`](crate::SourceApi::aio_return) as usual.
+///
+/// The one difference an event loop can see is in the notification.  Only
+/// the kernel can post an `EVFILT_AIO` event, so like
+/// [`Fadvise`](crate::Fadvise)'s, the notification uses `EVFILT_USER`, and Mio
+/// reports it as readable rather than AIO.  So an event loop that filters on
+/// `Event::is_aio` must also accept readable events for its AIO tokens.
+/// [`SourceSet`](crate::SourceSet) and
+/// [`CompletionStream`](crate::CompletionStream) already do.
+///
+/// Only operations built on a single `Source` fall back.  Multi-stage
+/// operations like [`WriteThenFsync`](crate::WriteThenFsync), and batches
+/// submitted by [`submit_all`](crate::submit_all) or [`LioCb`](crate::LioCb),
+/// still fail with `ENOSYS`.  The policy applies crate-wide.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+#[repr(u8)]
+pub enum FallbackPolicy {
+    /// Always use AIO, failing with `ENOSYS` if it's unavailable.  This is
+    /// the default.
+    #[default]
+    Never,
+    /// Fall back if [`aio_available`] reports that AIO is unavailable, or if
+    /// a submission fails with `ENOSYS`.
+    WhenUnavailable,
+    /// Always fall back, even if AIO is available.  This is mostly useful
+    /// for testing.
+    Always,
+}
+
+impl FallbackPolicy {
+    /// Get the current crate-wide policy.
+    pub fn get() -> Self {
+        match FALLBACK.load(Ordering::Relaxed) {
+            1 => FallbackPolicy::WhenUnavailable,
+            2 => FallbackPolicy::Always,
+            _ => FallbackPolicy::Never,
+        }
+    }
+
+    /// Set the crate-wide policy.
+    pub fn set(self) {
+        FALLBACK.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// Should submission skip AIO entirely?
+    pub(crate) fn bypasses_aio(self) -> bool {
+        match self {
+            FallbackPolicy::Never => false,
+            FallbackPolicy::WhenUnavailable => !aio_available(),
+            FallbackPolicy::Always => true,
+        }
+    }
+}
+
+/// Check that a direct I/O transfer is aligned as `fd` requires.
+fn check_aligned(
+    fd: BorrowedFd,
+    offs: u64,
+    buf: *const u8,
+    len: usize,
+) -> nix::Result<()> {
+    let align = required_alignment(fd)?.max(1);
+    // Misalignment is bad input, not API misuse, so it isn't subject to the
+    // MisusePolicy.
+    if buf as usize % align != 0 || offs % align as u64 != 0 || len % align != 0
+    {
+        Err(Errno::EINVAL)
+    } else {
+        Ok(())
+    }
+}
+
+/// A heap-allocated, zero-initialized buffer with a chosen alignment.
+///
+/// Files opened with `O_DIRECT` require the buffer, offset, and length of
+/// every transfer to be aligned, as reported by [`required_alignment`].
+/// Ordinary allocations like `Vec<u8>` generally aren't aligned enough.  An
+/// `AlignedBuf` dereferences to `[u8]`, so it, or any aligned portion of it,
+/// can be passed to [`Source::read_at_aligned`] or
+/// [`Source::write_at_aligned`], or indeed to any other constructor.
+pub struct AlignedBuf {
+    ptr:   NonNull<u8>,
+    len:   usize,
+    align: usize,
+}
+
+impl AlignedBuf {
+    /// Allocate `len` zeroed bytes, aligned to `align`.
+    ///
+    /// Fails with `EINVAL` if `align` isn't a power of two, or with `ENOMEM`
+    /// if the allocation fails.
+    pub fn new(len: usize, align: usize) -> nix::Result<Self> {
+        let layout =
+            Layout::from_size_align(len, align).map_err(|_| Errno::EINVAL)?;
+        let ptr = if len == 0 {
+            // Zero-sized allocations aren't allowed, but a dangling, aligned
+            // pointer is a valid empty slice.
+            NonNull::new(align as *mut u8)
+        } else {
+            // Safe because the layout's size is nonzero.
+            NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+        };
+        let ptr = ptr.ok_or(Errno::ENOMEM)?;
+        Ok(AlignedBuf { ptr, len, align })
+    }
+
+    /// Allocate a buffer suitable for direct I/O on `fd`.
+    ///
+    /// `len` is rounded up to a multiple of [`required_alignment`].
+    pub fn for_fd(fd: BorrowedFd, len: usize) -> nix::Result<Self> {
+        let align = required_alignment(fd)?.max(1);
+        let len =
+            len.checked_add(align - 1).ok_or(Errno::ENOMEM)? / align * align;
+        AlignedBuf::new(len, align)
+    }
+
+    /// The buffer's alignment, as passed to [`AlignedBuf::new`].
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safe because the allocation is initialized and lives as long as
+        // self.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // Safe because the allocation is initialized and uniquely owned.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for AlignedBuf {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // Safe because new() allocated with exactly this layout.
+            unsafe {
+                let layout =
+                    Layout::from_size_align_unchecked(self.len, self.align);
+                alloc::dealloc(self.ptr.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+// Safe because AlignedBuf uniquely owns its allocation, like a Box<[u8]>.
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl<'a> Source<aio::AioRead<'a>> {
+    /// Asynchronously read from a file opened with `O_DIRECT`.
+    ///
+    /// This is like [`Source::read_at`], but first checks that `buf`'s
+    /// address and length, and `offs`, are multiples of
+    /// [`required_alignment`].  Otherwise, it fails with `EINVAL` right away,
+    /// rather than at submission.  [`AlignedBuf`] can allocate a suitable
+    /// buffer.
+    pub fn read_at_aligned(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        check_aligned(fd, offs, buf.as_ptr(), buf.len())?;
+        Source::read_at_checked(fd, offs, buf, prio)
+    }
+}
+
+impl<'a> Source<aio::AioWrite<'a>> {
+    /// Asynchronously write to a file opened with `O_DIRECT`.
+    ///
+    /// Like [`Source::read_at_aligned`], this checks alignment up front.
+    pub fn write_at_aligned(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> nix::Result<Self> {
+        check_aligned(fd, offs, buf.as_ptr(), buf.len())?;
+        Source::write_at_checked(fd, offs, buf, prio)
+    }
+}
@@ -0,0 +1,95 @@
+// vim: tw=80
+use std::{collections::VecDeque, io, pin::Pin};
+
+use mio::{event, Events, Interest, Poll, Token};
+
+use crate::aio::SourceApi;
+
+/// Yields independent operations in the order that they complete.
+///
+/// Each operation pushed onto the stream is registered with the `Poll` and
+/// submitted.  Iterating then blocks until the next one completes, and yields
+/// it along with the index returned by [`CompletionStream::push`].  The
+/// caller is responsible for calling [`SourceApi::aio_return`] on each.
+///
+/// The stream uses tokens equal to its indices, so the `Poll` shouldn't be
+/// shared with other sources while the stream is in use.  Events for unknown
+/// tokens are discarded.  Any other event counts as a completion, including
+/// the readable events of operations emulated per the
+/// [`FallbackPolicy`](crate::FallbackPolicy).
+#[derive(Debug)]
+pub struct CompletionStream<'p, S> {
+    poll:        &'p mut Poll,
+    events:      Events,
+    sources:     Vec<Option<Pin<Box<S>>>>,
+    ready:       VecDeque<usize>,
+    outstanding: usize,
+}
+
+impl<'p, S: SourceApi + event::Source> CompletionStream<'p, S> {
+    /// Create an empty stream that will use `poll` for notification.
+    pub fn new(poll: &'p mut Poll) -> Self {
+        CompletionStream {
+            poll,
+            events: Events::with_capacity(1024),
+            sources: Vec::new(),
+            ready: VecDeque::new(),
+            outstanding: 0,
+        }
+    }
+
+    /// How many operations have been pushed but not yet yielded?
+    pub fn len(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Have all pushed operations been yielded?
+    pub fn is_empty(&self) -> bool {
+        self.outstanding == 0
+    }
+
+    /// Register and submit a new operation.
+    ///
+    /// Returns the index that will accompany the operation when it's
+    /// yielded.  If submission fails, the operation is dropped.
+    pub fn push(&mut self, mut source: S) -> io::Result<usize> {
+        let idx = self.sources.len();
+        self.poll.registry().register(
+            &mut source,
+            Token(idx),
+            Interest::AIO,
+        )?;
+        let mut source = Box::pin(source);
+        source.as_mut().submit()?;
+        self.sources.push(Some(source));
+        self.outstanding += 1;
+        Ok(idx)
+    }
+}
+
+impl<'p, S: SourceApi + event::Source> Iterator for CompletionStream<'p, S> {
+    type Item = io::Result<(usize, Pin<Box<S>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some(idx) = self.ready.pop_front() {
+                if let Some(source) = self.sources[idx].take() {
+                    self.outstanding -= 1;
+                    return Some(Ok((idx, source)));
+                }
+            }
+            if self.outstanding == 0 {
+                return None;
+            }
+            if let Err(e) = self.poll.poll(&mut self.events, None) {
+                return Some(Err(e));
+            }
+            for ev in self.events.iter() {
+                let idx = usize::from(ev.token());
+                if matches!(self.sources.get(idx), Some(Some(_))) {
+                    self.ready.push_back(idx);
+                }
+            }
+        }
+    }
+}
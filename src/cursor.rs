@@ -0,0 +1,91 @@
+// vim: tw=80
+use std::{
+    collections::HashMap,
+    os::unix::io::{AsRawFd, BorrowedFd, RawFd},
+    pin::Pin,
+};
+
+use crate::{
+    aio::{AioOp, ReadAt, Source, SourceApi, WriteAt},
+    error::AioError,
+    priority::AioPriority,
+};
+
+/// Tracks a logical file position for each of many file descriptors.
+///
+/// POSIX AIO operations always use explicit offsets.  But when many
+/// short-lived operations stream through the same file, it can be convenient
+/// to bookkeep the offsets in one place, like a file's seek pointer.  A
+/// `CursorTable` assigns each new operation the current cursor for its file,
+/// and advances the cursor when the operation completes.
+///
+/// The cursor advances only on completion, and only by the number of bytes
+/// actually transferred.  So operations issued concurrently on the same file
+/// will all start at the same offset.  Callers that want to pipeline several
+/// operations should use explicit offsets instead.
+#[derive(Clone, Debug, Default)]
+pub struct CursorTable {
+    cursors: HashMap<RawFd, u64>,
+}
+
+impl CursorTable {
+    /// Create an empty table.  Every file's cursor starts at 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reap a completed operation, advancing its file's cursor by however
+    /// many bytes it transferred.
+    ///
+    /// This is a drop-in replacement for [`SourceApi::aio_return`].  Failed
+    /// operations and fsyncs don't move the cursor.
+    pub fn aio_return<T: AioOp>(
+        &mut self,
+        source: Pin<&mut Source<T>>,
+    ) -> Result<T::Output, AioError> {
+        let fd = source.aiocb().aio_fildes;
+        let output = source.aio_return()?;
+        if let Some(n) = T::transferred(&output) {
+            *self.cursors.entry(fd).or_default() += n as u64;
+        }
+        Ok(output)
+    }
+
+    /// The current cursor for the given file.
+    pub fn cursor(&self, fd: BorrowedFd) -> u64 {
+        self.cursors.get(&fd.as_raw_fd()).copied().unwrap_or(0)
+    }
+
+    /// Forget the given file's cursor, for example because it was closed.
+    ///
+    /// Since file descriptors get reused, callers should do this before
+    /// closing a file whose cursor they no longer need.
+    pub fn remove(&mut self, fd: BorrowedFd) {
+        self.cursors.remove(&fd.as_raw_fd());
+    }
+
+    /// Move the given file's cursor to an absolute position.
+    pub fn seek(&mut self, fd: BorrowedFd, offs: u64) {
+        self.cursors.insert(fd.as_raw_fd(), offs);
+    }
+
+    /// Create a [`Source::read_at`] operation at the file's current cursor.
+    pub fn read_at<'a>(
+        &self,
+        fd: BorrowedFd<'a>,
+        buf: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> ReadAt<'a> {
+        Source::read_at(fd, self.cursor(fd), buf, prio)
+    }
+
+    /// Create a [`Source::write_at`] operation at the file's current cursor.
+    pub fn write_at<'a>(
+        &self,
+        fd: BorrowedFd<'a>,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> WriteAt<'a> {
+        Source::write_at(fd, self.cursor(fd), buf, prio)
+    }
+}
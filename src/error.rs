@@ -0,0 +1,264 @@
+// vim: tw=80
+use std::{
+    error,
+    fmt,
+    io,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{errno::Errno, libc};
+
+/// Is the crate-wide [`MisusePolicy`] `Strict`?
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Extension methods for [`Errno`].
+pub trait ErrnoExt {
+    /// Might a failed operation succeed if resubmitted unchanged?
+    ///
+    /// `EAGAIN` (the system's AIO queue is full) and `EINTR` (interrupted by a
+    /// signal) are transient.  Everything else, such as `EIO`, `EINVAL`, or
+    /// `EBADF`, indicates a problem that resubmission won't fix.  Callers with
+    /// different needs can simply use their own predicate instead.
+    fn is_transient(&self) -> bool;
+}
+
+impl ErrnoExt for Errno {
+    fn is_transient(&self) -> bool {
+        matches!(self, Errno::EAGAIN | Errno::EINTR)
+    }
+}
+
+/// How to respond when an operation is used in the wrong state.
+///
+/// Examples of misuse include submitting an operation that's already in
+/// progress, calling `aio_return` on one that was never submitted, or
+/// rebinding one that the kernel still owns.  These are always bugs in the
+/// caller.  The policy applies crate-wide.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum MisusePolicy {
+    /// Return an error, like `EBUSY` or `EINVAL`.  This is the default, and is
+    /// appropriate for production.
+    #[default]
+    Lenient,
+    /// Panic.  This is useful for catching bugs loudly in test suites.
+    Strict,
+}
+
+impl MisusePolicy {
+    /// Get the current crate-wide policy.
+    pub fn get() -> Self {
+        if STRICT.load(Ordering::Relaxed) {
+            MisusePolicy::Strict
+        } else {
+            MisusePolicy::Lenient
+        }
+    }
+
+    /// Set the crate-wide policy.
+    pub fn set(self) {
+        STRICT.store(self == MisusePolicy::Strict, Ordering::Relaxed);
+    }
+}
+
+/// Report misuse of an operation according to the current [`MisusePolicy`].
+///
+/// Returns `e` if the policy is lenient.
+pub(crate) fn misuse(e: Errno, what: &str) -> Errno {
+    if MisusePolicy::get() == MisusePolicy::Strict {
+        panic!("mio-aio misuse: {what}: {e}");
+    }
+    e
+}
+
+/// A write that would exceed a file size limit.
+///
+/// Writes that would grow a file beyond the process's `RLIMIT_FSIZE`, or
+/// beyond the file system's maximum file size, fail with `EFBIG`.  This error
+/// adds the context needed to diagnose such failures.
+///
+/// `RLIMIT_FSIZE` has a soft and a hard limit.  Only the soft limit is
+/// enforced; the hard limit is merely the ceiling to which an unprivileged
+/// process may raise its soft limit.  So `limit` always refers to the soft
+/// limit.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct FileTooLarge {
+    /// The offset of the offending write
+    pub offset: u64,
+    /// The length of the offending write
+    pub len:    usize,
+    /// The process's soft `RLIMIT_FSIZE`, if it isn't infinite.  If this is
+    /// `None` or greater than `offset + len`, then the write must have failed
+    /// because of the file system's maximum file size instead.
+    pub limit:  Option<u64>,
+}
+
+impl FileTooLarge {
+    pub(crate) fn new(offset: u64, len: usize) -> Self {
+        FileTooLarge {
+            offset,
+            len,
+            limit: fsize_limit(),
+        }
+    }
+}
+
+impl fmt::Display for FileTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write of {} bytes at offset {} ", self.len, self.offset)?;
+        match self.limit {
+            Some(limit) => {
+                write!(f, "exceeds the file size limit of {} bytes", limit)
+            }
+            None => write!(f, "exceeds the file system's maximum file size"),
+        }
+    }
+}
+
+impl error::Error for FileTooLarge {}
+
+impl From<FileTooLarge> for io::Error {
+    fn from(e: FileTooLarge) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, e)
+    }
+}
+
+/// An error from one of the phases of an operation's life cycle.
+///
+/// Each variant wraps the underlying [`Errno`], which [`AioError::errno`]
+/// returns, and tags it with the phase that failed.  That distinguishes, for
+/// example, an `EINVAL` from submitting an operation with a bad offset from
+/// an `EINVAL` from reaping an operation that was never submitted.
+///
+/// `AioError` converts to `Errno` and to [`io::Error`], so the `?` operator
+/// works in functions returning either.  The reverse conversion, from a bare
+/// `Errno`, can't know which phase failed, so it yields
+/// [`AioError::Return`], the phase in which an operation's own failures are
+/// reported.  Prefer constructing the variant explicitly where the phase is
+/// known.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum AioError {
+    /// The operation couldn't be submitted, so it never started.
+    Submit(Errno),
+    /// The operation couldn't be canceled.
+    Cancel(Errno),
+    /// The operation failed, or its status couldn't be retrieved, or it
+    /// couldn't be reaped.  This includes `EINPROGRESS` from an operation that
+    /// hasn't finished yet.
+    Return(Errno),
+    /// The operation couldn't be registered with a reactor.
+    Register(Errno),
+}
+
+impl AioError {
+    /// The underlying error number.
+    pub fn errno(self) -> Errno {
+        match self {
+            AioError::Submit(e)
+            | AioError::Cancel(e)
+            | AioError::Return(e)
+            | AioError::Register(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for AioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            AioError::Submit(_) => "submit",
+            AioError::Cancel(_) => "cancel",
+            AioError::Return(_) => "reap",
+            AioError::Register(_) => "register",
+        };
+        write!(f, "failed to {} AIO operation: {}", phase, self.errno())
+    }
+}
+
+impl error::Error for AioError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AioError::Submit(e)
+            | AioError::Cancel(e)
+            | AioError::Return(e)
+            | AioError::Register(e) => Some(e),
+        }
+    }
+}
+
+impl From<Errno> for AioError {
+    fn from(e: Errno) -> AioError {
+        AioError::Return(e)
+    }
+}
+
+impl From<AioError> for Errno {
+    fn from(e: AioError) -> Errno {
+        e.errno()
+    }
+}
+
+impl From<AioError> for io::Error {
+    fn from(e: AioError) -> io::Error {
+        e.errno().into()
+    }
+}
+
+/// Error type for [`SourceApi::try_submit`](crate::SourceApi::try_submit).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SubmitError {
+    /// The system's AIO queue is full (`EAGAIN`).  The operation wasn't
+    /// started, and may be submitted again once other operations complete.
+    WouldBlock,
+    /// The operation couldn't be started for any other reason.  Submitting it
+    /// again unchanged won't help.
+    Fatal(Errno),
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::WouldBlock => write!(f, "AIO queue is full"),
+            SubmitError::Fatal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for SubmitError {}
+
+impl From<Errno> for SubmitError {
+    fn from(e: Errno) -> Self {
+        match e {
+            Errno::EAGAIN => SubmitError::WouldBlock,
+            e => SubmitError::Fatal(e),
+        }
+    }
+}
+
+impl From<SubmitError> for Errno {
+    fn from(e: SubmitError) -> Errno {
+        match e {
+            SubmitError::WouldBlock => Errno::EAGAIN,
+            SubmitError::Fatal(e) => e,
+        }
+    }
+}
+
+impl From<SubmitError> for io::Error {
+    fn from(e: SubmitError) -> io::Error {
+        Errno::from(e).into()
+    }
+}
+
+/// The process's soft `RLIMIT_FSIZE`, if it isn't infinite.
+pub(crate) fn fsize_limit() -> Option<u64> {
+    // Safe because rlimit is plain old data, and getrlimit initializes it.
+    let mut rlim: libc::rlimit = unsafe { mem::zeroed() };
+    let r = unsafe { libc::getrlimit(libc::RLIMIT_FSIZE, &mut rlim) };
+    if r != 0 || rlim.rlim_cur == libc::RLIM_INFINITY {
+        None
+    } else {
+        Some(rlim.rlim_cur)
+    }
+}
@@ -0,0 +1,216 @@
+// vim: tw=80
+use std::{
+    io,
+    os::unix::io::{AsRawFd, BorrowedFd, RawFd},
+    pin::Pin,
+    sync::atomic::Ordering,
+};
+
+use mio::{event, Interest, Registry, Token};
+use nix::{errno::Errno, libc, sys::aio};
+
+use crate::{
+    aio::{checked_offset, transferred, CancelStat, Source, SourceApi},
+    error::{misuse, AioError},
+    sendfile::{trigger, NEXT_IDENT},
+};
+
+/// Advice for [`Source::fadvise`], describing how a file's data will be used.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Advice {
+    /// No particular access pattern.  This undoes any earlier advice.
+    Normal,
+    /// The data will be accessed in random order.
+    Random,
+    /// The data will be accessed sequentially.
+    Sequential,
+    /// The data will be accessed soon, so read it into the cache.
+    WillNeed,
+    /// The data won't be accessed soon, so evict it from the cache.
+    DontNeed,
+    /// The data will be accessed only once.
+    NoReuse,
+}
+
+impl Advice {
+    fn as_raw(self) -> libc::c_int {
+        match self {
+            Advice::Normal => libc::POSIX_FADV_NORMAL,
+            Advice::Random => libc::POSIX_FADV_RANDOM,
+            Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+            Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+            Advice::NoReuse => libc::POSIX_FADV_NOREUSE,
+        }
+    }
+}
+
+impl<'a> Source<aio::AioFsync<'a>> {
+    /// Advise the kernel how a range of a file will be used, for example to
+    /// prefetch it into the cache.
+    ///
+    /// A `len` of 0 means the rest of the file.  See [`Fadvise`] for how this
+    /// differs from true AIO operations.
+    pub fn fadvise(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        len: u64,
+        advice: Advice,
+    ) -> Fadvise<'a> {
+        Fadvise {
+            fd,
+            offs,
+            len,
+            advice,
+            ident: NEXT_IDENT.fetch_add(1, Ordering::Relaxed),
+            target: None,
+            result: None,
+            in_progress: false,
+        }
+    }
+}
+
+/// A `posix_fadvise(2)` call, shaped like an AIO operation.
+///
+/// There's no asynchronous version of `posix_fadvise`, but it doesn't block
+/// for long either: advice like [`Advice::WillNeed`] merely starts the
+/// readahead.  So [`SourceApi::submit`] calls it synchronously, and then
+/// immediately posts a completion notification to the reactor.  Any error
+/// from `posix_fadvise` itself is reported by [`SourceApi::aio_return`], just
+/// like an operation that failed asynchronously.
+///
+/// Like [`SendFile`](crate::SendFile), the completion notification uses
+/// `EVFILT_USER`, so Mio reports it as readable rather than AIO.
+///
+/// Create one with [`Source::fadvise`].
+#[derive(Debug)]
+pub struct Fadvise<'a> {
+    fd:          BorrowedFd<'a>,
+    offs:        u64,
+    len:         u64,
+    advice:      Advice,
+    ident:       usize,
+    /// The kqueue and udata to notify upon completion, if registered.
+    target:      Option<(RawFd, usize)>,
+    /// The result of the last submission
+    result:      Option<nix::Result<()>>,
+    in_progress: bool,
+}
+
+impl<'a> event::Source for Fadvise<'a> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        _interests: Interest,
+    ) -> io::Result<()> {
+        self.target = Some((registry.as_raw_fd(), token.into()));
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.register(registry, token, interests)
+    }
+
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self.target = None;
+        Ok(())
+    }
+}
+
+impl<'a> SourceApi for Fadvise<'a> {
+    type Output = ();
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if !self.in_progress {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "aio_return before submit",
+            )));
+        }
+        self.in_progress = false;
+        self.result
+            .expect("submitted fadvise has no result")
+            .map_err(AioError::Return)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |_| 0)
+    }
+
+    /// `posix_fadvise` completes during submission, so there's never
+    /// anything to cancel.
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if !self.in_progress {
+            return Err(AioError::Cancel(misuse(
+                Errno::EINVAL,
+                "cancel before submit",
+            )));
+        }
+        Ok(CancelStat::AioAllDone)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.target = None;
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        match self.result {
+            Some(r) => r.map_err(AioError::Return),
+            None => Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "error before submit",
+            ))),
+        }
+    }
+
+    fn in_progress(&self) -> bool {
+        self.in_progress
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.target = Some((kq, udata));
+    }
+
+    /// Call `posix_fadvise`, and notify the reactor.
+    ///
+    /// Fails with `EOVERFLOW` if the offset or length doesn't fit in an
+    /// `off_t`.
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        let offs = checked_offset(self.offs).map_err(AioError::Submit)?;
+        let len = checked_offset(self.len).map_err(AioError::Submit)?;
+        // Safe because posix_fadvise doesn't touch any memory.
+        let r = unsafe {
+            libc::posix_fadvise(
+                self.fd.as_raw_fd(),
+                offs,
+                len,
+                self.advice.as_raw(),
+            )
+        };
+        // posix_fadvise returns the error number rather than setting errno.
+        self.result = Some(match r {
+            0 => Ok(()),
+            e => Err(Errno::from_raw(e)),
+        });
+        self.in_progress = true;
+        if let Some((kq, udata)) = self.target {
+            trigger(kq, self.ident, udata);
+        }
+        Ok(())
+    }
+}
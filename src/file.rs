@@ -0,0 +1,115 @@
+// vim: tw=80
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem::MaybeUninit,
+    os::unix::io::{AsRawFd, BorrowedFd},
+    pin::pin,
+};
+
+use nix::{errno::Errno, libc, sys::event::Kqueue};
+
+use crate::{aio::Source, priority::AioPriority};
+
+/// A synchronous file handle whose reads and writes are performed with AIO.
+///
+/// It implements [`Read`], [`Write`], and [`Seek`] by issuing a single
+/// [`Source::read_at`] or [`Source::write_at`] at a time, and blocking on a
+/// private kqueue until it completes.  Like a normal file handle, it keeps its
+/// own position, which each read or write advances by the number of bytes
+/// transferred.  That position is independent of the file descriptor's own
+/// offset, which is never used or changed.
+///
+/// This is meant for migrating simple code paths from blocking I/O, and for
+/// benchmarking AIO against `pread` and `pwrite`.  It gains none of AIO's
+/// concurrency.
+///
+/// Since AIO ignores the file's offset for files opened with `O_APPEND`, so
+/// will `AioFile`'s writes, but its position will still advance as though
+/// they had been written at the position.
+#[derive(Debug)]
+pub struct AioFile<'a> {
+    fd:   BorrowedFd<'a>,
+    kq:   Kqueue,
+    pos:  u64,
+    prio: AioPriority,
+}
+
+impl<'a> AioFile<'a> {
+    /// Wrap a file, starting at position 0.
+    pub fn new(fd: BorrowedFd<'a>) -> io::Result<Self> {
+        Ok(AioFile {
+            fd,
+            kq: Kqueue::new()?,
+            pos: 0,
+            prio: AioPriority::default(),
+        })
+    }
+
+    /// Set the priority of every subsequent operation.
+    pub fn with_priority(mut self, prio: impl Into<AioPriority>) -> Self {
+        self.prio = prio.into();
+        self
+    }
+
+    /// The current position, as reported by [`Seek::stream_position`].
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// The file's current size.
+    fn size(&self) -> io::Result<u64> {
+        let mut sb = MaybeUninit::<libc::stat>::uninit();
+        // Safe because sb is large enough
+        Errno::result(unsafe {
+            libc::fstat(self.fd.as_raw_fd(), sb.as_mut_ptr())
+        })?;
+        let sb = unsafe { sb.assume_init() };
+        Ok(sb.st_size as u64)
+    }
+}
+
+impl<'a> Read for AioFile<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let op =
+            pin!(Source::read_at_checked(self.fd, self.pos, buf, self.prio)?);
+        let n = op.submit_and_wait_kq(&self.kq)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'a> Write for AioFile<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let op =
+            pin!(Source::write_at_checked(self.fd, self.pos, buf, self.prio)?);
+        let n = op.submit_and_wait_kq(&self.kq)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    /// Does nothing, since nothing is buffered.  Use [`Source::fsync`] to
+    /// flush data to stable storage.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Seek for AioFile<'a> {
+    /// Fails with `EINVAL` if the new position would be negative or would
+    /// overflow.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let newpos = match pos {
+            SeekFrom::Start(offs) => Some(offs),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.size()?.checked_add_signed(delta),
+        };
+        self.pos = newpos.ok_or(Errno::EINVAL)?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,264 @@
+// vim: tw=80
+#[cfg(feature = "tokio")]
+use std::os::unix::io::RawFd;
+use std::{io, os::unix::io::BorrowedFd, pin::Pin};
+
+use mio::{event, Interest, Registry, Token};
+use nix::{errno::Errno, sys::aio::AioFsyncMode};
+
+use crate::{
+    aio::{transferred, CancelStat, Fsync, Source, SourceApi},
+    error::{misuse, AioError},
+    priority::AioPriority,
+};
+
+/// Fsyncs of several files, as a single Mio source.
+///
+/// Each file is synced by its own `aio_fsync` operation, with its own mode,
+/// but they're all submitted together, and all notify the same kqueue with the
+/// same token.  So the caller will receive one event per file, and nothing
+/// distinguishes the last.  Upon each event, check
+/// [`FsyncGroup::is_complete`], and keep polling until it returns true.  Until
+/// then, [`SourceApi::aio_return`] will fail with `EINPROGRESS`.  After that,
+/// it returns each file's result, in the order given to [`FsyncGroup::new`].
+///
+/// An empty group does nothing when submitted, and never delivers an event.
+#[derive(Debug)]
+pub struct FsyncGroup<'a> {
+    ops:       Box<[Fsync<'a>]>,
+    /// Submission errors, for operations that never started
+    failed:    Box<[Option<AioError>]>,
+    submitted: bool,
+}
+
+impl<'a> FsyncGroup<'a> {
+    /// Create a group that will fsync each file with its own mode.
+    pub fn new(
+        fds: &[(BorrowedFd<'a>, AioFsyncMode)],
+        prio: impl Into<AioPriority>,
+    ) -> Self {
+        let prio = prio.into();
+        let ops = fds
+            .iter()
+            .map(|(fd, mode)| Source::fsync(*fd, *mode, prio))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        FsyncGroup {
+            failed: vec![None; ops.len()].into_boxed_slice(),
+            ops,
+            submitted: false,
+        }
+    }
+
+    /// How many files does the group sync?
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Does the group contain no files?
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Is the group ready for [`SourceApi::aio_return`]?
+    ///
+    /// That is, has the group been submitted, and has every fsync finished?
+    /// It's false before submission, and again after the group is reaped.
+    pub fn is_complete(&self) -> bool {
+        self.submitted
+            && !self.ops.iter().any(|op| {
+                op.in_progress()
+                    && op.peek_error()
+                        == Err(AioError::Return(Errno::EINPROGRESS))
+            })
+    }
+
+    /// Pin each in-progress operation.
+    fn active(
+        self: Pin<&mut Self>,
+    ) -> impl Iterator<Item = Pin<&mut Fsync<'a>>> {
+        // Safe because the boxed slice is never reallocated, so its elements
+        // never move.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.ops
+            .iter_mut()
+            .filter(|op| op.in_progress())
+            .map(|op| unsafe { Pin::new_unchecked(op) })
+    }
+}
+
+impl<'a> SourceApi for FsyncGroup<'a> {
+    type Output = Vec<Result<(), AioError>>;
+
+    /// Reap every fsync, once they've all finished.
+    ///
+    /// Fails with `EINPROGRESS` if any are still in progress.  Otherwise,
+    /// returns each file's result, including any submission errors.
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<Self::Output, AioError> {
+        if !self.submitted {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "aio_return before submit",
+            )));
+        }
+        if self.as_mut().error() == Err(AioError::Return(Errno::EINPROGRESS)) {
+            return Err(AioError::Return(Errno::EINPROGRESS));
+        }
+        // Safe because we don't move any operations.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.submitted = false;
+        let results = this
+            .ops
+            .iter_mut()
+            .zip(this.failed.iter_mut())
+            .map(|(op, failed)| match failed.take() {
+                Some(e) => Err(e),
+                // Safe because the boxed slice is never reallocated.
+                None => unsafe { Pin::new_unchecked(op) }.aio_return(),
+            })
+            .collect();
+        Ok(results)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |_| 0)
+    }
+
+    /// Try to cancel every fsync that's still in progress.
+    ///
+    /// Returns [`CancelStat::AioNotCanceled`] if any couldn't be canceled,
+    /// [`CancelStat::AioAllDone`] if all had already finished, and otherwise
+    /// [`CancelStat::AioCanceled`].
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        let mut stat = CancelStat::AioAllDone;
+        for op in self.active() {
+            match op.cancel()? {
+                CancelStat::AioNotCanceled => stat = CancelStat::AioNotCanceled,
+                CancelStat::AioCanceled if stat.is_all_done() => {
+                    stat = CancelStat::AioCanceled
+                }
+                _ => (),
+            }
+        }
+        Ok(stat)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        for op in self.ops.iter_mut() {
+            op.deregister_raw();
+        }
+    }
+
+    /// Fails with `EINPROGRESS` if any fsync is still in progress.
+    /// Otherwise, returns the first error of any fsync, if any failed.
+    fn error(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if !self.submitted {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "error before submit",
+            )));
+        }
+        let mut r = match self.failed.iter().flatten().next() {
+            Some(e) => Err(*e),
+            None => Ok(()),
+        };
+        for op in self.as_mut().active() {
+            match op.error() {
+                Err(e) if e.errno() == Errno::EINPROGRESS => return Err(e),
+                Err(e) if r.is_ok() => r = Err(e),
+                _ => (),
+            }
+        }
+        r
+    }
+
+    fn in_progress(&self) -> bool {
+        self.ops.iter().any(|op| op.in_progress())
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        for op in self.ops.iter_mut() {
+            op.register_raw(kq, udata);
+        }
+    }
+
+    /// Submit every fsync.
+    ///
+    /// If some fail to submit, the rest are still submitted, and
+    /// [`SourceApi::aio_return`] will report their errors.  Only if none could
+    /// be submitted does this fail, with the first error.
+    fn submit(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.in_progress() || self.submitted {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        // Safe because we don't move any operations.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut first_error = None;
+        for (op, failed) in this.ops.iter_mut().zip(this.failed.iter_mut()) {
+            // Safe because the boxed slice is never reallocated.
+            if let Err(e) = unsafe { Pin::new_unchecked(op) }.submit() {
+                *failed = Some(e);
+                first_error.get_or_insert(e);
+            }
+        }
+        match first_error {
+            Some(e) if !this.ops.iter().any(|op| op.in_progress()) => {
+                this.failed.iter_mut().for_each(|failed| *failed = None);
+                Err(e)
+            }
+            _ => {
+                this.submitted = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> event::Source for FsyncGroup<'a> {
+    /// Register every fsync with a Mio reactor, using the same token.
+    ///
+    /// # Errors
+    ///
+    /// - `EBUSY`: the group is already in progress and was registered with a
+    ///   different reactor.
+    /// - `InvalidInput`: `interests` doesn't include [`Interest::AIO`].
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        for op in self.ops.iter_mut() {
+            op.register(registry, token, interests)?;
+        }
+        Ok(())
+    }
+
+    /// Change the token of every fsync.
+    ///
+    /// Like [`Source`]'s, this fails with `EBUSY` if the group is in progress
+    /// and was registered with a different reactor or token.
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        for op in self.ops.iter_mut() {
+            op.reregister(registry, token, interests)?;
+        }
+        Ok(())
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        for op in self.ops.iter_mut() {
+            op.deregister(registry)?;
+        }
+        Ok(())
+    }
+}
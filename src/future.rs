@@ -0,0 +1,289 @@
+// vim: tw=80
+use std::{
+    collections::HashMap,
+    future::Future,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    task::{ready, Context, Poll, Waker},
+    thread,
+};
+
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{
+        aio,
+        event::{EventFilter, EventFlag, FilterFlag, KEvent, Kqueue},
+    },
+};
+
+use crate::{
+    aio::{owned_slice, AioOp, Source, SourceApi},
+    error::AioError,
+    priority::AioPriority,
+};
+
+/// Source of unique identifiers for in-flight futures.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The process-wide reactor, created on first use.
+static REACTOR: Mutex<Option<&'static Reactor>> = Mutex::new(None);
+
+/// Tasks waiting for their operations to complete.
+#[derive(Debug, Default)]
+struct Wakers {
+    map:   HashMap<usize, Waker>,
+    /// The error that stopped the reactor's thread, if it has stopped
+    error: Option<Errno>,
+}
+
+/// Owns a private kqueue, and wakes tasks as their operations complete.
+#[derive(Debug)]
+struct Reactor {
+    kq:     Kqueue,
+    wakers: Mutex<Wakers>,
+}
+
+impl Reactor {
+    /// Get the process-wide reactor, starting its thread if necessary.
+    fn get() -> nix::Result<&'static Reactor> {
+        let mut guard = REACTOR.lock().unwrap();
+        if let Some(reactor) = *guard {
+            return Ok(reactor);
+        }
+        let reactor: &'static Reactor = Box::leak(Box::new(Reactor {
+            kq:     Kqueue::new()?,
+            wakers: Mutex::default(),
+        }));
+        thread::Builder::new()
+            .name("mio-aio reactor".to_owned())
+            .spawn(move || reactor.run())
+            .map_err(|_| Errno::EAGAIN)?;
+        *guard = Some(reactor);
+        Ok(reactor)
+    }
+
+    fn run(&self) {
+        let ev = KEvent::new(
+            0,
+            EventFilter::EVFILT_AIO,
+            EventFlag::empty(),
+            FilterFlag::empty(),
+            0,
+            0,
+        );
+        let mut events = vec![ev; 64];
+        loop {
+            let n = match self.kq.kevent(&[], &mut events, None) {
+                Ok(n) => n,
+                Err(Errno::EINTR) => continue,
+                Err(e) => {
+                    // No more completions will be delivered, so wake every
+                    // task, to let it discover that.
+                    let mut wakers = self.wakers.lock().unwrap();
+                    wakers.error = Some(e);
+                    for (_, waker) in wakers.map.drain() {
+                        waker.wake();
+                    }
+                    return;
+                }
+            };
+            let mut wakers = self.wakers.lock().unwrap();
+            for ev in &events[..n] {
+                if let Some(waker) = wakers.map.remove(&(ev.udata() as usize)) {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+/// A `Future` that submits an operation and resolves once it's complete.
+///
+/// Created by [`Source::submit_and_wait`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Completion<'s, T: AioOp> {
+    source:    Pin<&'s mut Source<T>>,
+    id:        usize,
+    submitted: bool,
+}
+
+/// Stop waking the task that polled operation `id`.
+fn forget_waker(id: usize) {
+    if let Ok(reactor) = Reactor::get() {
+        reactor.wakers.lock().unwrap().map.remove(&id);
+    }
+}
+
+/// Poll an operation on behalf of a future: submit it on first poll, and reap
+/// it once it's complete.
+///
+/// `id` must be unique to the future, and `submitted` must be false on first
+/// poll.  If the reactor's thread has stopped, an operation that's still in
+/// progress fails with the reactor's error, since it would never be woken.
+fn poll_source<T: AioOp>(
+    mut source: Pin<&mut Source<T>>,
+    id: usize,
+    submitted: &mut bool,
+    cx: &mut Context<'_>,
+) -> Poll<Result<T::Output, AioError>> {
+    let reactor = match Reactor::get() {
+        Ok(reactor) => reactor,
+        Err(e) => return Poll::Ready(Err(AioError::Register(e))),
+    };
+    if !*submitted {
+        if let Some(e) = reactor.wakers.lock().unwrap().error {
+            return Poll::Ready(Err(AioError::Register(e)));
+        }
+        // Safe because registration doesn't move the operation.
+        let s = unsafe { source.as_mut().get_unchecked_mut() };
+        let kq = reactor.kq.as_fd().as_raw_fd();
+        if let Err(e) = s.register_kq(kq, id) {
+            return Poll::Ready(Err(AioError::Register(Errno::from_raw(
+                e.raw_os_error().unwrap_or(libc::EINVAL),
+            ))));
+        }
+        if let Err(e) = source.as_mut().submit() {
+            return Poll::Ready(Err(e));
+        }
+        *submitted = true;
+    }
+    // Store the waker before checking the status, so a completion that
+    // races with us can't be missed.
+    let dead = {
+        let mut wakers = reactor.wakers.lock().unwrap();
+        wakers.map.insert(id, cx.waker().clone());
+        wakers.error
+    };
+    match source.as_mut().error().map_err(AioError::errno) {
+        Err(Errno::EINPROGRESS) => match dead {
+            Some(e) => {
+                forget_waker(id);
+                Poll::Ready(Err(AioError::Register(e)))
+            }
+            None => Poll::Pending,
+        },
+        _ => {
+            forget_waker(id);
+            let r = source.as_mut().aio_return();
+            // Safe because deregistration doesn't move the operation.
+            unsafe { source.as_mut().get_unchecked_mut() }.deregister_kq();
+            Poll::Ready(r)
+        }
+    }
+}
+
+impl<'s, T: AioOp> Drop for Completion<'s, T> {
+    fn drop(&mut self) {
+        if self.submitted {
+            forget_waker(self.id);
+        }
+    }
+}
+
+impl<'s, T: AioOp> Future for Completion<'s, T> {
+    type Output = Result<T::Output, AioError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        poll_source(this.source.as_mut(), this.id, &mut this.submitted, cx)
+    }
+}
+
+/// A `Future` that writes an owned buffer, and resolves to that same buffer
+/// once the write is complete.
+///
+/// The buffer is handed back as the same allocation that was passed in, so
+/// it can be returned to a buffer pool.  If the write fails, the buffer is
+/// dropped.
+///
+/// Dropping the future before it resolves is safe: if the write is still in
+/// progress, dropping cancels it, and blocks until the kernel is done with
+/// the buffer, just like dropping a [`Source`].  Only then is the buffer
+/// freed.
+///
+/// Created by [`Source::write_owned`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteOwned<'a> {
+    // Must be declared before buf, so that it gets dropped first.
+    source:    Pin<Box<Source<aio::AioWrite<'a>>>>,
+    buf:       Option<Vec<u8>>,
+    id:        usize,
+    submitted: bool,
+}
+
+impl<'a> Drop for WriteOwned<'a> {
+    fn drop(&mut self) {
+        if self.submitted {
+            forget_waker(self.id);
+        }
+    }
+}
+
+impl<'a> Future for WriteOwned<'a> {
+    type Output = Result<(Box<[u8]>, usize), AioError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let r = ready!(poll_source(
+            this.source.as_mut(),
+            this.id,
+            &mut this.submitted,
+            cx
+        ));
+        Poll::Ready(r.map(|nbytes| {
+            let buf = this.buf.take().expect("future polled after completion");
+            (buf.into_boxed_slice(), nbytes)
+        }))
+    }
+}
+
+impl<'a> Source<aio::AioWrite<'a>> {
+    /// Asynchronously write an owned buffer to a file, and get it back
+    /// afterwards.
+    ///
+    /// This combines [`Source::write_from`] with [`Source::submit_and_wait`].
+    /// The returned future resolves to the buffer and the number of bytes
+    /// written.  See [`WriteOwned`] for what happens if it's dropped early.
+    pub fn write_owned(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: Box<[u8]>,
+        prio: impl Into<AioPriority>,
+    ) -> WriteOwned<'a> {
+        let buf = buf.into_vec();
+        // Safe because the returned future owns buf.
+        let slice = unsafe { owned_slice(&buf) };
+        WriteOwned {
+            source:    Box::pin(Source::write_at(fd, offs, slice, prio)),
+            buf:       Some(buf),
+            id:        NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            submitted: false,
+        }
+    }
+}
+
+impl<T: AioOp> Source<T> {
+    /// Submit the operation, and asynchronously wait for it to complete.
+    ///
+    /// This doesn't need Mio or Tokio.  Instead, completion notifications are
+    /// delivered to a private kqueue serviced by a dedicated thread, so the
+    /// returned future may be polled by any executor.  It overrides any
+    /// existing registration.  The operation is submitted on first poll.
+    ///
+    /// If the reactor's thread fails, every future still waiting resolves to
+    /// [`AioError::Register`], as does every future polled afterwards.
+    pub fn submit_and_wait(self: Pin<&mut Self>) -> Completion<'_, T> {
+        Completion {
+            source:    self,
+            id:        NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            submitted: false,
+        }
+    }
+}
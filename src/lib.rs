@@ -2,8 +2,17 @@
 //!
 //! # Feature Flags
 //!
+//! * `async` - Add [`Source::submit_and_wait`], a runtime-agnostic `Future`
+//!   that works with any executor, and [`Source::write_owned`].
+//! * `bytes` - Add [`Source::read_bytes`] and [`Source::write_bytes`], which
+//!   use buffers from the [`bytes`](https://docs.rs/bytes) crate.
+//! * `log` - Log short transfers and `EAGAIN` submission failures via the
+//!   [`log`](https://docs.rs/log) crate.
+//! * `metrics` - Count submitted, completed, cancelled, and failed operations,
+//!   and time each operation's latency.
+//!   Read the counters with [`snapshot`].
 //! * `tokio` - Add extra methods needed for consumers to implement Tokio's
-//!             `AioSource` trait.
+//!   `AioSource` trait.
 //!
 //! # See Also
 //!
@@ -13,15 +22,95 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 mod aio;
+#[cfg(feature = "bytes")]
+mod bytes;
+mod callback;
+mod capabilities;
+mod completion;
+mod cursor;
+mod error;
+mod fadvise;
+mod file;
+mod fsync_group;
+#[cfg(feature = "async")]
+mod future;
+mod lio;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod priority;
+mod quota;
+mod sendfile;
+mod source_set;
 
 pub use aio::{
+    cancel_fd,
+    plan_vectored_writes,
+    submit_all,
+    wait_any,
+    AioCancelStat,
+    AioFsync,
     AioFsyncMode,
+    AioRead,
+    AioReadv,
+    AioState,
+    AioWrite,
+    AioWritev,
+    CancelOutcome,
+    CancelStat,
+    CompletionStatus,
+    DynSource,
     Fsync,
+    NotifyMode,
+    OnComplete,
+    OpDescriptor,
+    Opcode,
     ReadAt,
+    ReadAtIndexed,
+    ReadInto,
     ReadvAt,
+    ReadvAtOwned,
+    Reaped,
     Source,
     SourceApi,
+    State,
     WriteAt,
+    WriteAtVerified,
+    WriteFrom,
+    WriteThenFsync,
+    WriteThenSync,
     WritevAt,
+    WritevAtOwned,
 };
-pub use nix::errno::Errno;
+#[cfg(feature = "bytes")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bytes")))]
+pub use bytes::{ReadBytes, WriteBytes};
+pub use capabilities::{
+    aio_available,
+    aio_limits,
+    required_alignment,
+    supports_vectored,
+    AioLimits,
+    AlignedBuf,
+    FallbackPolicy,
+};
+pub use completion::CompletionStream;
+pub use cursor::CursorTable;
+pub use error::{AioError, ErrnoExt, FileTooLarge, MisusePolicy, SubmitError};
+pub use fadvise::{Advice, Fadvise};
+pub use file::AioFile;
+pub use fsync_group::FsyncGroup;
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub use future::{Completion, WriteOwned};
+pub use lio::{LioCb, LioCbBuilder, LioError, LioOutcome};
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use metrics::{snapshot, Stats};
+pub use nix::{
+    errno::Errno,
+    sys::signal::{SigevNotify, Signal},
+};
+pub use priority::AioPriority;
+pub use quota::{AioQuota, Permit};
+pub use sendfile::SendFile;
+pub use source_set::SourceSet;
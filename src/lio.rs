@@ -0,0 +1,724 @@
+// vim: tw=80
+use std::{
+    error,
+    fmt,
+    io,
+    os::unix::io::{AsRawFd, BorrowedFd, RawFd},
+    pin::Pin,
+};
+
+use mio::{event, Interest, Registry, Token};
+use nix::{
+    errno::Errno,
+    libc::{self, off_t},
+    sys::{
+        aio::{self, LioMode},
+        event::EventFlag,
+        signal::{SigEvent, SigevNotify},
+    },
+};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::{
+    aio::{aio_return_nointr, cancel_and_reap, raw_aio_error},
+    capabilities::aio_limits,
+    error::AioError,
+    priority::AioPriority,
+};
+
+/// A single operation within an [`LioCb`].
+#[derive(Debug)]
+enum LioOp<'a> {
+    Read(aio::AioRead<'a>),
+    Write(aio::AioWrite<'a>),
+}
+
+impl<'a> LioOp<'a> {
+    /// Reap the operation with `aio_return`.
+    ///
+    /// # Safety
+    ///
+    /// The operation must not move until it's been reaped.
+    unsafe fn aio_return(&mut self) -> Result<usize, AioError> {
+        let r = match self {
+            LioOp::Read(op) => aio_return_nointr(Pin::new_unchecked(op)),
+            LioOp::Write(op) => aio_return_nointr(Pin::new_unchecked(op)),
+        };
+        #[cfg(feature = "metrics")]
+        metrics::record_return(&r);
+        r.map_err(AioError::Return)
+    }
+
+    /// Cancel the operation, block until it's finished, and reap it.
+    ///
+    /// # Safety
+    ///
+    /// The operation must not move until it's been reaped.
+    unsafe fn cancel_and_reap(&mut self) -> Result<usize, AioError> {
+        let r = match self {
+            LioOp::Read(op) => cancel_and_reap(Pin::new_unchecked(op)),
+            LioOp::Write(op) => cancel_and_reap(Pin::new_unchecked(op)),
+        };
+        #[cfg(feature = "metrics")]
+        metrics::record_return(&r);
+        r.map_err(AioError::Return)
+    }
+}
+
+impl<'a> AsMut<libc::aiocb> for LioOp<'a> {
+    fn as_mut(&mut self) -> &mut libc::aiocb {
+        match self {
+            LioOp::Read(op) => op.as_mut(),
+            LioOp::Write(op) => op.as_mut(),
+        }
+    }
+}
+
+impl<'a> AsRef<libc::aiocb> for LioOp<'a> {
+    fn as_ref(&self) -> &libc::aiocb {
+        match self {
+            LioOp::Read(op) => op.as_ref(),
+            LioOp::Write(op) => op.as_ref(),
+        }
+    }
+}
+
+/// The aiocb pointers passed to a single `lio_listio` call.
+///
+/// The kernel identifies each call's LIO event by the address of this array.
+/// So it must stay allocated until the batch is reaped.  Otherwise a later
+/// chunk's array could reuse the address, and its registration would replace
+/// the earlier chunk's pending event instead of adding another.
+#[derive(Debug)]
+struct ChunkList(Box<[*mut libc::aiocb]>);
+
+// Safe because the pointers are only dereferenced by the kernel, during
+// `lio_listio`.  Afterwards, only the array's address matters.
+unsafe impl Send for ChunkList {}
+unsafe impl Sync for ChunkList {}
+
+/// The submission status of a single operation within an [`LioCb`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LioState {
+    /// Not yet submitted, or submitted but not queued by the kernel.
+    Idle,
+    /// Queued by the kernel, and not yet reaped.
+    InProgress,
+    /// Finished, with its final status already collected.
+    Done(Result<usize, AioError>),
+}
+
+/// What became of one operation in a batch, as reported by
+/// [`LioCb::drain_partial`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LioOutcome {
+    /// The operation finished, with this result.  Its buffer may have been
+    /// partly or wholly used.
+    Completed(Result<usize, AioError>),
+    /// The operation was in progress, but was canceled before it did
+    /// anything.
+    Canceled,
+    /// The operation was never initiated, so the kernel never saw its buffer.
+    Pending,
+}
+
+/// Error type for [`LioCb::submit`] and [`LioCb::resubmit`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LioError {
+    /// Some operations were initiated, but others weren't, usually because of
+    /// system resource limits.  Wait for the initiated operations to
+    /// complete, and then call [`LioCb::resubmit`].
+    EINCOMPLETE,
+    /// No operations were initiated.
+    Errno(Errno),
+}
+
+impl fmt::Display for LioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LioError::EINCOMPLETE => {
+                write!(f, "Some lio_listio operations were not initiated")
+            }
+            LioError::Errno(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for LioError {}
+
+impl From<Errno> for LioError {
+    fn from(e: Errno) -> Self {
+        LioError::Errno(e)
+    }
+}
+
+/// Builds an [`LioCb`] one operation at a time.
+#[derive(Debug, Default)]
+pub struct LioCbBuilder<'a> {
+    ops:       Vec<LioOp<'a>>,
+    max_chunk: Option<usize>,
+}
+
+impl<'a> LioCbBuilder<'a> {
+    /// Create a builder with room for `capacity` operations.
+    pub fn with_capacity(capacity: usize) -> Self {
+        LioCbBuilder {
+            ops:       Vec::with_capacity(capacity),
+            max_chunk: None,
+        }
+    }
+
+    /// Submit at most `n` operations with each `lio_listio` call.
+    ///
+    /// Larger batches will be split into several calls, each of which delivers
+    /// its own event.  See [`LioCb`] for the hazards of that.  The default is
+    /// the system's limit, as reported by
+    /// [`AioLimits::listio_max`](crate::AioLimits::listio_max).  Values of 0
+    /// are treated as 1.
+    pub fn max_chunk(mut self, n: usize) -> Self {
+        self.max_chunk = Some(n.max(1));
+        self
+    }
+
+    /// Add a read operation to the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`:   File descriptor to read from
+    /// * `offs`: Offset within the file at which to read
+    /// * `buf`:  Buffer to read into
+    /// * `prio`: Priority, as described in [`Source::read_at`](crate::Source::read_at)
+    pub fn read_at(
+        mut self,
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a mut [u8],
+        prio: impl Into<AioPriority>,
+    ) -> Self {
+        let mut op = aio::AioRead::new(
+            fd,
+            offs as off_t,
+            buf,
+            prio.into().get(),
+            SigevNotify::SigevNone,
+        );
+        AsMut::<libc::aiocb>::as_mut(&mut op).aio_lio_opcode = libc::LIO_READ;
+        self.ops.push(LioOp::Read(op));
+        self
+    }
+
+    /// Add a write operation to the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`:   File descriptor to write to
+    /// * `offs`: Offset within the file at which to write
+    /// * `buf`:  Data to write
+    /// * `prio`: Priority, as described in [`Source::write_at`](crate::Source::write_at)
+    pub fn write_at(
+        mut self,
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        buf: &'a [u8],
+        prio: impl Into<AioPriority>,
+    ) -> Self {
+        let mut op = aio::AioWrite::new(
+            fd,
+            offs as off_t,
+            buf,
+            prio.into().get(),
+            SigevNotify::SigevNone,
+        );
+        AsMut::<libc::aiocb>::as_mut(&mut op).aio_lio_opcode = libc::LIO_WRITE;
+        self.ops.push(LioOp::Write(op));
+        self
+    }
+
+    /// Finish building the batch.
+    pub fn finish(self) -> LioCb<'a> {
+        let state = vec![LioState::Idle; self.ops.len()];
+        let max_chunk =
+            self.max_chunk.unwrap_or_else(|| aio_limits().listio_max);
+        LioCb {
+            ops: self.ops,
+            state,
+            sigev_notify: SigevNotify::SigevNone,
+            max_chunk,
+            lists: Vec::new(),
+        }
+    }
+}
+
+/// A batch of reads and writes, submitted with a single `lio_listio(2)` call.
+///
+/// Unlike [`Source`](crate::Source), the whole batch needs only a single
+/// registration, with [`Interest::LIO`].  The reactor will deliver a single
+/// LIO event once every initiated operation is complete.
+///
+/// Batches larger than [`LioCbBuilder::max_chunk`] are split into several
+/// `lio_listio` calls, each of which delivers its own LIO event, with the
+/// same token.  So upon each event, check [`LioCb::is_complete`] before
+/// calling [`LioCb::into_results`].
+///
+/// Beware that events from earlier chunks may still be queued in the kqueue
+/// when `is_complete` first returns true.  They'll be delivered by later
+/// polls, with the batch's token.  So after a chunked batch completes, don't
+/// reuse its token for a different source until those stale events have been
+/// drained, or be prepared to ignore them.
+///
+/// # Dropping
+///
+/// Like a [`Source`](crate::Source), dropping a batch with operations still
+/// in progress cancels them, and blocks until the kernel is done with their
+/// buffers.  Operations that have finished but weren't yet collected by
+/// [`LioCb::into_results`] are simply reaped.
+///
+/// Build one with [`LioCbBuilder`].
+#[derive(Debug)]
+pub struct LioCb<'a> {
+    ops:          Vec<LioOp<'a>>,
+    state:        Vec<LioState>,
+    sigev_notify: SigevNotify,
+    /// Most operations to submit with one `lio_listio` call
+    max_chunk:    usize,
+    /// Every `lio_listio` call's list since the batch was last reaped
+    lists:        Vec<ChunkList>,
+}
+
+impl<'a> LioCb<'a> {
+    fn set_target(&mut self, kq: RawFd, udata: usize) {
+        self.sigev_notify = SigevNotify::SigevKeventFlags {
+            kq,
+            udata: udata as isize,
+            flags: EventFlag::EV_ONESHOT,
+        };
+    }
+
+    /// Does any operation in the batch have in-kernel state?
+    pub fn in_progress(&self) -> bool {
+        self.state.contains(&LioState::InProgress)
+    }
+
+    /// Does the batch contain no operations?
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// How many operations does the batch contain?
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// How many submitted operations haven't yet finished?
+    ///
+    /// This counts operations that are still in flight, plus any that the
+    /// kernel didn't initiate and that await [`LioCb::resubmit`].  It
+    /// decreases as each operation completes, before any are reaped.
+    ///
+    /// A batch that was never submitted has nothing in flight, so this returns
+    /// zero for it, not [`LioCb::len`].  It also returns zero once
+    /// [`LioCb::into_results`] reaps the batch.  So zero alone doesn't mean
+    /// the batch is ready to reap; use [`LioCb::is_complete`] for that.
+    pub fn remaining(&self) -> usize {
+        if self.state.iter().all(|s| *s == LioState::Idle) {
+            return 0;
+        }
+        self.ops
+            .iter()
+            .zip(self.state.iter())
+            .filter(|(op, state)| match state {
+                LioState::Idle => true,
+                LioState::InProgress => raw_aio_error(*op) == libc::EINPROGRESS,
+                LioState::Done(_) => false,
+            })
+            .count()
+    }
+
+    /// Is the batch ready for [`LioCb::into_results`]?
+    ///
+    /// That is, has the batch been submitted, and has every operation
+    /// finished?  It's false before submission, and again after the batch is
+    /// reaped.
+    pub fn is_complete(&self) -> bool {
+        self.state.iter().any(|s| *s != LioState::Idle) && self.remaining() == 0
+    }
+
+    /// Submit the operations selected by `mask`, with as many `lio_listio`
+    /// calls as `max_chunk` requires.
+    ///
+    /// Returns, for each operation, the result of the call that included it,
+    /// or `None` if it wasn't selected or its call wasn't attempted.  Once a
+    /// call fails with `EAGAIN`, the kernel's queue is full, so no further
+    /// calls are attempted.
+    fn listio(
+        &mut self,
+        mask: &[bool],
+        mode: LioMode,
+        sigev_notify: SigevNotify,
+    ) -> Vec<Option<nix::Result<()>>> {
+        let mut results = vec![None; self.ops.len()];
+        let mut selected = self
+            .ops
+            .iter_mut()
+            .enumerate()
+            .zip(mask)
+            .filter(|(_, selected)| **selected)
+            .map(|(op, _)| op)
+            .collect::<Vec<_>>();
+        for chunk in selected.chunks_mut(self.max_chunk) {
+            let list = ChunkList(
+                chunk
+                    .iter_mut()
+                    .map(|(_, op)| {
+                        AsMut::<libc::aiocb>::as_mut(&mut **op)
+                            as *mut libc::aiocb
+                    })
+                    .collect(),
+            );
+            let mut sigevent = SigEvent::new(sigev_notify).sigevent();
+            // Safe because the operations are heap-allocated and we never
+            // reallocate the Vec, so they won't move until they're reaped.
+            let r = Errno::result(unsafe {
+                libc::lio_listio(
+                    mode as libc::c_int,
+                    list.0.as_ptr(),
+                    list.0.len() as libc::c_int,
+                    &mut sigevent,
+                )
+            })
+            .map(drop);
+            self.lists.push(list);
+            for (i, _) in chunk.iter() {
+                results[*i] = Some(r);
+            }
+            if r == Err(Errno::EAGAIN) {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Reap every operation, and return their results in submission order.
+    ///
+    /// Call this after the reactor has delivered the final LIO event.
+    /// Operations that were never initiated will report
+    /// [`AioError::Submit`]`(EAGAIN)`, and those that failed to queue will
+    /// report their submission error the same way.  Other operations' errors
+    /// are reported as [`AioError::Return`].  Afterwards, the batch may be
+    /// submitted again.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `EINPROGRESS`, and reaps nothing, if any operation is still
+    /// in flight.  [`LioCb::is_complete`] reports when that's no longer so.
+    pub fn into_results(
+        self: Pin<&mut Self>,
+    ) -> Result<Vec<Result<usize, AioError>>, AioError> {
+        // Safe because we don't move any operations.
+        let this = unsafe { self.get_unchecked_mut() };
+        let busy = this.ops.iter().zip(this.state.iter()).any(|(op, state)| {
+            *state == LioState::InProgress
+                && raw_aio_error(op) == libc::EINPROGRESS
+        });
+        if busy {
+            return Err(AioError::Return(Errno::EINPROGRESS));
+        }
+        this.lists.clear();
+        let results = this
+            .ops
+            .iter_mut()
+            .zip(this.state.iter_mut())
+            .map(|(op, state)| {
+                let r = match *state {
+                    LioState::Idle => Err(AioError::Submit(Errno::EAGAIN)),
+                    // Safe because the operation is heap-allocated and we
+                    // never reallocate the Vec.
+                    LioState::InProgress => unsafe { op.aio_return() },
+                    LioState::Done(r) => r,
+                };
+                *state = LioState::Idle;
+                r
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Cancel any operations still in progress, and report what became of
+    /// every operation, in submission order.
+    ///
+    /// This is for shutting down while a batch is only partly complete.
+    /// Operations that couldn't be canceled are waited for, so that
+    /// afterwards the kernel is done with every buffer.  Like
+    /// [`LioCb::into_results`], it reaps the whole batch, which may then be
+    /// submitted again.
+    pub fn drain_partial(self: Pin<&mut Self>) -> Vec<LioOutcome> {
+        // Safe because we don't move any operations.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.lists.clear();
+        this.ops
+            .iter_mut()
+            .zip(this.state.iter_mut())
+            .map(|(op, state)| {
+                let outcome = match *state {
+                    LioState::Idle => LioOutcome::Pending,
+                    // Safe because the operation is heap-allocated and we
+                    // never reallocate the Vec.
+                    LioState::InProgress => {
+                        match unsafe { op.cancel_and_reap() } {
+                            Err(AioError::Return(Errno::ECANCELED)) => {
+                                LioOutcome::Canceled
+                            }
+                            r => LioOutcome::Completed(r),
+                        }
+                    }
+                    LioState::Done(r) => LioOutcome::Completed(r),
+                };
+                *state = LioState::Idle;
+                outcome
+            })
+            .collect()
+    }
+
+    /// Retry any operations that weren't initiated by a previous call to
+    /// [`LioCb::submit`] or [`LioCb::resubmit`].
+    ///
+    /// Call this after receiving the LIO event for the operations that were
+    /// initiated, or at any time if none were.  Like `submit`, it may fail
+    /// with [`LioError::EINCOMPLETE`] again.  If there's nothing left to
+    /// initiate, it returns `Ok` immediately, and no further event will be
+    /// delivered.
+    pub fn resubmit(self: Pin<&mut Self>) -> Result<(), LioError> {
+        // Safe because we don't move any operations.
+        let this = unsafe { self.get_unchecked_mut() };
+        if !this.state.contains(&LioState::Idle) {
+            return Ok(());
+        }
+        // Reap any operations that have already finished.  Until they're
+        // reaped, they still count against the kernel's queue limits.
+        for (op, state) in this.ops.iter_mut().zip(this.state.iter_mut()) {
+            if *state == LioState::InProgress
+                && raw_aio_error(op) != libc::EINPROGRESS
+            {
+                // Safe because the operation is heap-allocated and we never
+                // reallocate the Vec.
+                *state = LioState::Done(unsafe { op.aio_return() });
+            }
+        }
+        let idle = this
+            .state
+            .iter()
+            .map(|s| *s == LioState::Idle)
+            .collect::<Vec<_>>();
+        let sigev_notify = this.sigev_notify;
+        let results = this.listio(&idle, LioMode::LIO_NOWAIT, sigev_notify);
+        let mut first_error = None;
+        let mut initiated = false;
+        for ((op, state), r) in
+            this.ops.iter_mut().zip(this.state.iter_mut()).zip(results)
+        {
+            let e = match r {
+                None => continue,
+                Some(Ok(())) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::record_submitted(1);
+                    *state = LioState::InProgress;
+                    initiated = true;
+                    continue;
+                }
+                Some(Err(e)) => e,
+            };
+            first_error.get_or_insert(e);
+            // The operation's call failed, but the operation itself may
+            // have been initiated.  Figure out whether it was.
+            match raw_aio_error(op) {
+                libc::EAGAIN => (),
+                0 | libc::EINPROGRESS => {
+                    #[cfg(feature = "metrics")]
+                    metrics::record_submitted(1);
+                    *state = LioState::InProgress;
+                    initiated = true;
+                }
+                // Either the operation failed to queue, in which case the
+                // kernel stashed its error in the aiocb, or else it was
+                // queued and has already failed.  Only in the latter case
+                // must it be reaped.
+                // Safe because the operation is heap-allocated and we never
+                // reallocate the Vec.
+                err => match unsafe { op.aio_return() } {
+                    Err(AioError::Return(Errno::EINVAL)) => {
+                        let e = AioError::Submit(Errno::from_raw(err));
+                        *state = LioState::Done(Err(e));
+                    }
+                    r => {
+                        #[cfg(feature = "metrics")]
+                        metrics::record_submitted(1);
+                        *state = LioState::Done(r);
+                        initiated = true;
+                    }
+                },
+            }
+        }
+        match first_error {
+            None => Ok(()),
+            Some(_) if initiated => {
+                #[cfg(feature = "metrics")]
+                metrics::record_lio_requeue();
+                Err(LioError::EINCOMPLETE)
+            }
+            Some(e) => Err(LioError::Errno(e)),
+        }
+    }
+
+    /// Submit every operation in the batch with a single `lio_listio` call,
+    /// or with several if the batch is larger than
+    /// [`LioCbBuilder::max_chunk`].
+    ///
+    /// # Errors
+    ///
+    /// If the kernel initiated some but not all of the operations, this
+    /// returns [`LioError::EINCOMPLETE`].  The LIO event will still be
+    /// delivered once the initiated operations are complete.  At that point,
+    /// call [`LioCb::resubmit`] to initiate the rest.
+    pub fn submit(self: Pin<&mut Self>) -> Result<(), LioError> {
+        if self.in_progress() {
+            return Err(LioError::Errno(Errno::EBUSY));
+        }
+        self.resubmit()
+    }
+
+    /// Submit every operation in the batch, and block until they're all
+    /// complete.
+    ///
+    /// This uses `lio_listio`'s `LIO_WAIT` mode, so it needs no reactor, and
+    /// no LIO event will be delivered even if the batch is registered.  It's
+    /// convenient for shutdown and flush paths.  Returns each operation's
+    /// result, in submission order.  Some operations may fail while others
+    /// succeed.  Operations that the kernel didn't initiate, usually because
+    /// of resource limits, report [`AioError::Submit`]`(EAGAIN)`, as with
+    /// [`LioCb::into_results`].  Afterwards, the batch may be submitted again.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `EBUSY` if the batch is already in progress.  If none of the
+    /// operations even reached the kernel, fails with the error from
+    /// `lio_listio`, and the batch is unchanged.
+    pub fn submit_wait(
+        mut self: Pin<&mut Self>,
+    ) -> Result<Vec<Result<usize, AioError>>, LioError> {
+        if self.in_progress() {
+            return Err(LioError::Errno(Errno::EBUSY));
+        }
+        // Safe because we don't move any operations.
+        let this = unsafe { self.as_mut().get_unchecked_mut() };
+        let idle = this
+            .state
+            .iter()
+            .map(|s| *s == LioState::Idle)
+            .collect::<Vec<_>>();
+        let results =
+            this.listio(&idle, LioMode::LIO_WAIT, SigevNotify::SigevNone);
+        // lio_listio fails with EIO if any operation failed, with EAGAIN if
+        // any weren't initiated, or with EINTR if the wait was interrupted.
+        // In every case, check each operation individually.
+        let mut first_error = None;
+        let mut reaped = false;
+        for ((op, state), r) in
+            this.ops.iter_mut().zip(this.state.iter_mut()).zip(results)
+        {
+            match r {
+                // Its chunk was never submitted
+                None => continue,
+                Some(Err(e)) => {
+                    first_error.get_or_insert(e);
+                }
+                Some(Ok(())) => (),
+            }
+            let err = loop {
+                match raw_aio_error(op) {
+                    libc::EINPROGRESS => {
+                        let _ = aio::aio_suspend(&[&*op], None);
+                    }
+                    err => break err,
+                }
+            };
+            if err == libc::EAGAIN {
+                continue;
+            }
+            // Safe because the operation is heap-allocated and we never
+            // reallocate the Vec.
+            *state = match unsafe { op.aio_return() } {
+                // The operation failed to queue, so the kernel stashed its
+                // error in the aiocb.
+                Err(AioError::Return(Errno::EINVAL)) if err != 0 => {
+                    LioState::Done(Err(AioError::Submit(Errno::from_raw(err))))
+                }
+                r => {
+                    #[cfg(feature = "metrics")]
+                    metrics::record_submitted(1);
+                    LioState::Done(r)
+                }
+            };
+            reaped = true;
+        }
+        match first_error {
+            Some(e) if !reaped => Err(LioError::Errno(e)),
+            _ => self.into_results().map_err(|e| LioError::Errno(e.errno())),
+        }
+    }
+}
+
+impl<'a> Drop for LioCb<'a> {
+    fn drop(&mut self) {
+        for (op, state) in self.ops.iter_mut().zip(self.state.iter()) {
+            if *state == LioState::InProgress {
+                // Safe because the operation will be dropped in place, right
+                // after this.
+                let _ = unsafe { op.cancel_and_reap() };
+            }
+        }
+    }
+}
+
+impl<'a> event::Source for LioCb<'a> {
+    /// Register this batch with a Mio reactor.
+    ///
+    /// `interests` must include [`Interest::LIO`].  It may also include
+    /// [`Interest::AIO`], which is convenient for code that registers many
+    /// kinds of sources generically, but the batch's event will always be
+    /// reported as an LIO event.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `InvalidInput` if `interests` doesn't include
+    /// [`Interest::LIO`].
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        if !interests.is_lio() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "LioCb must be registered with Interest::LIO",
+            ));
+        }
+        self.set_target(registry.as_raw_fd(), usize::from(token));
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.register(registry, token, interests)
+    }
+
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self.sigev_notify = SigevNotify::SigevNone;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+// vim: tw=80
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use nix::errno::Errno;
+
+static SUBMITTED: AtomicU64 = AtomicU64::new(0);
+static COMPLETED: AtomicU64 = AtomicU64::new(0);
+static CANCELLED: AtomicU64 = AtomicU64::new(0);
+static ERRORED: AtomicU64 = AtomicU64::new(0);
+static LIO_REQUEUES: AtomicU64 = AtomicU64::new(0);
+
+/// Process-wide operation counters, as returned by [`snapshot`].
+///
+/// Every counter only ever increases.  Operations that are canceled before
+/// they start, and operations that fail, are counted when they're reaped.
+/// Operations that fail to submit at all are counted as `errored` but not
+/// `submitted`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Operations initiated by the kernel.
+    pub submitted:    u64,
+    /// Operations reaped successfully.
+    pub completed:    u64,
+    /// Operations reaped with `ECANCELED`.
+    pub cancelled:    u64,
+    /// Operations that failed to submit, or were reaped with any other error.
+    pub errored:      u64,
+    /// `lio_listio` submissions that only initiated part of their list, so
+    /// the rest must be resubmitted.
+    pub lio_requeues: u64,
+}
+
+/// Read the current values of the process-wide counters.
+///
+/// Composite operations like [`WriteThenFsync`](crate::WriteThenFsync) and
+/// [`WriteAtVerified`](crate::WriteAtVerified) aren't counted.
+pub fn snapshot() -> Stats {
+    Stats {
+        submitted:    SUBMITTED.load(Ordering::Relaxed),
+        completed:    COMPLETED.load(Ordering::Relaxed),
+        cancelled:    CANCELLED.load(Ordering::Relaxed),
+        errored:      ERRORED.load(Ordering::Relaxed),
+        lio_requeues: LIO_REQUEUES.load(Ordering::Relaxed),
+    }
+}
+
+/// Count the outcome of submitting a single operation.
+pub(crate) fn record_submit(r: &nix::Result<()>) {
+    let counter = if r.is_ok() { &SUBMITTED } else { &ERRORED };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count operations initiated by a single `lio_listio` call.
+pub(crate) fn record_submitted(n: u64) {
+    SUBMITTED.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Count the outcome of reaping a single operation.
+pub(crate) fn record_return<O>(r: &nix::Result<O>) {
+    let counter = match r {
+        Err(Errno::EINPROGRESS) => return,
+        Ok(_) => &COMPLETED,
+        Err(Errno::ECANCELED) => &CANCELLED,
+        Err(_) => &ERRORED,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a `lio_listio` call that only initiated part of its list.
+pub(crate) fn record_lio_requeue() {
+    LIO_REQUEUES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Measures how long a single operation has been in flight.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Stopwatch {
+    started: Option<Instant>,
+    /// Frozen once the operation is reaped
+    elapsed: Option<Duration>,
+}
+
+impl Stopwatch {
+    /// Start timing a newly submitted operation.
+    pub(crate) fn start(&mut self) {
+        self.started = Some(Instant::now());
+        self.elapsed = None;
+    }
+
+    /// Stop timing a reaped operation.
+    pub(crate) fn stop(&mut self) {
+        if let Some(started) = self.started {
+            self.elapsed.get_or_insert_with(|| started.elapsed());
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        self.elapsed.or_else(|| self.started.map(|s| s.elapsed()))
+    }
+}
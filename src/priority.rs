@@ -0,0 +1,60 @@
+// vim: tw=80
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use nix::{errno::Errno, libc};
+
+/// Cached value of `AIO_PRIO_DELTA_MAX`, or -1 if not yet known.
+static PRIO_DELTA_MAX: AtomicI32 = AtomicI32::new(-1);
+
+/// The largest amount by which an operation's priority may be lowered.
+fn prio_delta_max() -> i32 {
+    let cached = PRIO_DELTA_MAX.load(Ordering::Relaxed);
+    if cached >= 0 {
+        return cached;
+    }
+    // Safe because sysconf has no side effects.
+    let max = unsafe { libc::sysconf(libc::_SC_AIO_PRIO_DELTA_MAX) };
+    // -1 means that there's no such limit, or that priorities aren't
+    // supported at all.  Either way, only the default priority is valid.
+    let max = max.clamp(0, i32::MAX as libc::c_long) as i32;
+    PRIO_DELTA_MAX.store(max, Ordering::Relaxed);
+    max
+}
+
+/// An AIO request priority.
+///
+/// POSIX lets each operation lower its own priority by between 0 and
+/// `AIO_PRIO_DELTA_MAX`, relative to the process's scheduling priority.  Out
+/// of range values may be silently ignored or rejected when the operation is
+/// submitted.  [`AioPriority::new`] checks the value up front instead.
+///
+/// Every constructor accepts `impl Into<AioPriority>`, so a plain `i32` works
+/// too.  That conversion clamps negative values to 0, but otherwise doesn't
+/// check the system's limit.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AioPriority(i32);
+
+impl AioPriority {
+    /// Create a priority, checking that it's within the system's limits.
+    ///
+    /// Fails with `EINVAL` if `prio` is negative or greater than
+    /// `AIO_PRIO_DELTA_MAX`.
+    pub fn new(prio: i32) -> Result<Self, Errno> {
+        if (0..=prio_delta_max()).contains(&prio) {
+            Ok(AioPriority(prio))
+        } else {
+            Err(Errno::EINVAL)
+        }
+    }
+
+    /// Get the raw priority value.
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl From<i32> for AioPriority {
+    fn from(prio: i32) -> Self {
+        AioPriority(prio.max(0))
+    }
+}
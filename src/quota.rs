@@ -0,0 +1,83 @@
+// vim: tw=80
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::capabilities::aio_limits;
+
+#[derive(Debug)]
+struct Inner {
+    limit:     usize,
+    available: AtomicUsize,
+}
+
+/// A counting semaphore that limits how many operations are in flight.
+///
+/// Once the process's AIO queue is full, every further submission fails with
+/// `EAGAIN`, wasting a syscall.  Under high concurrency, callers can instead
+/// acquire a [`Permit`] from a shared `AioQuota` before each submission, and
+/// pass it to [`Source::submit_with_permit`](crate::Source::submit_with_permit).
+/// When [`AioQuota::try_acquire`] returns `None`, the caller knows to back off
+/// until some operation completes, without asking the kernel.
+///
+/// The permit is held until the operation is reaped, or the `Source` is
+/// dropped.  Note that canceling an operation doesn't release its permit,
+/// because the kernel counts canceled operations against the limit until
+/// they're reaped.
+///
+/// The quota only knows about operations submitted with its permits.
+/// Operations submitted any other way, including by other `AioQuota`s, still
+/// count against the kernel's limit.  Clones share the same permits.
+#[derive(Clone, Debug)]
+pub struct AioQuota(Arc<Inner>);
+
+impl AioQuota {
+    /// Create a quota allowing up to `limit` operations in flight.
+    pub fn new(limit: usize) -> Self {
+        AioQuota(Arc::new(Inner {
+            limit,
+            available: AtomicUsize::new(limit),
+        }))
+    }
+
+    /// Create a quota matching the process's queue limit, as reported by
+    /// [`aio_limits`](crate::aio_limits).
+    pub fn from_limits() -> Self {
+        AioQuota::new(aio_limits().max_queue_per_proc)
+    }
+
+    /// The total number of permits.
+    pub fn limit(&self) -> usize {
+        self.0.limit
+    }
+
+    /// The number of permits not currently held.
+    pub fn available(&self) -> usize {
+        self.0.available.load(Ordering::Acquire)
+    }
+
+    /// Acquire a permit to submit one operation, if any are available.
+    pub fn try_acquire(&self) -> Option<Permit> {
+        self.0
+            .available
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                n.checked_sub(1)
+            })
+            .ok()
+            .map(|_| Permit(self.0.clone()))
+    }
+}
+
+/// Permission to have one operation in flight, from an [`AioQuota`].
+///
+/// Dropping it returns the permit to the quota.
+#[derive(Debug)]
+#[must_use = "dropping a permit immediately releases it"]
+pub struct Permit(Arc<Inner>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.available.fetch_add(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,375 @@
+// vim: tw=80
+use std::{
+    io,
+    mem,
+    os::unix::io::{AsRawFd, BorrowedFd, RawFd},
+    pin::Pin,
+    ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use mio::{event, Interest, Registry, Token};
+use nix::{
+    errno::Errno,
+    libc::{self, off_t},
+};
+
+use crate::{
+    aio::{check_interests, transferred, CancelStat, Source, SourceApi},
+    error::{misuse, AioError},
+};
+
+/// Size of the bounce buffer used when `sendfile` isn't applicable.
+const FALLBACK_BUFSIZE: usize = 65536;
+
+/// Source of unique `EVFILT_USER` identifiers.
+pub(crate) static NEXT_IDENT: AtomicUsize = AtomicUsize::new(0);
+
+/// State shared with the helper thread.
+#[derive(Debug, Default)]
+struct Shared {
+    /// The kqueue and udata to notify upon completion, if registered.
+    target: Option<(RawFd, usize)>,
+    /// The operation's final result, once complete.
+    result: Option<nix::Result<usize>>,
+}
+
+/// Copies data from a file to a socket without going through userspace.
+///
+/// AIO must always copy data through a user buffer, but `sendfile(2)` can
+/// avoid that when the destination is a socket.  `SendFile` runs `sendfile`
+/// on a helper thread, and posts a completion notification to the reactor
+/// when it's done.  So it can be registered, submitted, and reaped just like
+/// any other [`Source`].  If `sendfile` isn't applicable, for example because
+/// the destination isn't a socket, the helper thread falls back to copying
+/// the data through a bounce buffer, reading it with AIO and writing it with
+/// `write(2)`.
+///
+/// Like other operations, `SendFile` must be registered with
+/// [`Interest::AIO`].  But its completion notification uses `EVFILT_USER`, so
+/// Mio reports it as readable rather than AIO.  Also, the destination should
+/// be in blocking mode.
+///
+/// Dropping a `SendFile` blocks until the helper thread is finished.
+#[derive(Debug)]
+pub struct SendFile<'a> {
+    fd:     BorrowedFd<'a>,
+    offs:   u64,
+    nbytes: usize,
+    sock:   BorrowedFd<'a>,
+    ident:  usize,
+    shared: Arc<Mutex<Shared>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<'a> SendFile<'a> {
+    /// Construct a new `SendFile` operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`:     Regular file to read from
+    /// * `offs`:   File offset at which to start reading
+    /// * `nbytes`: Number of bytes to copy
+    /// * `sock`:   Socket to write to.  Should be in blocking mode.
+    pub fn new(
+        fd: BorrowedFd<'a>,
+        offs: u64,
+        nbytes: usize,
+        sock: BorrowedFd<'a>,
+    ) -> Self {
+        SendFile {
+            fd,
+            offs,
+            nbytes,
+            sock,
+            ident: NEXT_IDENT.fetch_add(1, Ordering::Relaxed),
+            shared: Arc::default(),
+            thread: None,
+        }
+    }
+
+    fn set_target(&mut self, target: Option<(RawFd, usize)>) {
+        self.shared.lock().unwrap().target = target;
+    }
+
+    /// Stop notifying the reactor, and withdraw any notification that it
+    /// hasn't yet delivered.
+    fn clear_target(&mut self) {
+        // Hold the lock, so the helper thread can't post another
+        // notification in between.
+        let mut shared = self.shared.lock().unwrap();
+        if let Some((kq, _)) = shared.target.take() {
+            untrigger(kq, self.ident);
+        }
+    }
+}
+
+impl<'a> Drop for SendFile<'a> {
+    fn drop(&mut self) {
+        // The helper thread uses our file descriptors, so it must not outlive
+        // their borrow.
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<'a> event::Source for SendFile<'a> {
+    /// Register this operation with a Mio reactor.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `InvalidInput` if `interests` doesn't include
+    /// [`Interest::AIO`].
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        check_interests(interests)?;
+        self.set_target(Some((registry.as_raw_fd(), token.into())));
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.register(registry, token, interests)
+    }
+
+    /// Deregister this operation.
+    ///
+    /// If the operation has already completed but its notification hasn't yet
+    /// been delivered, the notification is withdrawn, so it can't be
+    /// mistaken for an event of whatever next uses the same token.
+    fn deregister(&mut self, _registry: &Registry) -> io::Result<()> {
+        self.clear_target();
+        Ok(())
+    }
+}
+
+impl<'a> SourceApi for SendFile<'a> {
+    type Output = usize;
+
+    fn aio_return(mut self: Pin<&mut Self>) -> Result<usize, AioError> {
+        if self.thread.is_none() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "aio_return before submit",
+            )));
+        }
+        let result = self.shared.lock().unwrap().result.take();
+        let r = result.ok_or(AioError::Return(Errno::EINPROGRESS))?;
+        if let Some(thread) = self.thread.take() {
+            // The thread has already stored its result, so it's about to
+            // exit.
+            let _ = thread.join();
+        }
+        r.map_err(AioError::Return)
+    }
+
+    fn bytes_transferred(self: Pin<&mut Self>) -> Option<usize> {
+        transferred(self.aio_return(), |n| n)
+    }
+
+    /// `sendfile` can't be canceled once started.
+    fn cancel(self: Pin<&mut Self>) -> Result<CancelStat, AioError> {
+        if self.thread.is_none() {
+            return Err(AioError::Cancel(misuse(
+                Errno::EINVAL,
+                "cancel before submit",
+            )));
+        }
+        if self.shared.lock().unwrap().result.is_some() {
+            Ok(CancelStat::AioAllDone)
+        } else {
+            Ok(CancelStat::AioNotCanceled)
+        }
+    }
+
+    fn error(self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.thread.is_none() {
+            return Err(AioError::Return(misuse(
+                Errno::EINVAL,
+                "error before submit",
+            )));
+        }
+        match self.shared.lock().unwrap().result {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(e)) => Err(AioError::Return(e)),
+            None => Err(AioError::Return(Errno::EINPROGRESS)),
+        }
+    }
+
+    fn in_progress(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn deregister_raw(&mut self) {
+        self.clear_target();
+    }
+
+    #[cfg(feature = "tokio")]
+    fn register_raw(&mut self, kq: RawFd, udata: usize) {
+        self.set_target(Some((kq, udata)));
+    }
+
+    fn submit(mut self: Pin<&mut Self>) -> Result<(), AioError> {
+        if self.thread.is_some() {
+            return Err(AioError::Submit(misuse(
+                Errno::EBUSY,
+                "submit while in progress",
+            )));
+        }
+        let fd = self.fd.as_raw_fd();
+        let sock = self.sock.as_raw_fd();
+        let offs = self.offs;
+        let nbytes = self.nbytes;
+        let ident = self.ident;
+        let shared = self.shared.clone();
+        let thread = thread::Builder::new()
+            .name("mio-aio sendfile".to_owned())
+            .spawn(move || {
+                let r = sendfile(fd, sock, offs, nbytes);
+                let mut shared = shared.lock().unwrap();
+                shared.result = Some(r);
+                if let Some((kq, udata)) = shared.target {
+                    trigger(kq, ident, udata);
+                }
+            })
+            .map_err(|_| AioError::Submit(Errno::EAGAIN))?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+}
+
+/// Copy `nbytes` from `fd` to `sock`, preferring `sendfile(2)`.
+///
+/// Returns the number of bytes copied, which may be short if `fd` hits EOF.
+fn sendfile(
+    fd: RawFd,
+    sock: RawFd,
+    offs: u64,
+    nbytes: usize,
+) -> nix::Result<usize> {
+    let mut sent = 0;
+    while sent < nbytes {
+        let mut sbytes: off_t = 0;
+        // Safe because we don't pass any headers or trailers.
+        let r = unsafe {
+            libc::sendfile(
+                fd,
+                sock,
+                (offs + sent as u64) as off_t,
+                nbytes - sent,
+                ptr::null_mut(),
+                &mut sbytes,
+                0,
+            )
+        };
+        sent += sbytes as usize;
+        if r == 0 {
+            // Either done, or EOF.
+            break;
+        }
+        match Errno::last() {
+            Errno::EINTR | Errno::EAGAIN | Errno::EBUSY => continue,
+            Errno::ENOTSOCK | Errno::EOPNOTSUPP | Errno::EINVAL
+                if sent == 0 =>
+            {
+                return copy(fd, sock, offs, nbytes);
+            }
+            e => return Err(e),
+        }
+    }
+    Ok(sent)
+}
+
+/// Copy `nbytes` from `fd` to `sock` through a bounce buffer, reading with AIO.
+fn copy(
+    fd: RawFd,
+    sock: RawFd,
+    offs: u64,
+    nbytes: usize,
+) -> nix::Result<usize> {
+    // Safe because SendFile's Drop joins this thread before its borrow of the
+    // file ends.
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    let mut buf = vec![0u8; FALLBACK_BUFSIZE.min(nbytes)];
+    let mut copied = 0;
+    while copied < nbytes {
+        let len = buf.len().min(nbytes - copied);
+        let nread = read_aio(fd, offs + copied as u64, &mut buf[..len])?;
+        if nread == 0 {
+            break;
+        }
+        let mut written = 0;
+        while written < nread {
+            // Safe because buf is valid for nread bytes.
+            let r = unsafe {
+                libc::write(
+                    sock,
+                    buf[written..nread].as_ptr().cast(),
+                    nread - written,
+                )
+            };
+            written += Errno::result(r)? as usize;
+        }
+        copied += nread;
+    }
+    Ok(copied)
+}
+
+/// Read into `buf` with AIO, and block until the read is complete.
+fn read_aio(fd: BorrowedFd, offs: u64, buf: &mut [u8]) -> nix::Result<usize> {
+    let mut aior = Box::pin(Source::read_at(fd, offs, buf, 0));
+    aior.as_mut().submit()?;
+    loop {
+        match aior.suspend(None).map_err(AioError::errno) {
+            Ok(()) => break,
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(aior.as_mut().aio_return()?)
+}
+
+/// Post an `EVFILT_USER` event to a kqueue.
+pub(crate) fn trigger(kq: RawFd, ident: usize, udata: usize) {
+    // Safe because every field of a kevent may be zero.
+    let mut kev: libc::kevent = unsafe { mem::zeroed() };
+    kev.ident = ident;
+    kev.filter = libc::EVFILT_USER;
+    kev.flags = libc::EV_ADD | libc::EV_ONESHOT;
+    kev.fflags = libc::NOTE_TRIGGER;
+    kev.udata = udata as *mut libc::c_void;
+    // Safe because we pass exactly one change and no events.  If it fails,
+    // the kqueue must already be closed, so there's nobody to notify.
+    unsafe {
+        libc::kevent(kq, &kev, 1, ptr::null_mut(), 0, ptr::null());
+    }
+}
+
+/// Remove an `EVFILT_USER` event from a kqueue, if it's still there.
+fn untrigger(kq: RawFd, ident: usize) {
+    // Safe because every field of a kevent may be zero.
+    let mut kev: libc::kevent = unsafe { mem::zeroed() };
+    kev.ident = ident;
+    kev.filter = libc::EVFILT_USER;
+    kev.flags = libc::EV_DELETE;
+    // Safe because we pass exactly one change and no events.  It fails with
+    // ENOENT if the event was never posted, or was already delivered.
+    unsafe {
+        libc::kevent(kq, &kev, 1, ptr::null_mut(), 0, ptr::null());
+    }
+}
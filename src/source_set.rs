@@ -0,0 +1,106 @@
+// vim: tw=80
+use std::{fmt, io, pin::Pin};
+
+use mio::{event, Events, Interest, Registry, Token};
+use nix::errno::Errno;
+
+use crate::{aio::SourceApi, AioError};
+
+type Slot<'a> = Option<Pin<Box<dyn SourceApi<Output = usize> + 'a>>>;
+
+/// A collection of independent operations that share one `Poll`.
+///
+/// Each operation added to the set is registered and submitted, with a token
+/// chosen by the set.  After polling, pass the events to
+/// [`SourceSet::drain_completed`] to reap whichever operations finished.
+/// That saves the caller from matching tokens back to operations by hand.
+///
+/// The operations may be of different types, so long as they all return a
+/// byte count.  Tokens are indices into the set, and are reused once their
+/// operations are reaped.  Events for other tokens are ignored, but the
+/// `Poll` should not be shared with sources whose tokens could collide.
+#[derive(Default)]
+pub struct SourceSet<'a> {
+    sources: Vec<Slot<'a>>,
+    /// Indices of empty slots in `sources`
+    free:    Vec<usize>,
+}
+
+impl<'a> SourceSet<'a> {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many operations are in the set?
+    pub fn len(&self) -> usize {
+        self.sources.len() - self.free.len()
+    }
+
+    /// Does the set contain no operations?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Register and submit a new operation.
+    ///
+    /// Returns the token that will accompany its completion.  If registration
+    /// or submission fails, the operation is dropped.
+    pub fn push<S>(
+        &mut self,
+        registry: &Registry,
+        mut source: S,
+    ) -> io::Result<Token>
+    where
+        S: SourceApi<Output = usize> + event::Source + 'a,
+    {
+        let idx = self.free.last().copied().unwrap_or(self.sources.len());
+        registry.register(&mut source, Token(idx), Interest::AIO)?;
+        let mut source = Box::pin(source);
+        source.as_mut().submit()?;
+        if self.free.pop().is_none() {
+            self.sources.push(None);
+        }
+        self.sources[idx] = Some(source);
+        Ok(Token(idx))
+    }
+
+    /// Reap every operation in the set that `events` reports as complete.
+    ///
+    /// Yields each operation's token along with the result of
+    /// [`SourceApi::aio_return`].  Events whose tokens don't belong to the set
+    /// are skipped, as are events that are neither AIO nor readable.  Readable
+    /// events come from operations emulated per the
+    /// [`FallbackPolicy`](crate::FallbackPolicy).  Multi-stage operations
+    /// like [`WriteThenFsync`](crate::WriteThenFsync) stay in the set until
+    /// their final stage completes.
+    pub fn drain_completed(
+        &mut self,
+        events: &Events,
+    ) -> impl Iterator<Item = (Token, Result<usize, AioError>)> {
+        let mut completed = Vec::new();
+        for ev in events.iter().filter(|ev| ev.is_aio() || ev.is_readable()) {
+            let idx = usize::from(ev.token());
+            let slot = match self.sources.get_mut(idx) {
+                Some(slot @ Some(_)) => slot,
+                _ => continue,
+            };
+            let r = slot.as_mut().unwrap().as_mut().aio_return();
+            if r == Err(AioError::Return(Errno::EINPROGRESS)) {
+                continue;
+            }
+            *slot = None;
+            self.free.push(idx);
+            completed.push((ev.token(), r));
+        }
+        completed.into_iter()
+    }
+}
+
+impl<'a> fmt::Debug for SourceSet<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceSet")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    io::{Read, Seek, Write},
+    os::unix::io::AsFd,
+};
+
+use mio::{Events, Interest, Poll, Token};
+use mio_aio::{FallbackPolicy, SourceApi, State};
+use tempfile::tempfile;
+
+const UDATA: Token = Token(0xdead_beef);
+
+/// Even with AIO available, a forced fallback should deliver a completion
+/// event, just like a real AIO operation.
+#[test]
+fn write_at() {
+    const WBUF: &[u8] = b"abcdef";
+    FallbackPolicy::Always.set();
+    let mut f = tempfile().unwrap();
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+    poll.registry()
+        .register(&mut aiow, UDATA, Interest::AIO)
+        .expect("registration failed");
+    let mut aiow = Box::pin(aiow);
+    aiow.as_mut().submit().unwrap();
+    assert_eq!(aiow.state(), State::Completed);
+    poll.poll(&mut events, None).expect("poll failed");
+    let mut it = events.iter();
+    let ev = it.next().unwrap();
+    assert_eq!(ev.token(), UDATA);
+    assert!(ev.is_readable());
+    assert_eq!(aiow.as_mut().aio_return(), Ok(WBUF.len()));
+    assert!(!aiow.in_progress());
+    drop(aiow);
+
+    let mut rbuf = Vec::new();
+    f.rewind().unwrap();
+    f.read_to_end(&mut rbuf).unwrap();
+    assert_eq!(rbuf, WBUF);
+}
+
+#[test]
+fn read_at() {
+    FallbackPolicy::Always.set();
+    let mut f = tempfile().unwrap();
+    f.write_all(b"abcdef").unwrap();
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let mut rbuf = vec![0; 4];
+
+    let mut aior = mio_aio::Source::read_at(f.as_fd(), 2, &mut rbuf, 0);
+    poll.registry()
+        .register(&mut aior, UDATA, Interest::AIO)
+        .expect("registration failed");
+    let mut aior = Box::pin(aior);
+    aior.as_mut().submit().unwrap();
+    poll.poll(&mut events, None).expect("poll failed");
+    assert_eq!(events.iter().next().unwrap().token(), UDATA);
+    assert_eq!(aior.as_mut().aio_return(), Ok(4));
+    drop(aior);
+    assert_eq!(rbuf, b"cdef");
+}
+
+/// Errors from the synchronous call are reported at completion
+#[test]
+fn error() {
+    FallbackPolicy::Always.set();
+    let f = tempfile().unwrap();
+    let mut aiow =
+        Box::pin(mio_aio::Source::write_at(f.as_fd(), u64::MAX, b"abc", 0));
+    aiow.as_mut().submit().unwrap();
+    assert_eq!(
+        aiow.as_mut().error(),
+        Err(mio_aio::AioError::Return(mio_aio::Errno::EINVAL))
+    );
+    assert_eq!(
+        aiow.as_mut().aio_return(),
+        Err(mio_aio::AioError::Return(mio_aio::Errno::EINVAL))
+    );
+}
+
+/// SourceSet should reap emulated operations, despite their readable events
+#[test]
+fn source_set() {
+    FallbackPolicy::Always.set();
+    let f = tempfile().unwrap();
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let mut set = mio_aio::SourceSet::new();
+
+    let t0 = set
+        .push(
+            poll.registry(),
+            mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0),
+        )
+        .unwrap();
+    let t1 = set
+        .push(
+            poll.registry(),
+            mio_aio::Source::write_at(f.as_fd(), 3, b"defg", 0),
+        )
+        .unwrap();
+    let mut results = Vec::new();
+    while !set.is_empty() {
+        poll.poll(&mut events, None).expect("poll failed");
+        results.extend(set.drain_completed(&events));
+    }
+    results.sort_by_key(|(token, _)| usize::from(*token));
+    assert_eq!(results, vec![(t0, Ok(3)), (t1, Ok(4))]);
+}
@@ -9,7 +9,7 @@ use std::{
 };
 
 use mio::{Events, Interest, Poll, Token};
-use mio_aio::SourceApi;
+use mio_aio::{AioError, SourceApi};
 use tempfile::tempfile;
 
 const UDATA: Token = Token(0xdead_beef);
@@ -46,6 +46,139 @@ pub fn test_aio_cancel() {
     assert!(it.next().is_none());
 }
 
+/// Registering an in-progress operation with a different reactor should fail,
+/// since its completion would silently go to the original reactor.
+#[test]
+pub fn test_cross_reactor_registration() {
+    let f = tempfile().unwrap();
+    let poll0 = Poll::new().unwrap();
+    let mut poll1 = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let mut aiof =
+        mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+    poll1
+        .registry()
+        .register(&mut aiof, UDATA, Interest::AIO)
+        .expect("registration failed");
+    let mut aiof = Box::pin(aiof);
+    aiof.as_mut().submit().unwrap();
+
+    // Safe because registration doesn't move the source
+    let aiof_ref = unsafe { aiof.as_mut().get_unchecked_mut() };
+    let e = poll0
+        .registry()
+        .reregister(aiof_ref, UDATA, Interest::AIO)
+        .unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(mio_aio::Errno::EBUSY as i32));
+
+    poll1.poll(&mut events, None).expect("poll failed");
+    let ev = events.iter().next().unwrap();
+    assert_eq!(ev.token(), UDATA);
+    aiof.as_mut().aio_return().unwrap();
+}
+
+mod abort_pending {
+    use mio_aio::State;
+
+    use super::*;
+
+    #[test]
+    fn ok() {
+        let f = tempfile().unwrap();
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        let mut aiof = Box::pin(aiof);
+        assert_eq!(aiof.state(), State::Idle);
+
+        aiof.as_mut().abort_pending().unwrap();
+        assert_eq!(aiof.state(), State::Aborted);
+        assert_eq!(
+            aiof.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::ECANCELED))
+        );
+        assert!(!aiof.in_progress());
+    }
+
+    #[test]
+    fn in_progress() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiof = Box::pin(aiof);
+
+        aiof.as_mut().submit().unwrap();
+        assert_eq!(aiof.state(), State::InProgress);
+        assert_eq!(
+            aiof.as_mut().abort_pending(),
+            Err(AioError::Cancel(mio_aio::Errno::EBUSY))
+        );
+
+        poll.poll(&mut events, None).expect("poll failed");
+        aiof.as_mut().aio_return().unwrap();
+        assert_eq!(aiof.state(), State::Idle);
+    }
+}
+
+mod aio_file {
+    use std::io::SeekFrom;
+
+    use super::*;
+
+    #[test]
+    fn read() {
+        const INITIAL: &[u8] = b"abcdef123456";
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+
+        let mut af = mio_aio::AioFile::new(f.as_fd()).unwrap();
+        assert_eq!(af.seek(SeekFrom::Start(2)).unwrap(), 2);
+        let mut rbuf = vec![0; 4];
+        af.read_exact(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"cdef");
+        assert_eq!(af.position(), 6);
+        let mut rest = Vec::new();
+        af.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"123456");
+    }
+
+    #[test]
+    fn seek() {
+        let mut f = tempfile().unwrap();
+        f.write_all(b"abcdef").unwrap();
+
+        let mut af = mio_aio::AioFile::new(f.as_fd()).unwrap();
+        assert_eq!(af.seek(SeekFrom::End(-2)).unwrap(), 4);
+        assert_eq!(af.seek(SeekFrom::Current(-3)).unwrap(), 1);
+        assert_eq!(af.stream_position().unwrap(), 1);
+        let e = af.seek(SeekFrom::Current(-2)).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(mio_aio::Errno::EINVAL as i32));
+        assert_eq!(af.position(), 1);
+    }
+
+    #[test]
+    fn write() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+
+        let mut af = mio_aio::AioFile::new(f.as_fd()).unwrap();
+        af.write_all(WBUF).unwrap();
+        af.write_all(WBUF).unwrap();
+        assert_eq!(af.position(), 12);
+        drop(af);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abcdefabcdef");
+    }
+}
+
 mod aio_fsync {
     use super::*;
 
@@ -75,6 +208,27 @@ mod aio_fsync {
         aiof.as_mut().aio_return().unwrap();
         assert!(it.next().is_none());
     }
+
+    /// peek_error should work on an unpinned shared reference
+    #[test]
+    fn peek_error() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+
+        let sources: &[&mio_aio::Fsync] = &[&aiof];
+        assert!(sources.iter().all(|s| s.peek_error().is_ok()));
+        aiof.as_mut().aio_return().unwrap();
+    }
 }
 
 mod aio_read {
@@ -116,32 +270,27 @@ mod aio_read {
         }
         assert!(rbuf.deref() == EXPECT);
     }
-}
-
-mod aio_readv {
-    use super::*;
 
+    /// The on_complete closure should see exactly the data read
     #[test]
-    fn ok() {
+    fn on_complete() {
         const INITIAL: &[u8] = b"abcdef123456";
-        let mut rbuf0 = vec![0; 4];
-        let mut rbuf1 = vec![0; 2];
-        let mut rbufs =
-            [IoSliceMut::new(&mut rbuf0), IoSliceMut::new(&mut rbuf1)];
-        const EXPECT0: &[u8] = b"cdef";
-        const EXPECT1: &[u8] = b"12";
+        let mut rbuf = vec![0; 16];
+        const EXPECT: &[u8] = b"cdef123456";
         let mut f = tempfile().unwrap();
         f.write_all(INITIAL).unwrap();
+        let mut seen = Vec::new();
 
         let mut poll = Poll::new().unwrap();
         let mut events = Events::with_capacity(1024);
         {
-            let mut aior = mio_aio::Source::readv_at(
+            let mut aior = mio_aio::Source::read_at(
                 f.as_fd(),
                 2, //offset
-                &mut rbufs,
+                &mut rbuf,
                 0, //priority
-            );
+            )
+            .on_complete(|data| seen.extend_from_slice(data));
             poll.registry()
                 .register(&mut aior, UDATA, Interest::AIO)
                 .expect("registration failed");
@@ -150,123 +299,4128 @@ mod aio_readv {
             aior.as_mut().submit().unwrap();
 
             poll.poll(&mut events, None).expect("poll failed");
-            let mut it = events.iter();
-            let ev = it.next().unwrap();
+            let ev = events.iter().next().unwrap();
             assert_eq!(ev.token(), UDATA);
-            assert!(ev.is_aio());
+            assert_eq!(aior.as_mut().aio_return().unwrap(), EXPECT.len());
+        }
+        assert_eq!(seen, EXPECT);
+    }
 
-            assert!(aior.as_mut().error().is_ok());
-            assert_eq!(
-                aior.as_mut().aio_return().unwrap(),
-                (EXPECT0.len() + EXPECT1.len())
+    /// A single registered Source may be rebound to a different file
+    #[test]
+    fn set_fd() {
+        const INITIAL0: &[u8] = b"abcdef123456";
+        const INITIAL1: &[u8] = b"ghijkl789012";
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 4];
+        const EXPECT0: &[u8] = b"cdef";
+        const EXPECT1: &[u8] = b"7890";
+        let mut f0 = tempfile().unwrap();
+        f0.write_all(INITIAL0).unwrap();
+        let mut f1 = tempfile().unwrap();
+        f1.write_all(INITIAL1).unwrap();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aior = mio_aio::Source::read_at(
+                f0.as_fd(),
+                2, //offset
+                &mut rbuf0,
+                0, //priority
             );
-            assert!(it.next().is_none());
+            poll.registry()
+                .register(&mut aior, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aior = Box::pin(aior);
+
+            aior.as_mut().submit().unwrap();
+            poll.poll(&mut events, None).expect("poll failed");
+            let ev = events.iter().next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert_eq!(aior.as_mut().aio_return().unwrap(), EXPECT0.len());
+
+            aior.as_mut().set_fd(f1.as_fd()).unwrap();
+            aior.as_mut().set_offset(6).unwrap();
+            aior.as_mut().set_buffer(&mut rbuf1).unwrap();
+            aior.as_mut().submit().unwrap();
+            assert_eq!(aior.as_mut().set_offset(0), Err(mio_aio::Errno::EBUSY));
+            poll.poll(&mut events, None).expect("poll failed");
+            let ev = events.iter().next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert_eq!(aior.as_mut().aio_return().unwrap(), EXPECT1.len());
         }
-        assert!(rbuf0 == EXPECT0);
-        assert!(rbuf1 == EXPECT1);
+        assert_eq!(rbuf0, EXPECT0);
+        assert_eq!(rbuf1, EXPECT1);
     }
 }
 
-mod aio_write {
+mod cancel_fd {
     use super::*;
 
+    /// Cancel several operations on the same file at once
     #[test]
-    fn cancel() {
-        let wbuf = String::from("abcdef").into_bytes().into_boxed_slice();
+    fn many() {
+        const WBUF: &[u8] = b"abcdef";
         let f = tempfile().unwrap();
-
         let mut poll = Poll::new().unwrap();
         let mut events = Events::with_capacity(1024);
-        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
-        poll.registry()
-            .register(&mut aiow, UDATA, Interest::AIO)
-            .expect("registration failed");
-        let mut aiow = Box::pin(aiow);
 
+        let mut ops = (0..4)
+            .map(|i| {
+                let mut aiow = mio_aio::Source::write_at(
+                    f.as_fd(),
+                    (i * WBUF.len()) as u64,
+                    WBUF,
+                    0,
+                );
+                poll.registry()
+                    .register(&mut aiow, Token(i), Interest::AIO)
+                    .expect("registration failed");
+                Box::pin(aiow)
+            })
+            .collect::<Vec<_>>();
+        for op in ops.iter_mut() {
+            op.as_mut().submit().unwrap();
+        }
+        mio_aio::cancel_fd(f.as_fd()).expect("aio_cancel failed");
+
+        // Every operation must still be reaped, whether or not it was
+        // canceled.
+        let mut remaining = ops.len();
+        while remaining > 0 {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                let op = &mut ops[usize::from(ev.token())];
+                match op.as_mut().aio_return() {
+                    Ok(n) => assert_eq!(n, WBUF.len()),
+                    Err(e) => assert_eq!(
+                        e,
+                        AioError::Return(mio_aio::Errno::ECANCELED)
+                    ),
+                }
+                remaining -= 1;
+            }
+        }
+    }
+}
+
+mod cancel_and_wait {
+    use mio_aio::CancelOutcome;
+
+    use super::*;
+
+    /// Regular files usually can't cancel an operation once it has started,
+    /// so this will typically wait for the write to finish.
+    #[test]
+    fn regular_file() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut aiow =
+            mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0).into_pinned_box();
         aiow.as_mut().submit().unwrap();
-        aiow.as_mut().cancel().unwrap();
+        match aiow.as_mut().cancel_and_wait().unwrap() {
+            CancelOutcome::Completed => {
+                assert_eq!(aiow.as_mut().aio_return(), Ok(WBUF.len()))
+            }
+            CancelOutcome::Canceled => assert_eq!(
+                aiow.as_mut().aio_return(),
+                Err(AioError::Return(mio_aio::Errno::ECANCELED))
+            ),
+            CancelOutcome::Failed(e) => panic!("write failed: {}", e),
+        }
+    }
 
-        poll.poll(&mut events, None).expect("poll failed");
-        let mut it = events.iter();
-        let ev = it.next().unwrap();
-        assert_eq!(ev.token(), UDATA);
-        assert!(ev.is_aio());
+    #[test]
+    fn before_submit() {
+        let f = tempfile().unwrap();
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0)
+            .into_pinned_box();
+        assert_eq!(
+            aiow.as_mut().cancel_and_wait(),
+            Err(AioError::Cancel(mio_aio::Errno::EINVAL))
+        );
+    }
+}
 
-        // Since we cancelled the I/O, we musn't care whether it succeeded.
-        let _ = aiow.as_mut().aio_return();
-        assert!(it.next().is_none());
+mod cancel_stat {
+    use mio_aio::{AioCancelStat, CancelStat};
+
+    #[test]
+    fn from_nix() {
+        assert_eq!(
+            CancelStat::from(AioCancelStat::AioCanceled),
+            CancelStat::AioCanceled
+        );
+        assert_eq!(
+            CancelStat::from(AioCancelStat::AioNotCanceled),
+            CancelStat::AioNotCanceled
+        );
+        assert_eq!(
+            CancelStat::from(AioCancelStat::AioAllDone),
+            CancelStat::AioAllDone
+        );
     }
 
     #[test]
-    fn ok() {
-        let wbuf = String::from("abcdef").into_bytes().into_boxed_slice();
-        let mut f = tempfile().unwrap();
-        let mut rbuf = Vec::new();
+    fn predicates() {
+        assert!(CancelStat::AioCanceled.is_canceled());
+        assert!(!CancelStat::AioCanceled.must_wait());
+        assert!(CancelStat::AioNotCanceled.must_wait());
+        assert!(CancelStat::AioAllDone.is_all_done());
+        assert!(!CancelStat::AioAllDone.must_wait());
+    }
+}
+
+mod checked_offset {
+    use super::*;
+
+    const TOO_BIG: u64 = i64::MAX as u64 + 1;
+
+    #[test]
+    fn in_range() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        assert!(mio_aio::Source::read_at_checked(
+            f.as_fd(),
+            i64::MAX as u64,
+            &mut rbuf,
+            0
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn read_at_checked() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        assert_eq!(
+            mio_aio::Source::read_at_checked(f.as_fd(), TOO_BIG, &mut rbuf, 0)
+                .err(),
+            Some(mio_aio::Errno::EOVERFLOW)
+        );
+    }
+
+    #[test]
+    fn set_offset() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        assert_eq!(
+            aiow.as_mut().set_offset(TOO_BIG),
+            Err(mio_aio::Errno::EOVERFLOW)
+        );
+        assert_eq!(aiow.offset(), 0);
+    }
+
+    #[test]
+    fn writev_at_checked() {
+        let f = tempfile().unwrap();
+        let wbufs = [IoSlice::new(b"abc")];
+        assert_eq!(
+            mio_aio::Source::writev_at_checked(f.as_fd(), u64::MAX, &wbufs, 0)
+                .err(),
+            Some(mio_aio::Errno::EOVERFLOW)
+        );
+    }
+}
+
+mod completion_stream {
+    use super::*;
+
+    /// Every pushed operation should be yielded exactly once
+    #[test]
+    fn all_complete() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut stream = mio_aio::CompletionStream::new(&mut poll);
+
+        for i in 0..8 {
+            let offs = (i * WBUF.len()) as u64;
+            let aiow = mio_aio::Source::write_at(f.as_fd(), offs, WBUF, 0);
+            assert_eq!(stream.push(aiow).unwrap(), i);
+        }
+        assert_eq!(stream.len(), 8);
+
+        let mut seen = [false; 8];
+        for r in &mut stream {
+            let (i, mut aiow) = r.unwrap();
+            assert!(!seen[i]);
+            seen[i] = true;
+            assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+        }
+        assert!(stream.is_empty());
+        assert!(seen.iter().all(|s| *s));
+    }
+}
+
+mod cursor_table {
+    use super::*;
 
+    /// Successive writes should be placed end-to-end, and a subsequent read
+    /// should start where they left off.
+    #[test]
+    fn sequential() {
+        const WBUFS: [&[u8]; 2] = [b"abc", b"defg"];
+        let mut f = tempfile().unwrap();
         let mut poll = Poll::new().unwrap();
         let mut events = Events::with_capacity(1024);
-        {
-            let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
+        let mut cursors = mio_aio::CursorTable::new();
+
+        for wbuf in WBUFS {
+            let mut aiow = cursors.write_at(f.as_fd(), wbuf, 0);
             poll.registry()
                 .register(&mut aiow, UDATA, Interest::AIO)
                 .expect("registration failed");
             let mut aiow = Box::pin(aiow);
-
             aiow.as_mut().submit().unwrap();
-
             poll.poll(&mut events, None).expect("poll failed");
-            let mut it = events.iter();
-            let ev = it.next().unwrap();
-            assert_eq!(ev.token(), UDATA);
-            assert!(ev.is_aio());
-
-            assert!(aiow.as_mut().error().is_ok());
-            assert_eq!(aiow.as_mut().aio_return().unwrap(), wbuf.len());
-            assert!(it.next().is_none());
+            assert_eq!(cursors.aio_return(aiow.as_mut()).unwrap(), wbuf.len());
         }
-        f.rewind().unwrap();
-        let len = f.read_to_end(&mut rbuf).unwrap();
-        assert!(len == wbuf.len());
-        assert!(rbuf == wbuf.deref());
+        assert_eq!(cursors.cursor(f.as_fd()), 7);
+
+        f.seek(std::io::SeekFrom::End(0)).unwrap();
+        f.write_all(b"hij").unwrap();
+        let mut rbuf = vec![0u8; 16];
+        let mut aior = cursors.read_at(f.as_fd(), &mut rbuf, 0);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        // A short read advances the cursor only by the bytes transferred
+        assert_eq!(cursors.aio_return(aior.as_mut()).unwrap(), 3);
+        assert_eq!(cursors.cursor(f.as_fd()), 10);
+        drop(aior);
+        assert_eq!(&rbuf[..3], b"hij");
     }
 }
 
-mod aio_writev {
+mod dyn_source {
+    use std::pin::Pin;
+
+    use mio_aio::DynSource;
+
     use super::*;
 
+    /// Operations of different types can share a Vec
     #[test]
-    fn ok() {
-        let wbuf0 = b"abcde";
-        let wbuf1 = b"fghi";
-        let wbufs = [IoSlice::new(wbuf0), IoSlice::new(wbuf1)];
-        let expected = b"abcdefghi";
+    fn mixed() {
         let mut f = tempfile().unwrap();
-        let mut rbuf = Vec::new();
-
+        f.write_all(b"abcdef").unwrap();
+        let mut rbuf = vec![0; 4];
         let mut poll = Poll::new().unwrap();
         let mut events = Events::with_capacity(1024);
-        {
-            let mut aiow = mio_aio::Source::writev_at(f.as_fd(), 0, &wbufs, 0);
-            poll.registry()
-                .register(&mut aiow, UDATA, Interest::AIO)
-                .expect("registration failed");
-            let mut aiow = Box::pin(aiow);
 
-            aiow.as_mut().submit().unwrap();
+        let mut aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0);
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 6, b"123", 0);
+        poll.registry()
+            .register(&mut aior, Token(0), Interest::AIO)
+            .unwrap();
+        poll.registry()
+            .register(&mut aiow, Token(1), Interest::AIO)
+            .unwrap();
+        let mut ops: Vec<Pin<Box<dyn DynSource>>> =
+            vec![aior.into_dyn(), aiow.into_dyn()];
+        for op in ops.iter_mut() {
+            op.as_mut().submit().unwrap();
+        }
 
+        let mut results = [None; 2];
+        while results.contains(&None) {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                let op = &mut ops[ev.token().0];
+                results[ev.token().0] = Some(op.as_mut().aio_return());
+            }
+        }
+        assert_eq!(results, [Some(Ok(4)), Some(Ok(3))]);
+        drop(ops);
+        assert_eq!(rbuf, b"abcd");
+    }
+}
+
+mod debug {
+    use std::os::unix::io::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn fsync() {
+        let f = tempfile().unwrap();
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        assert_eq!(
+            format!("{aiof:?}"),
+            format!(
+                "Source {{ opcode: Fsync, fd: {}, priority: 0, state: Idle }}",
+                f.as_raw_fd()
+            )
+        );
+    }
+
+    #[test]
+    fn in_progress() {
+        let mut f = tempfile().unwrap();
+        f.write_all(b"abcdef").unwrap();
+        let mut rbuf = vec![0; 4];
+        let mut aior =
+            Box::pin(mio_aio::Source::read_at(f.as_fd(), 2, &mut rbuf, 0));
+        aior.as_mut().submit().unwrap();
+        assert!(format!("{aior:?}").contains("state: InProgress"));
+        aior.as_mut().suspend(None).unwrap();
+        aior.as_mut().aio_return().unwrap();
+    }
+
+    #[test]
+    fn read_at() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        let aior = mio_aio::Source::read_at(f.as_fd(), 42, &mut rbuf, 0);
+        assert_eq!(
+            format!("{aior:?}"),
+            format!(
+                "Source {{ opcode: Read, fd: {}, offset: 42, len: 4, \
+                 priority: 0, state: Idle }}",
+                f.as_raw_fd()
+            )
+        );
+    }
+
+    /// The iovec array needn't outlive construction, so Debug mustn't read it
+    #[test]
+    fn readv_at_temporary_iovecs() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        let aior = mio_aio::Source::readv_at(
+            f.as_fd(),
+            42,
+            &mut [IoSliceMut::new(&mut rbuf)],
+            0,
+        );
+        assert_eq!(
+            format!("{aior:?}"),
+            format!(
+                "Source {{ opcode: Readv, fd: {}, offset: 42, len: 4, \
+                 priority: 0, state: Idle }}",
+                f.as_raw_fd()
+            )
+        );
+        assert_eq!(aior.total_len(), 4);
+    }
+}
+
+mod drop {
+    use super::*;
+
+    /// Dropping an in-progress operation should wait for it to finish
+    #[test]
+    fn in_progress() {
+        let wbuf: Vec<u8> = (0..1 << 24).map(|i| (i % 251) as u8).collect();
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        drop(aiow);
+
+        // The completion event may or may not have been delivered, but
+        // either way the kernel must be done with the buffer by now.
+        poll.poll(&mut events, Some(std::time::Duration::ZERO))
+            .expect("poll failed");
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert!(rbuf.len() <= wbuf.len());
+        assert_eq!(&rbuf[..], &wbuf[..rbuf.len()]);
+    }
+}
+
+mod notification {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use mio_aio::{Errno, SigevNotify, Signal};
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet};
+
+    use super::*;
+
+    static DELIVERED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handle_sigusr2(_: i32) {
+        DELIVERED.store(true, Ordering::Relaxed);
+    }
+
+    /// An unregistered operation may request signal notification instead
+    #[test]
+    fn sigev_signal() {
+        let sa = SigAction::new(
+            SigHandler::Handler(handle_sigusr2),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        // Safe because the handler is async-signal-safe.
+        unsafe { sigaction(Signal::SIGUSR2, &sa) }.unwrap();
+        let f = tempfile().unwrap();
+
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0)
+                .with_notification(SigevNotify::SigevSignal {
+                    signal:   Signal::SIGUSR2,
+                    si_value: 0,
+                });
+        assert!(!aiof.is_registered());
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+        loop {
+            match aiof.suspend(None) {
+                Err(AioError::Return(Errno::EINTR)) => continue,
+                r => break r.unwrap(),
+            }
+        }
+        for _ in 0..100 {
+            if DELIVERED.load(Ordering::Relaxed) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(DELIVERED.load(Ordering::Relaxed));
+        aiof.as_mut().aio_return().unwrap();
+    }
+
+    /// set_notification can't change an in-progress operation
+    #[test]
+    fn set_while_in_progress() {
+        let f = tempfile().unwrap();
+        let mut aiof = Box::pin(mio_aio::Source::fsync(
+            f.as_fd(),
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        aiof.as_mut().submit().unwrap();
+        assert_eq!(
+            aiof.as_mut().set_notification(SigevNotify::SigevNone),
+            Err(Errno::EBUSY)
+        );
+        aiof.suspend(None).unwrap();
+        aiof.as_mut().aio_return().unwrap();
+    }
+}
+
+mod register {
+    use std::io;
+
+    use super::*;
+
+    /// Registering with the wrong interest should fail cleanly
+    #[test]
+    fn wrong_interest() {
+        let f = tempfile().unwrap();
+        let poll = Poll::new().unwrap();
+        let mut aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        let e = poll
+            .registry()
+            .register(&mut aiof, UDATA, Interest::READABLE)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(!aiof.is_registered());
+    }
+
+    /// Reregistering an idle operation should change its token
+    #[test]
+    fn reregister() {
+        const NEW_UDATA: Token = Token(0xdead_beef);
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+        poll.registry()
+            .reregister(&mut aiof, NEW_UDATA, Interest::AIO)
+            .expect("reregistration failed");
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), NEW_UDATA);
+        assert!(ev.is_aio());
+        assert!(it.next().is_none());
+        aiof.as_mut().aio_return().unwrap();
+    }
+
+    /// The kernel has already copied an in-progress operation's token, so
+    /// changing it should fail.
+    #[test]
+    fn reregister_in_progress() {
+        const NEW_UDATA: Token = Token(0xdead_beef);
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+
+        // Safe because registration doesn't move the source
+        let aiof_ref = unsafe { aiof.as_mut().get_unchecked_mut() };
+        let e = poll
+            .registry()
+            .reregister(aiof_ref, NEW_UDATA, Interest::AIO)
+            .unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(mio_aio::Errno::EBUSY as i32));
+        // Reregistering with the same token is harmless
+        let aiof_ref = unsafe { aiof.as_mut().get_unchecked_mut() };
+        poll.registry()
+            .reregister(aiof_ref, UDATA, Interest::AIO)
+            .unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let ev = events.iter().next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        aiof.as_mut().aio_return().unwrap();
+    }
+}
+
+mod accessors {
+    use std::os::unix::io::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn write_at() {
+        let f = tempfile().unwrap();
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 42, b"abcdef", 3);
+        assert_eq!(aiow.fd(), f.as_raw_fd());
+        assert_eq!(aiow.offset(), 42);
+        assert_eq!(aiow.priority(), 3);
+    }
+
+    /// Accessors should work even once the operation is pinned and submitted
+    #[test]
+    fn pinned() {
+        let f = tempfile().unwrap();
+        let mut aiof = Box::pin(mio_aio::Source::fsync(
+            f.as_fd(),
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        aiof.as_mut().submit().unwrap();
+        assert_eq!(aiof.fd(), f.as_raw_fd());
+        assert_eq!(aiof.priority(), 0);
+        aiof.suspend(None).unwrap();
+        aiof.as_mut().aio_return().unwrap();
+    }
+}
+
+#[cfg(feature = "async")]
+mod submit_and_wait {
+    use std::{
+        future::Future,
+        sync::Arc,
+        task::{Context, Poll, Wake},
+        thread::{self, Thread},
+    };
+
+    use super::*;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal executor, so the test doesn't depend on any runtime
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = Box::pin(fut);
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(r) => return r,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn write_at() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0));
+        let r = block_on(aiow.as_mut().submit_and_wait());
+        assert_eq!(r.unwrap(), WBUF.len());
+        assert!(!aiow.is_registered());
+        drop(aiow);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// Many concurrent operations may share the internal reactor
+    #[test]
+    fn concurrent() {
+        let f = tempfile().unwrap();
+        let handles = (0..8)
+            .map(|_| {
+                let f = f.try_clone().unwrap();
+                thread::spawn(move || {
+                    let mut aiof = Box::pin(mio_aio::Source::fsync(
+                        f.as_fd(),
+                        mio_aio::AioFsyncMode::O_SYNC,
+                        0,
+                    ));
+                    block_on(aiof.as_mut().submit_and_wait())
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap().unwrap();
+        }
+    }
+
+    /// write_owned should hand back the very same buffer
+    #[test]
+    fn write_owned() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let buf = Box::<[u8]>::from(WBUF);
+        let ptr = buf.as_ptr();
+
+        let fut = mio_aio::Source::write_owned(f.as_fd(), 0, buf, 0);
+        let (buf, nbytes) = block_on(fut).unwrap();
+        assert_eq!(nbytes, WBUF.len());
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(&buf[..], WBUF);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// Dropping a write_owned future mid-flight must wait for the kernel to
+    /// finish with the buffer before freeing it.
+    #[test]
+    fn write_owned_dropped() {
+        let wbuf: Vec<u8> = (0..1 << 24).map(|i| (i % 251) as u8).collect();
+        let mut f = tempfile().unwrap();
+
+        let mut fut = Box::pin(mio_aio::Source::write_owned(
+            f.as_fd(),
+            0,
+            wbuf.clone().into_boxed_slice(),
+            0,
+        ));
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        // The first poll submits the write.  It may or may not complete.
+        let _ = fut.as_mut().poll(&mut cx);
+        drop(fut);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert!(rbuf.len() <= wbuf.len());
+        assert_eq!(&rbuf[..], &wbuf[..rbuf.len()]);
+    }
+}
+
+mod submit_and_wait_blocking {
+    use super::*;
+
+    #[test]
+    fn read_at() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf = vec![0; 4];
+        let mut aior =
+            Box::pin(mio_aio::Source::read_at(f.as_fd(), 2, &mut rbuf, 0));
+        assert_eq!(aior.as_mut().submit_and_wait_blocking(), Ok(4));
+        assert!(!aior.is_registered());
+        drop(aior);
+        assert_eq!(rbuf, b"cdef");
+    }
+
+    /// Submission errors are returned without waiting
+    #[test]
+    fn submit_error() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        aiow.as_mut().abort_pending().unwrap();
+        assert_eq!(
+            aiow.as_mut().submit_and_wait_blocking(),
+            Err(AioError::Submit(mio_aio::Errno::ECANCELED))
+        );
+    }
+}
+
+mod reset {
+    use super::*;
+
+    /// A registered read may be re-armed and resubmitted
+    #[test]
+    fn read_at() {
+        const WBUF: &[u8] = b"abcdefghijkl";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 4];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf0, 0);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        assert_eq!(aior.as_mut().aio_return().unwrap(), 4);
+
+        aior.as_mut().reset_read_at(8, &mut rbuf1, 0).unwrap();
+        assert_eq!(aior.offset(), 8);
+        aior.as_mut().submit().unwrap();
+        assert_eq!(
+            aior.as_mut().reset_read_at(0, &mut [], 0),
+            Err(mio_aio::Errno::EBUSY)
+        );
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        assert_eq!(aior.as_mut().aio_return().unwrap(), 4);
+        drop(aior);
+
+        assert_eq!(rbuf0, b"abcd");
+        assert_eq!(rbuf1, b"ijkl");
+    }
+
+    /// A registered write may be re-armed and resubmitted
+    #[test]
+    fn write_at() {
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abcd", 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), 4);
+
+        aiow.as_mut().reset_write_at(4, b"efgh", 0).unwrap();
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), 4);
+        drop(aiow);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abcdefgh");
+    }
+}
+
+mod into_result {
+    use super::*;
+
+    /// Once reaped, the operation may be unpinned and its buffer reused
+    #[test]
+    fn ok() {
+        let mut f = tempfile().unwrap();
+        f.write_all(b"abcdef").unwrap();
+        let mut rbuf = vec![0; 4];
+        let mut aior =
+            Box::pin(mio_aio::Source::read_at(f.as_fd(), 1, &mut rbuf, 0));
+        aior.as_mut().submit().unwrap();
+        aior.suspend(None).unwrap();
+        let (nread, aior) = aior.into_result().unwrap();
+        assert_eq!(nread, 4);
+        assert_eq!(aior.state(), mio_aio::State::Idle);
+        drop(aior);
+        assert_eq!(rbuf, b"bcde");
+    }
+
+    #[test]
+    fn not_submitted() {
+        let f = tempfile().unwrap();
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0);
+        assert_eq!(
+            aiow.into_pinned_box().into_result().err(),
+            Some(AioError::Return(mio_aio::Errno::EINVAL))
+        );
+    }
+}
+
+mod length_cap {
+    use super::*;
+
+    #[test]
+    fn read_at_n() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf = vec![0; 16];
+        let mut aior = Box::pin(
+            mio_aio::Source::read_at_n(f.as_fd(), 0, &mut rbuf, 4, 0).unwrap(),
+        );
+        assert_eq!(aior.as_mut().submit_and_wait_blocking(), Ok(4));
+        drop(aior);
+        assert_eq!(&rbuf[..5], b"abcd\0");
+    }
+
+    #[test]
+    fn write_at_n() {
+        let mut f = tempfile().unwrap();
+        let mut aiow = Box::pin(
+            mio_aio::Source::write_at_n(f.as_fd(), 0, b"abcdef", 3, 0).unwrap(),
+        );
+        assert_eq!(aiow.as_mut().submit_and_wait_blocking(), Ok(3));
+        drop(aiow);
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abc");
+    }
+
+    #[test]
+    fn too_long() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        assert_eq!(
+            mio_aio::Source::read_at_n(f.as_fd(), 0, &mut rbuf, 5, 0).err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+        assert_eq!(
+            mio_aio::Source::write_at_n(f.as_fd(), 0, b"abc", 4, 0).err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+    }
+}
+
+mod read_at_uninit {
+    use super::*;
+
+    /// Reading into uninitialized memory gives the same result as reading
+    /// into a zeroed buffer
+    #[test]
+    fn equivalence() {
+        let wbuf: Vec<u8> = (0..65536).map(|i| (i % 251) as u8).collect();
+        let mut f = tempfile().unwrap();
+        f.write_all(&wbuf).unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut zeroed = vec![0u8; wbuf.len()];
+        let mut uninit = Vec::<u8>::with_capacity(wbuf.len());
+        {
+            let mut aior =
+                mio_aio::Source::read_at(f.as_fd(), 0, &mut zeroed, 0);
+            poll.registry()
+                .register(&mut aior, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aior = Box::pin(aior);
+            aior.as_mut().submit().unwrap();
+            poll.poll(&mut events, None).expect("poll failed");
+            assert_eq!(aior.as_mut().aio_return().unwrap(), wbuf.len());
+        }
+        let n = {
+            let mut aior = mio_aio::Source::read_at_uninit(
+                f.as_fd(),
+                0,
+                uninit.spare_capacity_mut(),
+                0,
+            );
+            poll.registry()
+                .register(&mut aior, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aior = Box::pin(aior);
+            aior.as_mut().submit().unwrap();
+            poll.poll(&mut events, None).expect("poll failed");
+            aior.as_mut().aio_return().unwrap()
+        };
+        assert_eq!(n, wbuf.len());
+        // Safe because the kernel initialized the first n bytes
+        unsafe { uninit.set_len(n) };
+        assert_eq!(uninit, zeroed);
+        assert_eq!(uninit, wbuf);
+    }
+}
+
+mod bytes_transferred {
+    use super::*;
+
+    /// After canceling a large write, the partial count should match what
+    /// actually reached the file
+    #[test]
+    fn canceled_write() {
+        let wbuf = vec![0x5au8; 1 << 24];
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        aiow.as_mut().cancel().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+
+        let n = aiow.as_mut().bytes_transferred().unwrap();
+        assert!(n <= wbuf.len());
+        assert_eq!(f.metadata().unwrap().len(), n as u64);
+    }
+
+    #[test]
+    fn fsync() {
+        let f = tempfile().unwrap();
+        let mut aiof = Box::pin(mio_aio::Source::fsync(
+            f.as_fd(),
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        aiof.as_mut().submit().unwrap();
+        aiof.suspend(None).unwrap();
+        assert_eq!(aiof.as_mut().bytes_transferred(), Some(0));
+    }
+}
+
+mod vectored_capped {
+    use super::*;
+
+    const WBUFS: [&[u8]; 3] = [b"abc", b"defg", b"hi"];
+
+    /// Write the capped buffers, and return what landed in the file
+    fn write_capped(max: usize) -> (usize, usize, Vec<u8>) {
+        let mut f = tempfile().unwrap();
+        let iovs = WBUFS.iter().map(|b| IoSlice::new(b)).collect();
+        let mut aiow = Box::pin(mio_aio::Source::writev_at_capped(
+            f.as_fd(),
+            0,
+            iovs,
+            max,
+            0,
+        ));
+        let iovcnt = aiow.iovcnt();
+        let total_len = aiow.total_len();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        // Safe because registration doesn't move the operation
+        let aiow_ref = unsafe { aiow.as_mut().get_unchecked_mut() };
+        poll.registry()
+            .register(aiow_ref, UDATA, Interest::AIO)
+            .expect("registration failed");
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), total_len);
+        drop(aiow);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        (iovcnt, total_len, rbuf)
+    }
+
+    #[test]
+    fn total_len() {
+        let f = tempfile().unwrap();
+        let iovs = WBUFS.iter().map(|b| IoSlice::new(b)).collect::<Vec<_>>();
+        let aiow = mio_aio::Source::writev_at(f.as_fd(), 0, &iovs, 0);
+        assert_eq!(aiow.total_len(), 9);
+
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 5];
+        let mut iovs =
+            [IoSliceMut::new(&mut rbuf0), IoSliceMut::new(&mut rbuf1)];
+        let aior = mio_aio::Source::readv_at(f.as_fd(), 0, &mut iovs, 0);
+        assert_eq!(aior.total_len(), 9);
+    }
+
+    #[test]
+    fn exact() {
+        assert_eq!(write_capped(9), (3, 9, b"abcdefghi".to_vec()));
+    }
+
+    #[test]
+    fn over() {
+        assert_eq!(write_capped(100), (3, 9, b"abcdefghi".to_vec()));
+    }
+
+    /// A cap in the middle of a buffer should shorten it, and drop the rest
+    #[test]
+    fn under() {
+        assert_eq!(write_capped(5), (2, 5, b"abcde".to_vec()));
+    }
+
+    /// A cap at a buffer boundary should drop the following buffers
+    #[test]
+    fn under_at_boundary() {
+        assert_eq!(write_capped(3), (1, 3, b"abc".to_vec()));
+    }
+
+    #[test]
+    fn readv() {
+        const WBUF: &[u8] = b"abcdefghi";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 5];
+        let iovs =
+            vec![IoSliceMut::new(&mut rbuf0), IoSliceMut::new(&mut rbuf1)];
+        let mut aior = Box::pin(mio_aio::Source::readv_at_capped(
+            f.as_fd(),
+            0,
+            iovs,
+            6,
+            0,
+        ));
+        assert_eq!(aior.iovcnt(), 2);
+        assert_eq!(aior.total_len(), 6);
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        // Safe because registration doesn't move the operation
+        let aior_ref = unsafe { aior.as_mut().get_unchecked_mut() };
+        poll.registry()
+            .register(aior_ref, UDATA, Interest::AIO)
+            .expect("registration failed");
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aior.as_mut().aio_return().unwrap(), 6);
+        drop(aior);
+        assert_eq!(rbuf0, b"abcd");
+        assert_eq!(rbuf1, b"ef\0\0\0");
+    }
+}
+
+mod vectored_owned {
+    use super::*;
+
+    /// Build a vectored write whose iovec array doesn't outlive this function
+    fn make_writev<'a>(
+        f: &'a std::fs::File,
+        bufs: &'a [Vec<u8>],
+    ) -> mio_aio::WritevAtOwned<'a> {
+        let iovs = bufs.iter().map(|b| IoSlice::new(b)).collect::<Vec<_>>();
+        mio_aio::Source::writev_at_owned(f.as_fd(), 0, iovs, 0)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let wbufs = vec![b"abc".to_vec(), b"defg".to_vec(), b"hi".to_vec()];
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = make_writev(&f, &wbufs);
+        assert_eq!(aiow.iovcnt(), 3);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), 9);
+
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 5];
+        let iovs =
+            vec![IoSliceMut::new(&mut rbuf0), IoSliceMut::new(&mut rbuf1)];
+        let mut aior = mio_aio::Source::readv_at_owned(f.as_fd(), 0, iovs, 0);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aior.as_mut().aio_return().unwrap(), 9);
+        drop(aior);
+
+        assert_eq!(rbuf0, b"abcd");
+        assert_eq!(rbuf1, b"efghi");
+    }
+
+    #[test]
+    fn readv_into() {
+        let mut f = tempfile().unwrap();
+        f.write_all(b"abcdef123456").unwrap();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut rbufs = vec![vec![0; 4], vec![0; 2]];
+        let mut aior = mio_aio::Source::readv_into(f.as_fd(), 2, &mut rbufs, 0);
+        assert_eq!(aior.iovcnt(), 2);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aior.as_mut().aio_return().unwrap(), 6);
+        drop(aior);
+
+        assert_eq!(rbufs[0], b"cdef");
+        assert_eq!(rbufs[1], b"12");
+    }
+}
+
+#[cfg(feature = "bytes")]
+mod bytes {
+    use ::bytes::{Bytes, BytesMut};
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let wbuf = Bytes::from_static(b"abcdef");
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        // The caller may keep its own reference to the buffer
+        let mut aiow =
+            mio_aio::Source::write_bytes(f.as_fd(), 0, wbuf.clone(), 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), wbuf.len());
+
+        // Reads append to whatever the buffer already holds
+        let mut rbuf = BytesMut::with_capacity(16);
+        rbuf.extend_from_slice(b"xy");
+        let mut aior = mio_aio::Source::read_bytes(f.as_fd(), 0, rbuf, 0);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let rbuf = aior.as_mut().aio_return().unwrap();
+        assert_eq!(&rbuf[..], b"xyabcdef");
+    }
+}
+
+mod fdatasync {
+    use super::*;
+
+    #[test]
+    fn ok() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        f.write_all(WBUF).unwrap();
+        let mut aiof = mio_aio::Source::fdatasync(f.as_fd(), 0);
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        aiof.as_mut().aio_return().unwrap();
+        drop(aiof);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+}
+
+mod source_set {
+    use super::*;
+
+    /// A set may hold operations of different types
+    #[test]
+    fn mixed() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf = vec![0; 3];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut set = mio_aio::SourceSet::new();
+
+        let aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0);
+        let rtok = set.push(poll.registry(), aior).unwrap();
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 6, WBUF, 0);
+        let wtok = set.push(poll.registry(), aiow).unwrap();
+        let aiowf = mio_aio::Source::write_then_fsync(
+            f.as_fd(),
+            12,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        );
+        let wftok = set.push(poll.registry(), aiowf).unwrap();
+        assert_eq!(set.len(), 3);
+
+        let mut results = Vec::new();
+        while !set.is_empty() {
+            poll.poll(&mut events, None).expect("poll failed");
+            results.extend(set.drain_completed(&events));
+        }
+        results.sort_by_key(|(tok, _)| *tok);
+        let mut expected = vec![
+            (rtok, Ok(3)),
+            (wtok, Ok(WBUF.len())),
+            (wftok, Ok(WBUF.len())),
+        ];
+        expected.sort_by_key(|(tok, _)| *tok);
+        assert_eq!(results, expected);
+        drop(set);
+        assert_eq!(rbuf, b"abc");
+    }
+
+    /// Tokens of reaped operations are reused
+    #[test]
+    fn reuse() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut set = mio_aio::SourceSet::new();
+
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0);
+        let tok = set.push(poll.registry(), aiow).unwrap();
+        while !set.is_empty() {
+            poll.poll(&mut events, None).expect("poll failed");
+            set.drain_completed(&events).for_each(drop);
+        }
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 3, b"def", 0);
+        assert_eq!(set.push(poll.registry(), aiow).unwrap(), tok);
+    }
+}
+
+mod aio_priority {
+    use mio_aio::{AioPriority, Errno};
+
+    use super::*;
+
+    #[test]
+    fn new() {
+        assert_eq!(AioPriority::new(0).map(AioPriority::get), Ok(0));
+        assert_eq!(AioPriority::new(-1), Err(Errno::EINVAL));
+        assert_eq!(AioPriority::new(i32::MAX), Err(Errno::EINVAL));
+    }
+
+    #[test]
+    fn from_i32() {
+        assert_eq!(AioPriority::default().get(), 0);
+        assert_eq!(AioPriority::from(-5).get(), 0);
+        assert_eq!(AioPriority::from(3).get(), 3);
+    }
+
+    /// Constructors accept either an AioPriority or a bare i32
+    #[test]
+    fn constructor() {
+        let f = tempfile().unwrap();
+        let prio = AioPriority::new(0).unwrap();
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", prio);
+        assert_eq!(aiow.priority(), 0);
+    }
+}
+
+mod cancel_after {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// The timer fires on the operation's own token
+    #[test]
+    fn expires() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        aiow.as_mut().cancel_after(Duration::ZERO).unwrap();
+
+        let mut expired = false;
+        while !expired {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert_eq!(ev.token(), UDATA);
+                expired |= !ev.is_aio();
+            }
+        }
+        if aiow.as_mut().error()
+            == Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        {
+            aiow.as_mut().cancel().unwrap();
+        }
+        aiow.suspend(None).unwrap();
+        match aiow.as_mut().aio_return() {
+            Ok(n) => assert_eq!(n, WBUF.len()),
+            Err(e) => {
+                assert_eq!(e, AioError::Return(mio_aio::Errno::ECANCELED))
+            }
+        }
+    }
+
+    /// Deregistering the operation disarms its timer
+    #[test]
+    fn deregister() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut()
+            .cancel_after(Duration::from_millis(10))
+            .unwrap();
+        // Safe because deregistration doesn't move the operation.
+        let aiow_mut = unsafe { aiow.as_mut().get_unchecked_mut() };
+        poll.registry().deregister(aiow_mut).unwrap();
+
+        poll.poll(&mut events, Some(Duration::from_millis(100)))
+            .expect("poll failed");
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn unregistered() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        assert_eq!(
+            aiow.as_mut().cancel_after(Duration::from_secs(1)),
+            Err(AioError::Register(mio_aio::Errno::EINVAL))
+        );
+    }
+}
+
+mod aio_available {
+    /// The test suite can't run at all without AIO
+    #[test]
+    fn available() {
+        assert!(mio_aio::aio_available());
+    }
+}
+
+mod aio_error {
+    use mio_aio::Errno;
+
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let e = AioError::Submit(Errno::EAGAIN);
+        assert_eq!(e.errno(), Errno::EAGAIN);
+        assert_eq!(Errno::from(e), Errno::EAGAIN);
+        let ioe = std::io::Error::from(e);
+        assert_eq!(ioe.raw_os_error(), Some(Errno::EAGAIN as i32));
+    }
+
+    /// A bare Errno doesn't say which phase failed, so it's a Return error
+    #[test]
+    fn from_errno() {
+        assert_eq!(AioError::from(Errno::EIO), AioError::Return(Errno::EIO));
+    }
+
+    #[test]
+    fn display() {
+        let e = AioError::Cancel(Errno::EINVAL);
+        assert!(e.to_string().starts_with("failed to cancel AIO operation"));
+    }
+
+    /// Misuse of different phases is distinguishable, even with the same
+    /// errno.
+    #[test]
+    fn phase() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        assert_eq!(
+            aiow.as_mut().cancel(),
+            Err(AioError::Cancel(Errno::EINVAL))
+        );
+        assert_eq!(
+            aiow.as_mut().aio_return(),
+            Err(AioError::Return(Errno::EINVAL))
+        );
+    }
+}
+
+mod errno_ext {
+    use mio_aio::{Errno, ErrnoExt};
+
+    #[test]
+    fn is_transient() {
+        assert!(Errno::EAGAIN.is_transient());
+        assert!(Errno::EINTR.is_transient());
+        assert!(!Errno::EIO.is_transient());
+        assert!(!Errno::EINVAL.is_transient());
+    }
+}
+
+mod file_too_large {
+    use super::*;
+
+    #[test]
+    fn context() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let aiow = mio_aio::Source::write_at(f.as_fd(), 10, WBUF, 0);
+        let e = aiow.file_too_large();
+        assert_eq!(e.offset, 10);
+        assert_eq!(e.len, WBUF.len());
+        assert!(e.to_string().starts_with("write of 6 bytes at offset 10 "));
+    }
+
+    #[test]
+    fn within_limit() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow =
+            mio_aio::WriteAt::write_at_within_limit(f.as_fd(), 0, WBUF, 0)
+                .unwrap();
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+}
+
+mod from_inner {
+    use nix::sys::{aio::AioFsync, signal::SigevNotify};
+
+    use super::*;
+
+    #[test]
+    fn ok() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let inner = AioFsync::new(
+            f.as_fd(),
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+            SigevNotify::SigevNone,
+        );
+        let mut aiof = unsafe { mio_aio::Source::from_inner(inner) };
+        poll.registry()
+            .register(&mut aiof, UDATA, Interest::AIO)
+            .expect("registration failed");
+
+        let mut aiof = Box::pin(aiof);
+        aiof.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_aio());
+        aiof.as_mut().aio_return().unwrap();
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn into_inner() {
+        use nix::sys::aio::Aio;
+
+        let f = tempfile().unwrap();
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 7);
+        let inner = aiof.into_inner();
+        assert_eq!(inner.priority(), 7);
+        assert!(!inner.in_progress());
+    }
+}
+
+mod fadvise {
+    use std::os::unix::net::UnixStream;
+
+    use mio_aio::Advice;
+
+    use super::*;
+
+    #[test]
+    fn ok() {
+        let mut f = tempfile().unwrap();
+        f.write_all(b"abcdef123456").unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut fa =
+            mio_aio::Source::fadvise(f.as_fd(), 0, 0, Advice::WillNeed);
+        poll.registry()
+            .register(&mut fa, UDATA, Interest::READABLE)
+            .expect("registration failed");
+        let mut fa = Box::pin(fa);
+        fa.as_mut().submit().unwrap();
+        assert!(fa.in_progress());
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_readable());
+        assert_eq!(fa.as_mut().aio_return(), Ok(()));
+        assert!(!fa.in_progress());
+    }
+
+    /// Errors from posix_fadvise are reported at completion
+    #[test]
+    fn espipe() {
+        let (tx, _rx) = UnixStream::pair().unwrap();
+        let mut fa = Box::pin(mio_aio::Source::fadvise(
+            tx.as_fd(),
+            0,
+            0,
+            Advice::DontNeed,
+        ));
+        fa.as_mut().submit().unwrap();
+        assert_eq!(
+            fa.as_mut().error(),
+            Err(AioError::Return(mio_aio::Errno::ESPIPE))
+        );
+        assert_eq!(
+            fa.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::ESPIPE))
+        );
+    }
+}
+
+mod fsync_group {
+    use mio_aio::{AioFsyncMode, FsyncGroup};
+
+    use super::*;
+
+    /// Each file is synced with its own mode, and results are per-file
+    #[test]
+    fn mixed_modes() {
+        let f0 = tempfile().unwrap();
+        let f1 = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut group = FsyncGroup::new(
+            &[
+                (f0.as_fd(), AioFsyncMode::O_SYNC),
+                (f1.as_fd(), AioFsyncMode::O_DSYNC),
+            ],
+            0,
+        );
+        assert_eq!(group.len(), 2);
+        poll.registry()
+            .register(&mut group, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut group = Box::pin(group);
+        group.as_mut().submit().unwrap();
+
+        let results = loop {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert_eq!(ev.token(), UDATA);
+                assert!(ev.is_aio());
+            }
+            match group.as_mut().aio_return() {
+                Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => continue,
+                r => break r.unwrap(),
+            }
+        };
+        assert_eq!(results, vec![Ok(()), Ok(())]);
+        assert!(!group.in_progress());
+    }
+
+    /// is_complete becomes true only once every fsync has finished
+    #[test]
+    fn is_complete() {
+        let f0 = tempfile().unwrap();
+        let f1 = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut group = FsyncGroup::new(
+            &[
+                (f0.as_fd(), AioFsyncMode::O_SYNC),
+                (f1.as_fd(), AioFsyncMode::O_SYNC),
+            ],
+            0,
+        );
+        assert!(!group.is_complete());
+        poll.registry()
+            .register(&mut group, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut group = Box::pin(group);
+        group.as_mut().submit().unwrap();
+
+        let mut nevents = 0;
+        while !group.is_complete() {
+            poll.poll(&mut events, None).expect("poll failed");
+            nevents += events.iter().count();
+        }
+        assert!(nevents >= 1);
+        assert_eq!(group.as_mut().aio_return(), Ok(vec![Ok(()), Ok(())]));
+        assert!(!group.is_complete());
+    }
+
+    /// An empty group never signals readiness
+    #[test]
+    fn empty() {
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut group = FsyncGroup::new(&[], 0);
+        assert!(group.is_empty());
+        poll.registry()
+            .register(&mut group, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut group = Box::pin(group);
+        group.as_mut().submit().unwrap();
+        assert!(!group.in_progress());
+        poll.poll(&mut events, Some(std::time::Duration::from_millis(10)))
+            .expect("poll failed");
+        assert!(events.is_empty());
+        assert_eq!(group.as_mut().aio_return(), Ok(vec![]));
+    }
+}
+
+mod is_registered {
+    use super::*;
+
+    #[test]
+    fn register_and_deregister() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let poll = Poll::new().unwrap();
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        assert!(!aiow.is_registered());
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        assert!(aiow.is_registered());
+        poll.registry()
+            .deregister(&mut aiow)
+            .expect("deregistration failed");
+        assert!(!aiow.is_registered());
+    }
+}
+
+mod as_fd {
+    use std::os::unix::io::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn read_at() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0; 4];
+        let aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0);
+        assert_eq!(aior.as_raw_fd(), f.as_raw_fd());
+        assert_eq!(aior.as_fd().as_raw_fd(), f.as_raw_fd());
+    }
+}
+
+mod aio_readv {
+    use super::*;
+
+    #[test]
+    fn ok() {
+        const INITIAL: &[u8] = b"abcdef123456";
+        let mut rbuf0 = vec![0; 4];
+        let mut rbuf1 = vec![0; 2];
+        let mut rbufs =
+            [IoSliceMut::new(&mut rbuf0), IoSliceMut::new(&mut rbuf1)];
+        const EXPECT0: &[u8] = b"cdef";
+        const EXPECT1: &[u8] = b"12";
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aior = mio_aio::Source::readv_at(
+                f.as_fd(),
+                2, //offset
+                &mut rbufs,
+                0, //priority
+            );
+            poll.registry()
+                .register(&mut aior, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aior = Box::pin(aior);
+
+            aior.as_mut().submit().unwrap();
+
+            poll.poll(&mut events, None).expect("poll failed");
+            let mut it = events.iter();
+            let ev = it.next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert!(ev.is_aio());
+
+            assert!(aior.as_mut().error().is_ok());
+            assert_eq!(
+                aior.as_mut().aio_return().unwrap(),
+                (EXPECT0.len() + EXPECT1.len())
+            );
+            assert!(it.next().is_none());
+        }
+        assert!(rbuf0 == EXPECT0);
+        assert!(rbuf1 == EXPECT1);
+    }
+}
+
+mod aio_write {
+    use super::*;
+
+    /// A complete write needs no continuation
+    #[test]
+    fn continuation_complete() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::WriteAt::write_at(f.as_fd(), 0, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        assert!(aiow.as_mut().continuation().is_none());
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+        assert!(aiow.as_mut().continuation().is_none());
+    }
+
+    /// A write to a socket with no reader will be short.  Its continuations
+    /// should write the rest.
+    #[test]
+    fn continuation_short() {
+        use std::os::unix::net::UnixStream;
+
+        let wbuf: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        let (tx, mut rx) = UnixStream::pair().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::WriteAt::write_at(tx.as_fd(), 0, &wbuf, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut written = aiow.as_mut().aio_return().unwrap();
+        assert!(written < wbuf.len());
+
+        let reader = std::thread::spawn(move || {
+            let mut rbuf = Vec::new();
+            rx.read_to_end(&mut rbuf).unwrap();
+            rbuf
+        });
+        while let Some(mut cont) = aiow.as_mut().continuation() {
+            assert_eq!(cont.offset(), written as u64);
+            poll.registry()
+                .register(&mut cont, UDATA, Interest::AIO)
+                .expect("registration failed");
+            aiow = Box::pin(cont);
+            aiow.as_mut().submit().unwrap();
+            poll.poll(&mut events, None).expect("poll failed");
+            written += aiow.as_mut().aio_return().unwrap();
+        }
+        assert_eq!(written, wbuf.len());
+        drop(aiow);
+        drop(tx);
+        assert_eq!(reader.join().unwrap(), wbuf);
+    }
+
+    /// Concurrent appends to an O_APPEND file must not interleave
+    #[test]
+    fn append() {
+        const NRECS: usize = 8;
+        const RECSIZE: usize = 4096;
+        let tf = tempfile::NamedTempFile::new().unwrap();
+        let f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(tf.path())
+            .unwrap();
+        let wbufs = (0..NRECS)
+            .map(|i| vec![i as u8; RECSIZE])
+            .collect::<Vec<_>>();
+        let mut rbuf = Vec::new();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aiows = wbufs
+                .iter()
+                .enumerate()
+                .map(|(i, wbuf)| {
+                    // The offset should be ignored
+                    let mut aiow =
+                        mio_aio::Source::write_at(f.as_fd(), 0, wbuf, 0);
+                    poll.registry()
+                        .register(&mut aiow, Token(i), Interest::AIO)
+                        .expect("registration failed");
+                    Box::pin(aiow)
+                })
+                .collect::<Vec<_>>();
+            for aiow in aiows.iter_mut() {
+                aiow.as_mut().submit().unwrap();
+            }
+            let mut remaining = NRECS;
+            while remaining > 0 {
+                poll.poll(&mut events, None).expect("poll failed");
+                for ev in events.iter() {
+                    let aiow = &mut aiows[usize::from(ev.token())];
+                    assert_eq!(aiow.as_mut().aio_return().unwrap(), RECSIZE);
+                    remaining -= 1;
+                }
+            }
+        }
+        let mut f = tf.as_file();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf.len(), NRECS * RECSIZE);
+        let mut seen = rbuf
+            .chunks(RECSIZE)
+            .map(|rec| {
+                assert!(rec.iter().all(|b| *b == rec[0]), "torn record");
+                rec[0]
+            })
+            .collect::<Vec<_>>();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..NRECS as u8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cancel() {
+        let wbuf = String::from("abcdef").into_bytes().into_boxed_slice();
+        let f = tempfile().unwrap();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+
+        aiow.as_mut().submit().unwrap();
+        aiow.as_mut().cancel().unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_aio());
+
+        // Since we cancelled the I/O, we musn't care whether it succeeded.
+        let _ = aiow.as_mut().aio_return();
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn ok() {
+        let wbuf = String::from("abcdef").into_bytes().into_boxed_slice();
+        let mut f = tempfile().unwrap();
+        let mut rbuf = Vec::new();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, &wbuf, 0);
+            poll.registry()
+                .register(&mut aiow, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aiow = Box::pin(aiow);
+
+            aiow.as_mut().submit().unwrap();
+
+            poll.poll(&mut events, None).expect("poll failed");
+            let mut it = events.iter();
+            let ev = it.next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert!(ev.is_aio());
+
+            assert!(aiow.as_mut().error().is_ok());
+            assert_eq!(aiow.as_mut().aio_return().unwrap(), wbuf.len());
+            assert!(it.next().is_none());
+        }
+        f.rewind().unwrap();
+        let len = f.read_to_end(&mut rbuf).unwrap();
+        assert!(len == wbuf.len());
+        assert!(rbuf == wbuf.deref());
+    }
+}
+
+mod aio_writev {
+    use super::*;
+
+    #[test]
+    fn ok() {
+        let wbuf0 = b"abcde";
+        let wbuf1 = b"fghi";
+        let wbufs = [IoSlice::new(wbuf0), IoSlice::new(wbuf1)];
+        let expected = b"abcdefghi";
+        let mut f = tempfile().unwrap();
+        let mut rbuf = Vec::new();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aiow = mio_aio::Source::writev_at(f.as_fd(), 0, &wbufs, 0);
+            poll.registry()
+                .register(&mut aiow, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aiow = Box::pin(aiow);
+
+            aiow.as_mut().submit().unwrap();
+
+            poll.poll(&mut events, None).expect("poll failed");
+            let mut it = events.iter();
+            let ev = it.next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert!(ev.is_aio());
+
+            assert!(aiow.as_mut().error().is_ok());
+            assert_eq!(aiow.as_mut().aio_return().unwrap(), expected.len());
+            assert!(it.next().is_none());
+        }
+        f.rewind().unwrap();
+        let len = f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(len, expected.len());
+        assert_eq!(expected, &rbuf[..]);
+    }
+}
+
+mod lio_cb {
+    use std::{os::unix::net::UnixStream, thread, time::Duration};
+
+    use mio_aio::{LioCbBuilder, LioError, LioOutcome};
+
+    use super::*;
+
+    /// Mix reads and writes in a single batch
+    #[test]
+    fn ok() {
+        const INITIAL: &[u8] = b"abcdef123456";
+        const WBUF: &[u8] = b"CDEF";
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+        let mut rbuf0 = vec![0u8; 4];
+        let mut rbuf1 = vec![0u8; 4];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut liocb = LioCbBuilder::with_capacity(3)
+                .read_at(f.as_fd(), 0, &mut rbuf0, 0)
+                .write_at(f.as_fd(), 2, WBUF, 0)
+                .read_at(f.as_fd(), 8, &mut rbuf1, 0)
+                .finish();
+            assert_eq!(liocb.len(), 3);
+            poll.registry()
+                .register(&mut liocb, UDATA, Interest::LIO)
+                .expect("registration failed");
+            let mut liocb = Box::pin(liocb);
+            liocb.as_mut().submit().unwrap();
+            assert!(liocb.in_progress());
+
+            poll.poll(&mut events, None).expect("poll failed");
+            let mut it = events.iter();
+            let ev = it.next().unwrap();
+            assert_eq!(ev.token(), UDATA);
+            assert!(ev.is_lio());
+            assert!(it.next().is_none());
+
+            let results = liocb.as_mut().into_results().unwrap();
+            assert_eq!(results, vec![Ok(4), Ok(4), Ok(4)]);
+            assert!(!liocb.in_progress());
+        }
+        assert_eq!(&rbuf1[..], b"3456");
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(&rbuf[..], b"abCDEF123456");
+    }
+
+    /// A batch may be registered with both AIO and LIO interest, but its
+    /// event is always an LIO event
+    #[test]
+    fn combined_interest() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut liocb = LioCbBuilder::with_capacity(1)
+            .write_at(f.as_fd(), 0, WBUF, 0)
+            .finish();
+        poll.registry()
+            .register(&mut liocb, UDATA, Interest::AIO | Interest::LIO)
+            .expect("registration failed");
+        let mut liocb = Box::pin(liocb);
+        liocb.as_mut().submit().unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let ev = events.iter().next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_lio());
+        assert!(!ev.is_aio());
+        assert_eq!(liocb.as_mut().into_results(), Ok(vec![Ok(WBUF.len())]));
+    }
+
+    /// Flush a batch synchronously, without a reactor
+    #[test]
+    fn submit_wait() {
+        let mut f = tempfile().unwrap();
+        let mut liocb = Box::pin(
+            LioCbBuilder::with_capacity(2)
+                .write_at(f.as_fd(), 0, b"abc", 0)
+                .write_at(f.as_fd(), 3, b"defg", 0)
+                .finish(),
+        );
+        assert_eq!(liocb.as_mut().submit_wait(), Ok(vec![Ok(3), Ok(4)]));
+        assert!(!liocb.in_progress());
+        drop(liocb);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abcdefg");
+    }
+
+    /// One operation's failure shouldn't hide the others' results
+    #[test]
+    fn submit_wait_partial_failure() {
+        let f = tempfile().unwrap();
+        let mut liocb = Box::pin(
+            LioCbBuilder::with_capacity(2)
+                .write_at(f.as_fd(), 0, b"abc", 0)
+                // A negative offset
+                .write_at(f.as_fd(), u64::MAX, b"def", 0)
+                .finish(),
+        );
+        assert_eq!(
+            liocb.as_mut().submit_wait(),
+            Ok(vec![Ok(3), Err(AioError::Submit(mio_aio::Errno::EINVAL))])
+        );
+    }
+
+    /// A batch can't be registered without LIO interest
+    #[test]
+    fn wrong_interest() {
+        let f = tempfile().unwrap();
+        let poll = Poll::new().unwrap();
+        let mut liocb = LioCbBuilder::with_capacity(1)
+            .write_at(f.as_fd(), 0, b"abc", 0)
+            .finish();
+        let e = poll
+            .registry()
+            .register(&mut liocb, UDATA, Interest::AIO)
+            .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    /// Dropping an in-progress batch should cancel and reap its operations
+    #[test]
+    fn drop_in_progress() {
+        // Reads from a socket won't complete until data arrives, so they can
+        // be canceled.
+        let (rd, _wr) = UnixStream::pair().unwrap();
+        let mut rbufs = [[0u8; 1]; 2];
+        let mut builder = LioCbBuilder::with_capacity(2);
+        for rbuf in rbufs.iter_mut() {
+            builder = builder.read_at(rd.as_fd(), 0, rbuf, 0);
+        }
+        let mut liocb = Box::pin(builder.finish());
+        liocb.as_mut().submit().unwrap();
+        assert!(liocb.in_progress());
+        drop(liocb);
+    }
+
+    /// into_results must not reap anything while an operation is in flight
+    #[test]
+    fn into_results_in_progress() {
+        let f = tempfile().unwrap();
+        // A read from a socket won't complete until data arrives.
+        let (rd, mut wr) = UnixStream::pair().unwrap();
+        let mut rbuf = [0u8; 1];
+        let mut liocb = Box::pin(
+            LioCbBuilder::with_capacity(2)
+                .write_at(f.as_fd(), 0, b"abc", 0)
+                .read_at(rd.as_fd(), 0, &mut rbuf, 0)
+                .finish(),
+        );
+        liocb.as_mut().submit().unwrap();
+        assert_eq!(
+            liocb.as_mut().into_results(),
+            Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        );
+        assert!(liocb.in_progress());
+
+        wr.write_all(b"x").unwrap();
+        while !liocb.is_complete() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(liocb.as_mut().into_results(), Ok(vec![Ok(3), Ok(1)]));
+        drop(liocb);
+        assert_eq!(rbuf, *b"x");
+    }
+
+    /// drain_partial should report finished, canceled, and never-initiated
+    /// operations
+    #[test]
+    fn drain_partial() {
+        let f = tempfile().unwrap();
+        // A read from a socket won't complete until data arrives, so it can
+        // be canceled.
+        let (rd, _wr) = UnixStream::pair().unwrap();
+        let mut rbuf = [0u8; 1];
+        let mut liocb = Box::pin(
+            LioCbBuilder::with_capacity(2)
+                .write_at(f.as_fd(), 0, b"abc", 0)
+                .read_at(rd.as_fd(), 0, &mut rbuf, 0)
+                .finish(),
+        );
+        assert_eq!(
+            liocb.as_mut().drain_partial(),
+            vec![LioOutcome::Pending, LioOutcome::Pending]
+        );
+
+        liocb.as_mut().submit().unwrap();
+        while liocb.remaining() > 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            liocb.as_mut().drain_partial(),
+            vec![LioOutcome::Completed(Ok(3)), LioOutcome::Canceled]
+        );
+        assert!(!liocb.in_progress());
+    }
+
+    /// Dropping a finished batch before collecting its results should reap it
+    #[test]
+    fn drop_complete() {
+        let f = tempfile().unwrap();
+        let mut liocb = Box::pin(
+            LioCbBuilder::with_capacity(1)
+                .write_at(f.as_fd(), 0, b"abc", 0)
+                .finish(),
+        );
+        liocb.as_mut().submit().unwrap();
+        while !liocb.is_complete() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        drop(liocb);
+    }
+
+    /// remaining() should count down as each operation completes
+    #[test]
+    fn remaining() {
+        const NOPS: usize = 3;
+        // Reads from a socket won't complete until data arrives.
+        let (rd, mut wr) = UnixStream::pair().unwrap();
+        let mut rbufs = [[0u8; 1]; NOPS];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut builder = LioCbBuilder::with_capacity(NOPS);
+        for rbuf in rbufs.iter_mut() {
+            builder = builder.read_at(rd.as_fd(), 0, rbuf, 0);
+        }
+        let mut liocb = builder.finish();
+        assert_eq!(liocb.remaining(), 0);
+        poll.registry()
+            .register(&mut liocb, UDATA, Interest::LIO)
+            .expect("registration failed");
+        let mut liocb = Box::pin(liocb);
+        liocb.as_mut().submit().unwrap();
+        assert_eq!(liocb.remaining(), NOPS);
+        assert!(!liocb.is_complete());
+
+        for i in (0..NOPS).rev() {
+            wr.write_all(b"x").unwrap();
+            while liocb.remaining() > i {
+                thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(liocb.remaining(), i);
+        }
+        assert!(liocb.is_complete());
+
+        poll.poll(&mut events, None).expect("poll failed");
+        assert!(events.iter().next().unwrap().is_lio());
+        let results = liocb.as_mut().into_results().unwrap();
+        assert_eq!(results, vec![Ok(1); NOPS]);
+        assert_eq!(liocb.remaining(), 0);
+    }
+
+    /// Submit more operations than the kernel's per-process queue limit, so
+    /// that lio_listio can't initiate all of them at once.
+    #[test]
+    fn incomplete() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let limits = mio_aio::aio_limits();
+        let alm = limits.listio_max;
+        let maqpp = limits.max_queue_per_proc;
+        // Enough batches to overflow the queue, even if other tests are
+        // running concurrently.
+        let num_listios = (maqpp + alm - 1) / alm * 2;
+        let ops_per_listio = alm;
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut liocbs = (0..num_listios)
+            .map(|i| {
+                let mut builder = LioCbBuilder::with_capacity(ops_per_listio);
+                for j in 0..ops_per_listio {
+                    let offs = ((i * ops_per_listio + j) * WBUF.len()) as u64;
+                    builder = builder.write_at(f.as_fd(), offs, WBUF, 0);
+                }
+                let mut liocb = builder.finish();
+                assert_eq!(liocb.len(), ops_per_listio);
+                poll.registry()
+                    .register(&mut liocb, Token(i), Interest::LIO)
+                    .expect("registration failed");
+                Box::pin(liocb)
+            })
+            .collect::<Vec<_>>();
+
+        let mut saw_incomplete = false;
+        let mut nwritten = 0;
+        // Batches with operations that haven't yet been initiated
+        let mut needs_resubmit = vec![true; num_listios];
+        // Batches for which we expect an event
+        let mut waiting = vec![false; num_listios];
+        loop {
+            for (i, liocb) in liocbs.iter_mut().enumerate() {
+                if !needs_resubmit[i] || waiting[i] {
+                    continue;
+                }
+                match liocb.as_mut().resubmit() {
+                    Ok(()) => {
+                        needs_resubmit[i] = false;
+                        waiting[i] = true;
+                    }
+                    Err(LioError::EINCOMPLETE) => {
+                        saw_incomplete = true;
+                        waiting[i] = true;
+                    }
+                    Err(LioError::Errno(mio_aio::Errno::EAGAIN)) => {
+                        saw_incomplete = true;
+                    }
+                    Err(e) => panic!("lio_listio failed: {:?}", e),
+                }
+            }
+            if !waiting.contains(&true) {
+                if needs_resubmit.contains(&true) {
+                    // The queue is full of other processes' operations
+                    continue;
+                }
+                break;
+            }
+
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert!(ev.is_lio());
+                let i = usize::from(ev.token());
+                waiting[i] = false;
+                if needs_resubmit[i] {
+                    // Only operations awaiting resubmission remain
+                    assert!(liocbs[i].remaining() > 0);
+                } else {
+                    assert!(liocbs[i].is_complete());
+                    let results = liocbs[i].as_mut().into_results().unwrap();
+                    for r in results {
+                        assert_eq!(r, Ok(WBUF.len()));
+                        nwritten += 1;
+                    }
+                }
+            }
+        }
+        assert!(saw_incomplete);
+        assert_eq!(nwritten, num_listios * ops_per_listio);
+    }
+    /// A batch larger than the system's listio limit should be split into
+    /// several lio_listio calls, but still complete as one batch.
+    #[test]
+    fn chunked() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let nops = mio_aio::aio_limits().listio_max + 1;
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut builder = LioCbBuilder::with_capacity(nops);
+        for i in 0..nops {
+            let offs = (i * WBUF.len()) as u64;
+            builder = builder.write_at(f.as_fd(), offs, WBUF, 0);
+        }
+        let mut liocb = builder.finish();
+        poll.registry()
+            .register(&mut liocb, UDATA, Interest::LIO)
+            .expect("registration failed");
+        let mut liocb = Box::pin(liocb);
+        liocb.as_mut().submit().unwrap();
+
+        // Each chunk delivers its own event
+        while !liocb.is_complete() {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert_eq!(ev.token(), UDATA);
+                assert!(ev.is_lio());
+            }
+        }
+        let results = liocb.as_mut().into_results().unwrap();
+        assert_eq!(results, vec![Ok(WBUF.len()); nops]);
+        assert!(!liocb.is_complete());
+        drop(liocb);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF.repeat(nops));
+    }
+
+    /// Every chunk's LIO event should arrive, even though each chunk's list is
+    /// built separately.
+    #[test]
+    fn one_event_per_chunk() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut builder = LioCbBuilder::with_capacity(5).max_chunk(2);
+        for (i, wbuf) in [b"ab", b"cd", b"ef", b"gh", b"ij"].iter().enumerate()
+        {
+            builder = builder.write_at(f.as_fd(), 2 * i as u64, *wbuf, 0);
+        }
+        let mut liocb = builder.finish();
+        poll.registry()
+            .register(&mut liocb, UDATA, Interest::LIO)
+            .expect("registration failed");
+        let mut liocb = Box::pin(liocb);
+        liocb.as_mut().submit().unwrap();
+
+        let mut nevents = 0;
+        while nevents < 3 {
+            poll.poll(&mut events, Some(Duration::from_secs(5)))
+                .expect("poll failed");
+            assert!(!events.is_empty(), "timed out with {nevents} events");
+            for ev in events.iter() {
+                assert_eq!(ev.token(), UDATA);
+                assert!(ev.is_lio());
+                nevents += 1;
+            }
+        }
+        assert!(liocb.is_complete());
+        assert_eq!(liocb.as_mut().into_results(), Ok(vec![Ok(2); 5]));
+        poll.poll(&mut events, Some(Duration::from_millis(10)))
+            .expect("poll failed");
+        assert!(events.is_empty());
+    }
+
+    /// submit_wait should split a batch, too
+    #[test]
+    fn submit_wait_chunked() {
+        let mut f = tempfile().unwrap();
+        let mut builder = LioCbBuilder::with_capacity(5).max_chunk(2);
+        for (i, wbuf) in [b"ab", b"cd", b"ef", b"gh", b"ij"].iter().enumerate()
+        {
+            builder = builder.write_at(f.as_fd(), 2 * i as u64, *wbuf, 0);
+        }
+        let mut liocb = Box::pin(builder.finish());
+        assert_eq!(liocb.as_mut().submit_wait(), Ok(vec![Ok(2); 5]));
+        drop(liocb);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abcdefghij");
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    /// Counters are process-wide and tests run concurrently, so only check
+    /// that they increased.
+    #[test]
+    fn write_at() {
+        let f = tempfile().unwrap();
+        let before = mio_aio::snapshot();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        assert_eq!(aiow.as_mut().submit_and_wait_blocking(), Ok(3));
+        let after = mio_aio::snapshot();
+        assert!(after.submitted > before.submitted);
+        assert!(after.completed > before.completed);
+    }
+
+    /// A negative offset is rejected by the kernel at submission
+    #[test]
+    fn submit_error() {
+        let f = tempfile().unwrap();
+        let before = mio_aio::snapshot();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), u64::MAX, b"abc", 0));
+        assert_eq!(
+            aiow.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::EINVAL))
+        );
+        let after = mio_aio::snapshot();
+        assert!(after.errored > before.errored);
+    }
+
+    /// Latency should be measured from submission, and freeze once reaped
+    #[test]
+    fn elapsed() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        assert_eq!(aiow.elapsed(), None);
+        assert_eq!(aiow.as_mut().submit_and_wait_blocking(), Ok(3));
+        let latency = aiow.elapsed().unwrap();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(aiow.elapsed(), Some(latency));
+    }
+}
+
+mod misuse_policy {
+    use super::*;
+
+    /// By default, misuse should return errors rather than panic
+    #[test]
+    fn lenient() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        assert_eq!(
+            mio_aio::MisusePolicy::get(),
+            mio_aio::MisusePolicy::Lenient
+        );
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        assert_eq!(
+            aiow.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::EINVAL))
+        );
+        aiow.as_mut().submit().unwrap();
+        assert_eq!(
+            aiow.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::EBUSY))
+        );
+
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+
+    /// Every method that needs in-kernel state should fail cleanly before
+    /// submission
+    #[test]
+    fn before_submit() {
+        let f = tempfile().unwrap();
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        let mut aiof = Box::pin(aiof);
+        assert_eq!(
+            aiof.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::EINVAL))
+        );
+        assert_eq!(
+            aiof.as_mut().error(),
+            Err(AioError::Return(mio_aio::Errno::EINVAL))
+        );
+        assert_eq!(
+            aiof.as_mut().cancel(),
+            Err(AioError::Cancel(mio_aio::Errno::EINVAL))
+        );
+    }
+
+    /// The state should advance through every phase of the lifecycle
+    #[test]
+    fn lifecycle() {
+        use mio_aio::State;
+
+        let f = tempfile().unwrap();
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        let mut aiof = Box::pin(aiof);
+        assert_eq!(aiof.state(), State::Idle);
+        aiof.as_mut().submit().unwrap();
+        assert_eq!(aiof.state(), State::InProgress);
+        aiof.suspend(None).unwrap();
+        aiof.as_mut().error().unwrap();
+        assert_eq!(aiof.state(), State::Completed);
+        aiof.as_mut().aio_return().unwrap();
+        assert_eq!(aiof.state(), State::Idle);
+        assert_eq!(
+            aiof.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::EINVAL))
+        );
+    }
+}
+
+mod notify_mode {
+    use std::time::Duration;
+
+    use mio_aio::NotifyMode;
+
+    use super::*;
+
+    /// A level-triggered operation notifies until it's reaped, and notifies
+    /// again after being reset and resubmitted on the same registration.
+    #[test]
+    fn level() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0)
+            .with_notify_mode(NotifyMode::Level)
+            .register_pinned(poll.registry(), UDATA)
+            .unwrap();
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        poll.poll(&mut events, Some(Duration::ZERO))
+            .expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+        poll.poll(&mut events, Some(Duration::ZERO))
+            .expect("poll failed");
+        assert!(events.is_empty());
+
+        aiow.as_mut().reset_write_at(6, WBUF, 0).unwrap();
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+
+    /// A cleared operation notifies once per submission
+    #[test]
+    fn clear() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0)
+            .with_notify_mode(NotifyMode::Clear)
+            .register_pinned(poll.registry(), UDATA)
+            .unwrap();
+        for offs in [0, 6] {
+            aiow.as_mut().reset_write_at(offs, WBUF, 0).unwrap();
+            aiow.as_mut().submit().unwrap();
+            poll.poll(&mut events, None).expect("poll failed");
+            assert_eq!(events.iter().next().unwrap().token(), UDATA);
+            poll.poll(&mut events, Some(Duration::ZERO))
+                .expect("poll failed");
+            assert!(events.is_empty());
+            assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+        }
+    }
+
+    /// A dispatched operation notifies once per submission, without being
+    /// re-registered
+    #[test]
+    fn dispatch() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0)
+            .with_notify_mode(NotifyMode::Dispatch)
+            .register_pinned(poll.registry(), UDATA)
+            .unwrap();
+        for offs in [0, 6] {
+            aiow.as_mut().reset_write_at(offs, WBUF, 0).unwrap();
+            aiow.as_mut().submit().unwrap();
             poll.poll(&mut events, None).expect("poll failed");
             let mut it = events.iter();
             let ev = it.next().unwrap();
             assert_eq!(ev.token(), UDATA);
             assert!(ev.is_aio());
+            assert!(it.next().is_none());
+            poll.poll(&mut events, Some(Duration::ZERO))
+                .expect("poll failed");
+            assert!(events.is_empty());
+            assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+        }
+    }
+
+    /// Composite operations honor the mode too.  Each level-triggered stage
+    /// notifies until it's reaped.
+    #[test]
+    fn write_then_fsync_level() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_then_fsync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        )
+        .with_notify_mode(NotifyMode::Level);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        let written = loop {
+            poll.poll(&mut events, None).expect("poll failed");
+            assert_eq!(events.iter().next().unwrap().token(), UDATA);
+            poll.poll(&mut events, Some(Duration::ZERO))
+                .expect("poll failed");
+            assert_eq!(events.iter().next().unwrap().token(), UDATA);
+            match aiow.as_mut().aio_return() {
+                Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => continue,
+                r => break r.unwrap(),
+            }
+        };
+        assert_eq!(written, WBUF.len());
+        poll.poll(&mut events, Some(Duration::ZERO))
+            .expect("poll failed");
+        assert!(events.is_empty());
+    }
+}
+
+mod opcode {
+    use mio_aio::Opcode;
+
+    use super::*;
+
+    #[test]
+    fn predicates() {
+        assert!(Opcode::Read.is_read());
+        assert!(Opcode::Readv.is_read());
+        assert!(Opcode::Write.is_write());
+        assert!(Opcode::Writev.is_write());
+        assert!(Opcode::Fsync.is_sync());
+        assert!(!Opcode::Read.is_write());
+        assert!(!Opcode::Write.is_sync());
+        assert!(!Opcode::Fsync.is_read());
+    }
+
+    #[test]
+    fn source() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0u8; 16];
+        let aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0);
+        assert_eq!(aior.opcode(), Opcode::Read);
+        let aiof =
+            mio_aio::Source::fsync(f.as_fd(), mio_aio::AioFsyncMode::O_SYNC, 0);
+        assert_eq!(aiof.opcode(), Opcode::Fsync);
+    }
+}
+
+mod pinned {
+    use super::*;
+
+    #[test]
+    fn register_pinned() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0)
+            .register_pinned(poll.registry(), UDATA)
+            .unwrap();
+        assert!(aiow.is_registered());
+        aiow.as_mut().submit().unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let ev = events.iter().next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_aio());
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+
+    #[test]
+    fn into_pinned_box() {
+        let f = tempfile().unwrap();
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0)
+            .into_pinned_box();
+        assert_eq!(aiow.as_mut().submit_and_wait_blocking(), Ok(3));
+    }
+}
+
+mod plan_vectored_writes {
+    use super::*;
+
+    /// Write more buffers than IOV_MAX allows in a single operation
+    #[test]
+    fn many() {
+        let data: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        let wbufs: Vec<IoSlice> = data.chunks(1).map(IoSlice::new).collect();
+        let mut f = tempfile().unwrap();
+        let mut rbuf = Vec::new();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let ops = mio_aio::plan_vectored_writes(f.as_fd(), 0, &wbufs, 0);
+            assert!(ops.len() > 1);
+            let mut ops = ops
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut op)| {
+                    poll.registry()
+                        .register(&mut op, Token(i), Interest::AIO)
+                        .expect("registration failed");
+                    Box::pin(op)
+                })
+                .collect::<Vec<_>>();
+            for op in ops.iter_mut() {
+                op.as_mut().submit().unwrap();
+            }
+
+            let mut written = 0;
+            let mut remaining = ops.len();
+            while remaining > 0 {
+                poll.poll(&mut events, None).expect("poll failed");
+                for ev in events.iter() {
+                    let op = &mut ops[usize::from(ev.token())];
+                    written += op.as_mut().aio_return().unwrap();
+                    remaining -= 1;
+                }
+            }
+            assert_eq!(written, data.len());
+        }
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, data);
+    }
+}
+
+/// Compare partial transfers against a `pread`/`pwrite` oracle.
+mod poll_state {
+    use mio_aio::AioState;
+
+    use super::*;
+
+    #[test]
+    fn read_at() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        f.write_all(WBUF).unwrap();
+        let mut rbuf = vec![0; WBUF.len()];
+        let mut aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0)
+            .into_pinned_box();
+        aior.as_mut().submit().unwrap();
+        loop {
+            match aior.as_mut().poll_state() {
+                AioState::Pending => std::thread::yield_now(),
+                AioState::Ready => break,
+                AioState::Failed(e) => panic!("read failed: {}", e),
+            }
+        }
+        assert_eq!(aior.as_mut().aio_return(), Ok(WBUF.len()));
+        drop(aior);
+        assert_eq!(rbuf, WBUF);
+    }
+
+    #[test]
+    fn before_submit() {
+        let f = tempfile().unwrap();
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0)
+            .into_pinned_box();
+        assert_eq!(
+            aiow.as_mut().poll_state(),
+            AioState::Failed(mio_aio::Errno::EINVAL)
+        );
+    }
+}
+
+mod proptests {
+    use std::os::unix::fs::FileExt;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Submit an operation and wait for it to complete.
+    fn run<S>(mut op: S) -> Result<S::Output, AioError>
+    where
+        S: SourceApi + mio::event::Source,
+    {
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        poll.registry()
+            .register(&mut op, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut op = Box::pin(op);
+        op.as_mut().submit()?;
+        poll.poll(&mut events, None).expect("poll failed");
+        op.as_mut().aio_return()
+    }
+
+    /// Create a file with the given contents.
+    fn file_with(contents: &[u8]) -> std::fs::File {
+        let mut f = tempfile().unwrap();
+        f.write_all(contents).unwrap();
+        f
+    }
+
+    /// Read a file's entire contents.
+    fn contents(mut f: &std::fs::File) -> Vec<u8> {
+        let mut v = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut v).unwrap();
+        v
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// Reads may be short or empty at or beyond EOF
+        #[test]
+        fn read_at(
+            initial in prop::collection::vec(any::<u8>(), 0..2048),
+            offs in 0u64..3072,
+            len in 0usize..2048,
+        ) {
+            let f = file_with(&initial);
+            let mut rbuf = vec![0u8; len];
+            let mut expected = vec![0u8; len];
+            let expected_len = f.read_at(&mut expected, offs).unwrap();
+
+            let n = run(mio_aio::Source::read_at(f.as_fd(), offs, &mut rbuf, 0))
+                .unwrap();
+            prop_assert_eq!(n, expected_len);
+            prop_assert_eq!(&rbuf[..n], &expected[..n]);
+        }
+
+        /// Short vectored reads may end in the middle of any iovec
+        #[test]
+        fn readv_at(
+            initial in prop::collection::vec(any::<u8>(), 0..2048),
+            offs in 0u64..3072,
+            lens in prop::collection::vec(0usize..256, 1..16),
+        ) {
+            let f = file_with(&initial);
+            let total = lens.iter().sum();
+            let mut expected = vec![0u8; total];
+            let expected_len = f.read_at(&mut expected, offs).unwrap();
+
+            let mut rbufs: Vec<Vec<u8>> =
+                lens.iter().map(|len| vec![0u8; *len]).collect();
+            let n = {
+                let mut iovs: Vec<IoSliceMut> = rbufs
+                    .iter_mut()
+                    .map(|b| IoSliceMut::new(&mut b[..]))
+                    .collect();
+                run(mio_aio::Source::readv_at(f.as_fd(), offs, &mut iovs, 0))
+                    .unwrap()
+            };
+            prop_assert_eq!(n, expected_len);
+            let actual = rbufs.concat();
+            prop_assert_eq!(&actual[..n], &expected[..n]);
+        }
+
+        /// Writes may extend the file, or leave a hole before them
+        #[test]
+        fn write_at(
+            initial in prop::collection::vec(any::<u8>(), 0..2048),
+            offs in 0u64..3072,
+            wbuf in prop::collection::vec(any::<u8>(), 0..2048),
+        ) {
+            let f = file_with(&initial);
+            let oracle = file_with(&initial);
+            oracle.write_all_at(&wbuf, offs).unwrap();
+
+            let n = run(mio_aio::Source::write_at(f.as_fd(), offs, &wbuf, 0))
+                .unwrap();
+            prop_assert_eq!(n, wbuf.len());
+            prop_assert_eq!(contents(&f), contents(&oracle));
+        }
+
+        /// Vectored writes must place every iovec, including empty ones,
+        /// contiguously
+        #[test]
+        fn writev_at(
+            initial in prop::collection::vec(any::<u8>(), 0..2048),
+            offs in 0u64..3072,
+            wbufs in prop::collection::vec(
+                prop::collection::vec(any::<u8>(), 0..256),
+                1..16
+            ),
+        ) {
+            let f = file_with(&initial);
+            let oracle = file_with(&initial);
+            let data = wbufs.concat();
+            oracle.write_all_at(&data, offs).unwrap();
+
+            let iovs: Vec<IoSlice> =
+                wbufs.iter().map(|b| IoSlice::new(b)).collect();
+            let n = run(mio_aio::Source::writev_at(f.as_fd(), offs, &iovs, 0))
+                .unwrap();
+            prop_assert_eq!(n, data.len());
+            prop_assert_eq!(contents(&f), contents(&oracle));
+        }
+    }
+}
+
+mod quota {
+    use mio_aio::AioQuota;
+
+    use super::*;
+
+    /// Once every permit is held, the next submission must wait for one to
+    /// be released.
+    #[test]
+    fn gated() {
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let quota = AioQuota::new(2);
+
+        let mut ops = (0..2)
+            .map(|i| {
+                let mut aiof = mio_aio::Source::fsync(
+                    f.as_fd(),
+                    mio_aio::AioFsyncMode::O_SYNC,
+                    0,
+                );
+                poll.registry()
+                    .register(&mut aiof, Token(i), Interest::AIO)
+                    .expect("registration failed");
+                let mut aiof = Box::pin(aiof);
+                let permit = quota.try_acquire().unwrap();
+                aiof.as_mut().submit_with_permit(permit).unwrap();
+                aiof
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(quota.available(), 0);
+        assert!(quota.try_acquire().is_none());
+
+        // Reaping one operation releases its permit.
+        poll.poll(&mut events, None).expect("poll failed");
+        let i = events.iter().next().unwrap().token().0;
+        ops[i].as_mut().aio_return().unwrap();
+        assert_eq!(quota.available(), 1);
+
+        // Dropping the other releases its permit, too.
+        drop(ops);
+        assert_eq!(quota.available(), 2);
+    }
+
+    #[test]
+    fn from_limits() {
+        let quota = AioQuota::from_limits();
+        assert_eq!(quota.limit(), mio_aio::aio_limits().max_queue_per_proc);
+        assert_eq!(quota.available(), quota.limit());
+    }
+}
+
+mod read_at_indexed {
+    use super::*;
+
+    #[test]
+    fn ok() {
+        const INITIAL: &[u8] = b"abcdef123456";
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut slab = vec![[0u8; 4]; 3];
+
+        let mut aior =
+            mio_aio::Source::read_at_indexed(f.as_fd(), 2, &mut slab[1], 1, 0);
+        assert_eq!(aior.index(), 1);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(aior.as_mut().aio_return(), Ok((1, 4)));
+        drop(aior);
+        assert_eq!(&slab[1], b"cdef");
+    }
+}
+
+mod read_into {
+    use std::{fs::OpenOptions, os::unix::io::AsRawFd};
+
+    use super::*;
+
+    /// A failed read can be taken apart and retried with the same buffer
+    #[test]
+    fn into_retry_parts() {
+        const INITIAL: &[u8] = b"abcdef";
+        // Reading from a write-only file fails
+        let wo = OpenOptions::new().write(true).open("/dev/null").unwrap();
+        let mut aior =
+            Box::pin(mio_aio::Source::read_into(wo.as_fd(), 2, vec![0; 4], 0));
+        assert_eq!(
+            aior.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::EBADF))
+        );
+        let (buf, fd, offs, prio) = aior.into_retry_parts().unwrap();
+        assert_eq!(buf.len(), 4);
+        assert_eq!(fd.as_raw_fd(), wo.as_raw_fd());
+        assert_eq!(offs, 2);
+        assert_eq!(prio, 0);
+
+        // Retry with a readable file
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+        let mut aior =
+            Box::pin(mio_aio::Source::read_into(f.as_fd(), offs, buf, prio));
+        aior.as_mut().submit().unwrap();
+        while aior.as_mut().error()
+            == Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let (buf, nbytes) = aior.as_mut().aio_return().unwrap();
+        assert_eq!(&buf[..nbytes], b"cdef");
+
+        // Once the buffer is reclaimed, there's nothing to retry
+        assert_eq!(
+            aior.into_retry_parts().unwrap_err(),
+            mio_aio::Errno::EINVAL
+        );
+    }
+
+    /// The buffer should be usable after the operation is gone
+    #[test]
+    fn ok() {
+        const INITIAL: &[u8] = b"abcdef123456";
+        let mut f = tempfile().unwrap();
+        f.write_all(INITIAL).unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aior = mio_aio::Source::read_into(f.as_fd(), 2, vec![0; 4], 0);
+        poll.registry()
+            .register(&mut aior, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aior = Box::pin(aior);
+        aior.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let (rbuf, nbytes) = aior.as_mut().aio_return().unwrap();
+        assert_eq!(
+            aior.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::EINVAL))
+        );
+        drop(aior);
+        assert_eq!(nbytes, 4);
+        assert_eq!(&rbuf[..], b"cdef");
+    }
+}
+
+mod register_kq {
+    use std::os::unix::io::AsRawFd;
+
+    use nix::sys::event::{EventFilter, EventFlag, FilterFlag, KEvent, Kqueue};
+
+    use super::*;
+
+    /// An operation can notify a kqueue that isn't managed by Mio
+    #[test]
+    fn custom_kqueue() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let kq = Kqueue::new().unwrap();
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        aiow.register_kq(kq.as_fd().as_raw_fd(), 42).unwrap();
+        assert!(aiow.is_registered());
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        let mut evs = [KEvent::new(
+            0,
+            EventFilter::EVFILT_AIO,
+            EventFlag::empty(),
+            FilterFlag::empty(),
+            0,
+            0,
+        )];
+        assert_eq!(kq.kevent(&[], &mut evs, None).unwrap(), 1);
+        assert_eq!(evs[0].filter().unwrap(), EventFilter::EVFILT_AIO);
+        assert_eq!(evs[0].udata(), 42);
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+
+    /// register_kq can steer a Mio-registered source to a different kqueue
+    #[test]
+    fn overrides_registry() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let kq = Kqueue::new().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        aiow.register_kq(kq.as_fd().as_raw_fd(), 42).unwrap();
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        let mut evs = [KEvent::new(
+            0,
+            EventFilter::EVFILT_AIO,
+            EventFlag::empty(),
+            FilterFlag::empty(),
+            0,
+            0,
+        )];
+        assert_eq!(kq.kevent(&[], &mut evs, None).unwrap(), 1);
+        assert_eq!(evs[0].udata(), 42);
+        poll.poll(&mut events, Some(std::time::Duration::ZERO))
+            .expect("poll failed");
+        assert!(events.is_empty());
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+
+    /// The completion event alone tells whether the operation succeeded
+    #[test]
+    fn completion_status() {
+        use mio_aio::CompletionStatus;
+
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let kq = Kqueue::new().unwrap();
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        aiow.register_kq(kq.as_fd().as_raw_fd(), 42).unwrap();
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        let other = KEvent::new(
+            0,
+            EventFilter::EVFILT_AIO,
+            EventFlag::empty(),
+            FilterFlag::empty(),
+            0,
+            0,
+        );
+        let mut evs = [other];
+        assert_eq!(kq.kevent(&[], &mut evs, None).unwrap(), 1);
+        assert_eq!(
+            aiow.completion_status(&evs[0]),
+            CompletionStatus::Succeeded
+        );
+        assert_eq!(aiow.completion_status(&other), CompletionStatus::Unrelated);
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+}
+
+mod aio_limits {
+    #[test]
+    fn positive() {
+        let limits = mio_aio::aio_limits();
+        assert!(limits.listio_max > 0);
+        assert!(limits.max_queue_per_proc > 0);
+        assert!(limits.max_queue > 0);
+        assert!(limits.max_queue_per_proc <= limits.max_queue);
+    }
+}
+
+mod required_alignment {
+    use super::*;
+
+    #[test]
+    fn regular_file() {
+        let f = tempfile().unwrap();
+        let align = mio_aio::required_alignment(f.as_fd()).unwrap();
+        assert!(align.is_power_of_two());
+    }
+}
+
+mod aligned {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+
+    use mio_aio::AlignedBuf;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    /// Open a temporary file with O_DIRECT, if the file system supports it.
+    fn open_direct() -> Option<(NamedTempFile, std::fs::File)> {
+        let nf = NamedTempFile::new().unwrap();
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(nix::libc::O_DIRECT)
+            .open(nf.path())
+            .ok()?;
+        Some((nf, f))
+    }
+
+    #[test]
+    fn aligned_buf() {
+        let buf = AlignedBuf::new(1000, 512).unwrap();
+        assert_eq!(buf.len(), 1000);
+        assert_eq!(buf.align(), 512);
+        assert_eq!(buf.as_ptr() as usize % 512, 0);
+        assert!(buf.iter().all(|b| *b == 0));
+        assert_eq!(
+            AlignedBuf::new(1000, 500).err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+        assert!(AlignedBuf::new(0, 512).unwrap().is_empty());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let Some((_nf, f)) = open_direct() else {
+            eprintln!("O_DIRECT not supported.  Skipping test.");
+            return;
+        };
+        let mut wbuf = AlignedBuf::for_fd(f.as_fd(), 1).unwrap();
+        let len = wbuf.len();
+        wbuf.fill(b'x');
+        let mut aiow = Box::pin(
+            mio_aio::Source::write_at_aligned(f.as_fd(), 0, &wbuf, 0).unwrap(),
+        );
+        assert_eq!(aiow.as_mut().submit_and_wait_blocking(), Ok(len));
+        drop(aiow);
+
+        let mut rbuf = AlignedBuf::for_fd(f.as_fd(), len).unwrap();
+        let mut aior = Box::pin(
+            mio_aio::Source::read_at_aligned(f.as_fd(), 0, &mut rbuf, 0)
+                .unwrap(),
+        );
+        assert_eq!(aior.as_mut().submit_and_wait_blocking(), Ok(len));
+        drop(aior);
+        assert_eq!(&rbuf[..], &wbuf[..]);
+    }
+
+    #[test]
+    fn misaligned() {
+        let Some((_nf, f)) = open_direct() else {
+            eprintln!("O_DIRECT not supported.  Skipping test.");
+            return;
+        };
+        let mut buf = AlignedBuf::for_fd(f.as_fd(), 2).unwrap();
+        let len = buf.len();
+        assert!(len > 1);
+        // Misaligned offset
+        assert_eq!(
+            mio_aio::Source::write_at_aligned(f.as_fd(), 1, &buf, 0).err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+        // Misaligned length
+        assert_eq!(
+            mio_aio::Source::write_at_aligned(f.as_fd(), 0, &buf[..len - 1], 0)
+                .err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+        // Misaligned address
+        assert_eq!(
+            mio_aio::Source::read_at_aligned(f.as_fd(), 0, &mut buf[1..], 0)
+                .err(),
+            Some(mio_aio::Errno::EINVAL)
+        );
+    }
+}
+
+mod supports_vectored {
+    /// Every supported host runs FreeBSD 13.0 or later
+    #[test]
+    fn supported() {
+        assert!(mio_aio::supports_vectored());
+        // The cached result should be the same
+        assert!(mio_aio::supports_vectored());
+    }
+}
+
+mod send_file {
+    use std::{os::unix::net::UnixStream, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn ok() {
+        const DATA: &[u8] = b"abcdef123456";
+        let mut f = tempfile().unwrap();
+        f.write_all(DATA).unwrap();
+        let (tx, mut rx) = UnixStream::pair().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut sf = mio_aio::SendFile::new(f.as_fd(), 2, 8, tx.as_fd());
+        poll.registry()
+            .register(&mut sf, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut sf = Box::pin(sf);
+        sf.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_readable());
+        assert_eq!(sf.as_mut().aio_return().unwrap(), 8);
+
+        let mut rbuf = [0u8; 8];
+        rx.read_exact(&mut rbuf).unwrap();
+        assert_eq!(&rbuf, &DATA[2..10]);
+    }
+
+    /// Deregistering should withdraw an undelivered notification
+    #[test]
+    fn deregister() {
+        let f = tempfile().unwrap();
+        let (tx, _rx) = UnixStream::pair().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut sf = mio_aio::SendFile::new(f.as_fd(), 0, 0, tx.as_fd());
+        poll.registry()
+            .register(&mut sf, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut sf = Box::pin(sf);
+        sf.as_mut().submit().unwrap();
+        while sf.as_mut().error()
+            == Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+        poll.registry().deregister(&mut *sf).unwrap();
+        poll.poll(&mut events, Some(Duration::from_millis(10)))
+            .expect("poll failed");
+        assert!(events.is_empty());
+        assert_eq!(sf.as_mut().aio_return(), Ok(0));
+    }
+
+    #[test]
+    fn wrong_interest() {
+        let f = tempfile().unwrap();
+        let (tx, _rx) = UnixStream::pair().unwrap();
+        let poll = Poll::new().unwrap();
+        let mut sf = mio_aio::SendFile::new(f.as_fd(), 0, 1, tx.as_fd());
+        let e = poll
+            .registry()
+            .register(&mut sf, UDATA, Interest::READABLE)
+            .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    /// When the destination isn't a socket, it should fall back to copying
+    #[test]
+    fn fallback() {
+        const DATA: &[u8] = b"abcdef123456";
+        let mut f = tempfile().unwrap();
+        f.write_all(DATA).unwrap();
+        let mut dst = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut sf = mio_aio::SendFile::new(f.as_fd(), 0, 100, dst.as_fd());
+        poll.registry()
+            .register(&mut sf, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut sf = Box::pin(sf);
+        sf.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(events.iter().next().unwrap().token(), UDATA);
+        // Short copy, because the source hit EOF
+        assert_eq!(sf.as_mut().aio_return().unwrap(), DATA.len());
+        drop(sf);
+
+        let mut rbuf = Vec::new();
+        dst.rewind().unwrap();
+        dst.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, DATA);
+    }
+}
+
+mod submit_observed {
+    use std::os::unix::io::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn write() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut descs = Vec::new();
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 42, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut()
+            .submit_observed(&mut |desc| descs.push(desc))
+            .unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        aiow.as_mut().aio_return().unwrap();
 
-            assert!(aiow.as_mut().error().is_ok());
-            assert_eq!(aiow.as_mut().aio_return().unwrap(), expected.len());
-            assert!(it.next().is_none());
+        assert_eq!(descs.len(), 1);
+        assert_eq!(descs[0].fd, f.as_raw_fd());
+        assert_eq!(descs[0].offset, 42);
+        assert_eq!(descs[0].len, WBUF.len());
+        assert_eq!(descs[0].opcode, mio_aio::Opcode::Write);
+        assert_eq!(descs[0].priority, 0);
+    }
+
+    /// A vectored operation's length is the sum of its buffers
+    #[test]
+    fn writev() {
+        let f = tempfile().unwrap();
+        let mut descs = Vec::new();
+        let iovs = [IoSlice::new(b"abc"), IoSlice::new(b"defg")];
+
+        let mut aiow =
+            Box::pin(mio_aio::Source::writev_at(f.as_fd(), 0, &iovs, 0));
+        aiow.as_mut()
+            .submit_observed(&mut |desc| descs.push(desc))
+            .unwrap();
+        aiow.as_mut().suspend(None).unwrap();
+        aiow.as_mut().aio_return().unwrap();
+
+        assert_eq!(descs.len(), 1);
+        assert_eq!(descs[0].len, 7);
+        assert_eq!(descs[0].opcode, mio_aio::Opcode::Writev);
+    }
+}
+
+mod submit_all {
+    use std::pin::Pin;
+
+    use super::*;
+
+    #[test]
+    fn writes() {
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let bufs: [&[u8]; 3] = [b"abc", b"def", b"ghi"];
+        let mut aiows = bufs
+            .iter()
+            .enumerate()
+            .map(|(i, buf)| {
+                let offs = 3 * i as u64;
+                let mut aiow =
+                    mio_aio::Source::write_at(f.as_fd(), offs, buf, 0);
+                poll.registry()
+                    .register(&mut aiow, Token(i), Interest::AIO)
+                    .expect("registration failed");
+                Box::pin(aiow)
+            })
+            .collect::<Vec<_>>();
+
+        let mut pins = aiows.iter_mut().map(Pin::as_mut).collect::<Vec<_>>();
+        mio_aio::submit_all(&mut pins).unwrap();
+        drop(pins);
+        assert!(aiows.iter().all(|aiow| aiow.in_progress()));
+
+        let mut ndone = 0;
+        while ndone < bufs.len() {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert!(ev.is_aio());
+                let aiow = &mut aiows[usize::from(ev.token())];
+                assert_eq!(aiow.as_mut().aio_return(), Ok(3));
+                ndone += 1;
+            }
         }
+        assert!(aiows.iter().all(|aiow| !aiow.in_progress()));
+        drop(aiows);
+
+        let mut rbuf = Vec::new();
         f.rewind().unwrap();
-        let len = f.read_to_end(&mut rbuf).unwrap();
-        assert_eq!(len, expected.len());
-        assert_eq!(expected, &rbuf[..]);
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, b"abcdefghi");
+    }
+
+    /// Nothing is submitted if any operation is already in progress
+    #[test]
+    fn busy() {
+        let f = tempfile().unwrap();
+        let mut aiow0 =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        let mut aiow1 =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 3, b"def", 0));
+        aiow1.as_mut().submit().unwrap();
+        assert_eq!(
+            mio_aio::submit_all(&mut [aiow0.as_mut(), aiow1.as_mut()]),
+            Err(AioError::Submit(mio_aio::Errno::EBUSY))
+        );
+        assert!(!aiow0.in_progress());
+        aiow1.suspend(None).unwrap();
+        aiow1.as_mut().aio_return().unwrap();
+    }
+
+    #[test]
+    fn fsync() {
+        let f = tempfile().unwrap();
+        let mut aiof = Box::pin(mio_aio::Source::fsync(
+            f.as_fd(),
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        assert_eq!(
+            mio_aio::submit_all(&mut [aiof.as_mut()]),
+            Err(AioError::Submit(mio_aio::Errno::EINVAL))
+        );
+    }
+}
+
+mod suspend {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// After suspend returns, the kevent must already be pending.
+    #[test]
+    fn event_is_pending() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_at(f.as_fd(), 0, WBUF, 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        aiow.suspend(None).unwrap();
+        poll.poll(&mut events, Some(Duration::ZERO))
+            .expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_aio());
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+    }
+}
+
+mod try_submit {
+    use mio_aio::SubmitError;
+
+    use super::*;
+
+    /// Errors other than EAGAIN are fatal
+    #[test]
+    fn fatal() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        aiow.as_mut().abort_pending().unwrap();
+        assert_eq!(
+            aiow.as_mut().try_submit(),
+            Err(SubmitError::Fatal(mio_aio::Errno::ECANCELED))
+        );
+    }
+
+    #[test]
+    fn ok() {
+        let f = tempfile().unwrap();
+        let mut aiow =
+            Box::pin(mio_aio::Source::write_at(f.as_fd(), 0, b"abc", 0));
+        aiow.as_mut().try_submit().unwrap();
+        aiow.suspend(None).unwrap();
+        assert_eq!(aiow.as_mut().aio_return(), Ok(3));
+    }
+}
+
+mod with_priority {
+    use nix::sys::aio::Aio;
+
+    use super::*;
+
+    #[test]
+    fn ok() {
+        let f = tempfile().unwrap();
+        let mut rbuf = vec![0u8; 16];
+        let aior = mio_aio::Source::read_at(f.as_fd(), 0, &mut rbuf, 0)
+            .with_priority(3);
+        assert_eq!(aior.into_inner().priority(), 3);
+    }
+}
+
+mod write_at_verified {
+    use super::*;
+
+    /// A too-small scratch buffer is bad input
+    #[test]
+    fn short_scratch() {
+        let f = tempfile().unwrap();
+        let mut scratch = vec![0; 2];
+        let r = mio_aio::Source::write_at_verified(
+            f.as_fd(),
+            0,
+            b"abc",
+            &mut scratch,
+            0,
+        );
+        assert_eq!(r.err(), Some(mio_aio::Errno::EINVAL));
+    }
+
+    #[test]
+    fn ok() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut scratch = vec![0; WBUF.len()];
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_at_verified(
+            f.as_fd(),
+            0, //offset
+            WBUF,
+            &mut scratch,
+            0, //priority
+        )
+        .unwrap();
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+
+        aiow.as_mut().submit().unwrap();
+        let written = loop {
+            poll.poll(&mut events, None).expect("poll failed");
+            for ev in events.iter() {
+                assert_eq!(ev.token(), UDATA);
+            }
+            match aiow.as_mut().aio_return() {
+                Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => continue,
+                r => break r.unwrap(),
+            }
+        };
+        assert_eq!(written, WBUF.len());
+    }
+
+    /// It can't be submitted again during either stage
+    #[test]
+    fn busy() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut scratch = vec![0; WBUF.len()];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_at_verified(
+            f.as_fd(),
+            0,
+            WBUF,
+            &mut scratch,
+            0,
+        )
+        .unwrap();
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        let mut stages = 0;
+        let written = loop {
+            assert_eq!(
+                aiow.as_mut().submit(),
+                Err(AioError::Submit(mio_aio::Errno::EBUSY))
+            );
+            poll.poll(&mut events, None).expect("poll failed");
+            stages += events.iter().count();
+            match aiow.as_mut().aio_return() {
+                Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => continue,
+                r => break r.unwrap(),
+            }
+        };
+        assert_eq!(written, WBUF.len());
+        assert_eq!(stages, 2);
+    }
+
+    /// Dropping it during either stage should wait for that stage
+    #[test]
+    fn drop_in_progress() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut scratch = vec![0; WBUF.len()];
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        // During the write stage
+        let mut aiow = Box::pin(
+            mio_aio::Source::write_at_verified(
+                f.as_fd(),
+                0,
+                WBUF,
+                &mut scratch,
+                0,
+            )
+            .unwrap(),
+        );
+        aiow.as_mut().submit().unwrap();
+        drop(aiow);
+
+        // During the read stage
+        let mut aiow = mio_aio::Source::write_at_verified(
+            f.as_fd(),
+            0,
+            WBUF,
+            &mut scratch,
+            0,
+        )
+        .unwrap();
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(
+            aiow.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        );
+        drop(aiow);
+    }
+}
+
+mod write_from {
+    use std::os::unix::io::AsRawFd;
+
+    use super::*;
+
+    /// A failed write can be taken apart and retried with the same buffer
+    #[test]
+    fn into_retry_parts() {
+        const WBUF: &[u8] = b"abcdef";
+        // Writing to a read-only file fails
+        let ro = std::fs::File::open("/dev/null").unwrap();
+        let mut aiow = Box::pin(mio_aio::Source::write_from(
+            ro.as_fd(),
+            42,
+            Box::from(WBUF),
+            0,
+        ));
+        assert_eq!(
+            aiow.as_mut().submit(),
+            Err(AioError::Submit(mio_aio::Errno::EBADF))
+        );
+        let (buf, fd, offs, prio) = aiow.into_retry_parts().unwrap();
+        assert_eq!(&buf[..], WBUF);
+        assert_eq!(fd.as_raw_fd(), ro.as_raw_fd());
+        assert_eq!(offs, 42);
+        assert_eq!(prio, 0);
+    }
+
+    #[test]
+    fn ok() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow =
+            mio_aio::Source::write_from(f.as_fd(), 0, Box::from(WBUF), 0);
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        let (wbuf, nbytes) = aiow.as_mut().aio_return().unwrap();
+        assert_eq!(nbytes, WBUF.len());
+        assert_eq!(&wbuf[..], WBUF);
+        drop(aiow);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// The callback should run, and report the result, without polling.
+    #[test]
+    fn with_callback() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        mio_aio::Source::write_from_with_callback(
+            f.as_fd(),
+            0,
+            Box::from(WBUF),
+            0,
+            move |r| tx.send(r).unwrap(),
+        )
+        .unwrap();
+        let r = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(r, Ok(WBUF.len()));
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// A submission error is returned directly, and the callback never runs.
+    #[test]
+    fn with_callback_overflow() {
+        let f = tempfile().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel::<Result<usize, AioError>>();
+
+        let r = mio_aio::Source::write_from_with_callback(
+            f.as_fd(),
+            u64::MAX,
+            Box::from(&b"abcdef"[..]),
+            0,
+            move |r| tx.send(r).unwrap(),
+        );
+        assert_eq!(r, Err(AioError::Submit(mio_aio::Errno::EOVERFLOW)));
+        assert!(rx.recv().is_err());
+    }
+}
+
+mod write_then_fsync {
+    use super::*;
+
+    #[test]
+    fn ok() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let mut rbuf = Vec::new();
+
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        {
+            let mut aiow = mio_aio::Source::write_then_fsync(
+                f.as_fd(),
+                0, //offset
+                WBUF,
+                mio_aio::AioFsyncMode::O_SYNC,
+                0, //priority
+            );
+            poll.registry()
+                .register(&mut aiow, UDATA, Interest::AIO)
+                .expect("registration failed");
+            let mut aiow = Box::pin(aiow);
+
+            aiow.as_mut().submit().unwrap();
+
+            // The first event is for the write stage; the second is for the
+            // fsync stage.
+            let mut nevents = 0;
+            let written = loop {
+                poll.poll(&mut events, None).expect("poll failed");
+                for ev in events.iter() {
+                    assert_eq!(ev.token(), UDATA);
+                    assert!(ev.is_aio());
+                    nevents += 1;
+                }
+                match aiow.as_mut().aio_return() {
+                    Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => {
+                        continue
+                    }
+                    r => break r.unwrap(),
+                }
+            };
+            assert_eq!(written, WBUF.len());
+            assert_eq!(nevents, 2);
+        }
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// It can't be submitted again during either stage
+    #[test]
+    fn busy() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let mut aiow = mio_aio::Source::write_then_fsync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        );
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        let mut stages = 0;
+        let written = loop {
+            assert_eq!(
+                aiow.as_mut().submit(),
+                Err(AioError::Submit(mio_aio::Errno::EBUSY))
+            );
+            poll.poll(&mut events, None).expect("poll failed");
+            stages += events.iter().count();
+            match aiow.as_mut().aio_return() {
+                Err(AioError::Return(mio_aio::Errno::EINPROGRESS)) => continue,
+                r => break r.unwrap(),
+            }
+        };
+        assert_eq!(written, WBUF.len());
+        assert_eq!(stages, 2);
+    }
+
+    /// Dropping it during either stage should wait for that stage
+    #[test]
+    fn drop_in_progress() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        // During the write stage
+        let mut aiow = Box::pin(mio_aio::Source::write_then_fsync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        aiow.as_mut().submit().unwrap();
+        drop(aiow);
+
+        // During the fsync stage
+        let mut aiow = mio_aio::Source::write_then_fsync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        );
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+        poll.poll(&mut events, None).expect("poll failed");
+        assert_eq!(
+            aiow.as_mut().aio_return(),
+            Err(AioError::Return(mio_aio::Errno::EINPROGRESS))
+        );
+        drop(aiow);
+    }
+}
+
+mod write_then_sync {
+    use super::*;
+
+    /// Both stages should complete with a single event
+    #[test]
+    fn ok() {
+        const WBUF: &[u8] = b"abcdef";
+        let mut f = tempfile().unwrap();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let mut aiow = mio_aio::Source::write_then_sync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        );
+        poll.registry()
+            .register(&mut aiow, UDATA, Interest::AIO)
+            .expect("registration failed");
+        let mut aiow = Box::pin(aiow);
+        aiow.as_mut().submit().unwrap();
+
+        poll.poll(&mut events, None).expect("poll failed");
+        let mut it = events.iter();
+        let ev = it.next().unwrap();
+        assert_eq!(ev.token(), UDATA);
+        assert!(ev.is_aio());
+        assert!(it.next().is_none());
+        assert_eq!(aiow.as_mut().aio_return().unwrap(), WBUF.len());
+
+        // There should be no other event.
+        poll.poll(&mut events, Some(std::time::Duration::ZERO))
+            .expect("poll failed");
+        assert!(events.is_empty());
+        drop(aiow);
+
+        let mut rbuf = Vec::new();
+        f.rewind().unwrap();
+        f.read_to_end(&mut rbuf).unwrap();
+        assert_eq!(rbuf, WBUF);
+    }
+
+    /// Dropping it in progress should wait for both stages
+    #[test]
+    fn drop_in_progress() {
+        const WBUF: &[u8] = b"abcdef";
+        let f = tempfile().unwrap();
+        let mut aiow = Box::pin(mio_aio::Source::write_then_sync(
+            f.as_fd(),
+            0,
+            WBUF,
+            mio_aio::AioFsyncMode::O_SYNC,
+            0,
+        ));
+        aiow.as_mut().submit().unwrap();
+        drop(aiow);
     }
 }
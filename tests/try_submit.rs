@@ -0,0 +1,43 @@
+use std::{
+    io::Write,
+    os::unix::{io::AsFd, net::UnixStream},
+};
+
+use mio_aio::{SourceApi, State, SubmitError};
+
+/// Fill the AIO queue with reads from an empty socket, which won't complete
+/// until data arrives, and then check that the next submission would block.
+#[test]
+fn would_block() {
+    // Far more than any reasonable value of vfs.aio.max_aio_queue_per_proc
+    const LIMIT: usize = 65536;
+    let (rd, mut wr) = UnixStream::pair().unwrap();
+    let mut rbufs = vec![[0u8; 1]; LIMIT];
+    let mut pending = Vec::new();
+    let mut blocked = None;
+    for rbuf in rbufs.iter_mut() {
+        let mut aior =
+            Box::pin(mio_aio::Source::read_at(rd.as_fd(), 0, rbuf, 0));
+        match aior.as_mut().try_submit() {
+            Ok(()) => pending.push(aior),
+            Err(SubmitError::WouldBlock) => {
+                blocked = Some(aior);
+                break;
+            }
+            Err(e) => panic!("submit failed: {e}"),
+        }
+    }
+    let mut aior = blocked.expect("The AIO queue never filled up");
+    assert_eq!(aior.state(), State::Idle);
+
+    // Drain the queue, and then retry the same operation.
+    wr.write_all(&vec![0; pending.len()]).unwrap();
+    for mut aior in pending {
+        aior.suspend(None).unwrap();
+        assert_eq!(aior.as_mut().aio_return(), Ok(1));
+    }
+    aior.as_mut().try_submit().unwrap();
+    wr.write_all(&[0]).unwrap();
+    aior.suspend(None).unwrap();
+    assert_eq!(aior.as_mut().aio_return(), Ok(1));
+}
@@ -0,0 +1,33 @@
+use std::os::unix::io::AsFd;
+
+use mio_aio::{AioError, AioFsyncMode, Errno, SourceApi};
+use tempfile::tempfile;
+
+/// Operations submitted without registration can be drained by `wait_any`
+#[test]
+fn fsyncs() {
+    let f = tempfile().unwrap();
+    let mut aiofs = [
+        Box::pin(mio_aio::Source::fsync(f.as_fd(), AioFsyncMode::O_SYNC, 0)),
+        Box::pin(mio_aio::Source::fsync(f.as_fd(), AioFsyncMode::O_SYNC, 0)),
+    ];
+    for aiof in aiofs.iter_mut() {
+        aiof.as_mut().submit().unwrap();
+    }
+
+    let mut ndone = 0;
+    while ndone < aiofs.len() {
+        let reaped = mio_aio::wait_any(None).unwrap();
+        let aiof = aiofs
+            .iter_mut()
+            .find(|aiof| reaped.is_for(aiof))
+            .expect("reaped an unknown operation");
+        assert!(aiof.as_mut().reaped(&reaped).is_ok());
+        assert!(!aiof.in_progress());
+        ndone += 1;
+    }
+    assert_eq!(
+        mio_aio::wait_any(Some(std::time::Duration::ZERO)).map(drop),
+        Err(AioError::Return(Errno::EAGAIN))
+    );
+}